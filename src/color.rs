@@ -0,0 +1,57 @@
+//! ANSI coloring for stderr progress output
+//!
+//! Disabled unless stderr is a terminal, and always off with `--no-color` or a
+//! non-empty `NO_COLOR` environment variable. Output files are never colored.
+
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Decide once at startup whether stderr output is colored
+pub fn init(no_color: bool) {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = !no_color && !no_color_env && std::io::stderr().is_terminal();
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+fn paint(code: &str, text: impl Display) -> String {
+    if ENABLED.load(Ordering::Relaxed) {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Success counts and completed steps
+pub fn green(text: impl Display) -> String {
+    paint("32", text)
+}
+
+/// Warnings
+pub fn yellow(text: impl Display) -> String {
+    paint("33", text)
+}
+
+/// Errors and counts that need attention
+pub fn red(text: impl Display) -> String {
+    paint("31", text)
+}
+
+/// The `Warning:` prefix of a warning line
+pub fn warning() -> String {
+    yellow("Warning:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_color_is_plain() {
+        init(true);
+        assert_eq!(green(3), "3");
+        assert_eq!(warning(), "Warning:");
+    }
+}
@@ -0,0 +1,71 @@
+//! Per-model llama-server defaults loaded from a `--model-config` JSON file
+
+use crate::structs::{ModelConfig, ModelProfile, Result, ZError};
+use std::fs;
+use std::path::Path;
+
+impl ModelConfig {
+    /// Load a model config file
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be read or is not a valid model config
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            ZError::Config(format!("Invalid model config {}: {e}", path.display()))
+        })
+    }
+
+    /// Profile whose key equals the model's filename
+    #[must_use]
+    pub fn profile_for(&self, model_path: &Path) -> Option<&ModelProfile> {
+        let filename = model_path.file_name()?.to_str()?;
+        self.models.get(filename)
+    }
+}
+
+impl ModelProfile {
+    /// A CLI value if given, else this profile's value, else `default`
+    #[must_use]
+    pub fn resolve(cli: Option<u32>, profile: Option<u32>, default: u32) -> u32 {
+        cli.or(profile).unwrap_or(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_model_config() {
+        let dir = TempDir::new().expect("create temp dir");
+        let path = dir.path().join("models.json");
+        fs::write(
+            &path,
+            r#"{"models": {
+                "big.gguf": {"gpu_layers": 20, "context_size": 8192, "extra_args": ["-fa"]},
+                "small.gguf": {"context_size": 4096}
+            }}"#,
+        )
+        .expect("write config");
+
+        let config = ModelConfig::from_file(&path).expect("load config");
+        let big = config.profile_for(Path::new("/models/big.gguf")).expect("big profile");
+        assert_eq!(big.gpu_layers, Some(20));
+        assert_eq!(big.extra_args, vec!["-fa"]);
+
+        let small = config.profile_for(Path::new("small.gguf")).expect("small profile");
+        assert_eq!(small.gpu_layers, None);
+        assert!(small.extra_args.is_empty());
+        assert!(config.profile_for(Path::new("other.gguf")).is_none());
+
+        // CLI beats the profile, which beats the built-in default
+        assert_eq!(ModelProfile::resolve(Some(99), big.gpu_layers, 50), 99);
+        assert_eq!(ModelProfile::resolve(None, big.gpu_layers, 50), 20);
+        assert_eq!(ModelProfile::resolve(None, small.gpu_layers, 50), 50);
+
+        fs::write(&path, r#"{"models": {"x.gguf": {"gpu_layer": 1}}}"#).expect("write config");
+        assert!(ModelConfig::from_file(&path).is_err());
+    }
+}
@@ -1,14 +1,46 @@
 //! Context manager for lazy loading of ML output and instruction files
 
-use crate::structs::{FileInfo, FileType, Result, ZError};
-use std::cell::RefCell;
+use crate::structs::{ColumnStats, CsvData, FileInfo, FileType, Result, ZError};
 use std::collections::HashMap;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
 
 /// Size limits for 4GB VRAM constraint
 pub const MAX_FILE_CONTENT: usize = 2000;
 pub const MAX_CSV_ROWS: usize = 20;
+pub const MAX_READ_LINES: usize = 50;
+pub const MAX_CSV_SUMMARY: usize = 1500;
+pub const MAX_SEARCH_RESULTS: usize = 20;
+const MAX_SEARCH_SNIPPET: usize = 200;
+
+/// Name of the optional exclusion file in a context directory
+pub const IGNORE_FILE: &str = ".zignore";
+
+/// Join `name` onto `base`, refusing any name that would leave it: anything but a
+/// single plain file name (so no `..` or separators), or an existing file that
+/// resolves outside `base` through a symlink
+///
+/// # Errors
+/// Returns `ZError::Config` if the path escapes `base`, or an IO error if an
+/// existing path cannot be resolved
+pub fn resolve_within(base: &Path, name: &str) -> Result<PathBuf> {
+    let escapes = || ZError::Config(format!("Path escapes {}: {name}", base.display()));
+
+    let mut components = Path::new(name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(escapes());
+    }
+
+    let path = base.join(name);
+    if path.exists() && !path.canonicalize()?.starts_with(base.canonicalize()?) {
+        return Err(escapes());
+    }
+    Ok(path)
+}
 
 /// Create file info from a path
 fn file_info_from_path(path: &Path) -> Result<FileInfo> {
@@ -44,13 +76,30 @@ fn file_info_from_path(path: &Path) -> Result<FileInfo> {
 pub struct ContextManager {
     context_dir: PathBuf,
     file_index: Vec<FileInfo>,
-    // Lazy-loaded caches
-    loaded_files: RefCell<HashMap<String, String>>,
+    // Lazy-loaded caches (locked so read-only tools can run in parallel)
+    loaded_files: Mutex<HashMap<String, String>>,
 }
 
 impl ContextManager {
+    /// Context manager with no files, for running XML-only tools
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            context_dir: PathBuf::new(),
+            file_index: Vec::new(),
+            loaded_files: Mutex::default(),
+        }
+    }
+
     /// Create a context manager from a directory
     ///
+    /// Files matching the patterns in an optional `.zignore` file in the
+    /// directory are left out of the index. Supported subset of gitignore:
+    /// one pattern per line, `#` comments, `!` negation (last match wins),
+    /// `*`, `?` and `[...]` wildcards, and `\` escapes. Subdirectories are
+    /// never indexed, so `/` anchors are dropped and `dir/` patterns match
+    /// nothing. Hidden files stay skipped even when negated.
+    ///
     /// # Errors
     /// Returns error if directory cannot be read
     pub fn from_directory(dir: &Path) -> Result<Self> {
@@ -61,6 +110,13 @@ impl ContextManager {
             )));
         }
 
+        let ignore_path = dir.join(IGNORE_FILE);
+        let ignore = if ignore_path.is_file() {
+            parse_ignore_patterns(&fs::read_to_string(&ignore_path)?)
+        } else {
+            Vec::new()
+        };
+
         let mut file_index = Vec::new();
 
         for entry in fs::read_dir(dir)? {
@@ -78,8 +134,17 @@ impl ContextManager {
             {
                 continue;
             }
+            if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| is_ignored(n, &ignore))
+            {
+                continue;
+            }
 
-            match file_info_from_path(&path) {
+            let indexed = resolve_within(dir, &entry.file_name().to_string_lossy())
+                .and_then(|path| file_info_from_path(&path));
+            match indexed {
                 Ok(info) => file_index.push(info),
                 Err(e) => eprintln!("Warning: Could not index {}: {e}", path.display()),
             }
@@ -91,7 +156,7 @@ impl ContextManager {
         Ok(Self {
             context_dir: dir.to_path_buf(),
             file_index,
-            loaded_files: RefCell::new(HashMap::new()),
+            loaded_files: Mutex::new(HashMap::new()),
         })
     }
 
@@ -119,7 +184,12 @@ impl ContextManager {
     /// Returns error if file not found or cannot be read
     pub fn read_file(&self, filename: &str) -> Result<String> {
         // Check cache
-        if let Some(content) = self.loaded_files.borrow().get(filename) {
+        if let Some(content) = self
+            .loaded_files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(filename)
+        {
             return Ok(content.clone());
         }
 
@@ -129,15 +199,15 @@ impl ContextManager {
         }
 
         // Read file
-        let path = self.context_dir.join(filename);
+        let path = resolve_within(&self.context_dir, filename)?;
         let content = fs::read_to_string(&path)?;
 
         // Truncate if needed
-        let truncated = if content.len() > MAX_FILE_CONTENT {
+        let char_count = content.chars().count();
+        let truncated = if char_count > MAX_FILE_CONTENT {
             format!(
-                "{}...\n[Truncated: {} chars total]",
+                "{}...\n[Truncated: {char_count} chars total]",
                 truncate_string(&content, MAX_FILE_CONTENT),
-                content.len()
             )
         } else {
             content
@@ -145,12 +215,139 @@ impl ContextManager {
 
         // Cache it
         self.loaded_files
-            .borrow_mut()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
             .insert(filename.to_string(), truncated.clone());
 
         Ok(truncated)
     }
 
+    /// Read a window of lines from a file (1-based `start`, `count` clamped to `MAX_READ_LINES`)
+    ///
+    /// # Errors
+    /// Returns error if file not found or cannot be read
+    pub fn read_lines(&self, filename: &str, start: usize, count: usize) -> Result<String> {
+        use std::fmt::Write as _;
+
+        if !self.file_index.iter().any(|f| f.filename == filename) {
+            return Err(ZError::Config(format!("File not in context: {filename}")));
+        }
+
+        let path = resolve_within(&self.context_dir, filename)?;
+        let content = fs::read_to_string(&path)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let total = lines.len();
+
+        let start = start.max(1);
+        let count = count.clamp(1, MAX_READ_LINES);
+
+        if start > total {
+            return Ok(format!(
+                "[{filename} has {total} lines; start line {start} is past the end]"
+            ));
+        }
+
+        let end = (start + count - 1).min(total);
+        let mut result = format!("[Lines {start}-{end} of {total}]\n");
+        for (i, line) in lines[start - 1..end].iter().enumerate() {
+            let _ = writeln!(result, "{}: {line}", start + i);
+        }
+
+        Ok(result)
+    }
+
+    /// Read the content under a Markdown heading, up to the next heading of the
+    /// same or higher level (so subsections are included). `heading` matches
+    /// case-insensitively, with or without its leading `#`s. If no heading
+    /// matches, the available headings are listed instead.
+    ///
+    /// # Errors
+    /// Returns error if file not found, not Markdown, or cannot be read
+    pub fn read_section(&self, filename: &str, heading: &str) -> Result<String> {
+        use std::fmt::Write as _;
+
+        let info = self
+            .get_file_info(filename)
+            .ok_or_else(|| ZError::Config(format!("File not in context: {filename}")))?;
+        if info.file_type != FileType::Markdown {
+            return Err(ZError::Config(format!("{filename} is not a Markdown file")));
+        }
+
+        let content = fs::read_to_string(resolve_within(&self.context_dir, filename)?)?;
+        let lines: Vec<&str> = content.lines().collect();
+        let headings = markdown_headings(&lines);
+
+        let wanted = heading.trim_start_matches('#').trim();
+        let Some(pos) = headings
+            .iter()
+            .position(|(_, _, text)| text.eq_ignore_ascii_case(wanted))
+        else {
+            let mut result =
+                format!("Heading '{wanted}' not found in {filename}. Available headings:\n");
+            for (_, depth, text) in &headings {
+                let _ = writeln!(result, "{} {text}", "#".repeat(*depth));
+            }
+            return Ok(result);
+        };
+
+        let (start, depth, _) = headings[pos];
+        let end = headings[pos + 1..]
+            .iter()
+            .find(|(_, d, _)| *d <= depth)
+            .map_or(lines.len(), |(line, _, _)| *line);
+
+        let section = lines[start..end].join("\n");
+        let section = section.trim_end();
+        let char_count = section.chars().count();
+        if char_count > MAX_FILE_CONTENT {
+            return Ok(format!(
+                "{}...\n[Truncated: {char_count} chars total]",
+                truncate_string(section, MAX_FILE_CONTENT),
+            ));
+        }
+        Ok(section.to_string())
+    }
+
+    /// Search all indexed files for a substring, returning `(filename, line_no, snippet)`
+    /// with 1-based line numbers, capped at `MAX_SEARCH_RESULTS`
+    #[must_use]
+    pub fn search(&self, query: &str, case_insensitive: bool) -> Vec<(String, usize, String)> {
+        let needle = if case_insensitive {
+            query.to_lowercase()
+        } else {
+            query.to_string()
+        };
+
+        let mut matches = Vec::new();
+        for info in &self.file_index {
+            let Ok(content) = resolve_within(&self.context_dir, &info.filename)
+                .and_then(|path| Ok(fs::read_to_string(path)?))
+            else {
+                continue;
+            };
+
+            for (i, line) in content.lines().enumerate() {
+                let found = if case_insensitive {
+                    line.to_lowercase().contains(&needle)
+                } else {
+                    line.contains(&needle)
+                };
+                if found {
+                    matches.push((
+                        info.filename.clone(),
+                        i + 1,
+                        truncate_string(line.trim(), MAX_SEARCH_SNIPPET),
+                    ));
+                    if matches.len() >= MAX_SEARCH_RESULTS {
+                        return matches;
+                    }
+                }
+            }
+        }
+
+        matches
+    }
+
     /// Query rows from a CSV file
     ///
     /// # Errors
@@ -161,6 +358,39 @@ impl ContextManager {
         filter: Option<&str>,
         limit: Option<usize>,
     ) -> Result<String> {
+        let content = self.read_csv_text(filename)?;
+
+        let limit = limit.unwrap_or(MAX_CSV_ROWS).min(MAX_CSV_ROWS);
+
+        let mut lines = content.lines();
+        let Some(header) = lines.next() else {
+            return Ok(String::new());
+        };
+
+        let filtered: Vec<&str> =
+            lines.filter(|line| row_matches(line, filter)).take(limit).collect();
+
+        let mut result = String::from(header);
+        result.push('\n');
+        for line in filtered {
+            result.push_str(line);
+            result.push('\n');
+        }
+
+        Ok(result)
+    }
+
+    /// Number of data rows in a CSV file matching `filter`, as in `query_csv`
+    ///
+    /// # Errors
+    /// Returns error if file not found or not CSV
+    pub fn count_csv(&self, filename: &str, filter: Option<&str>) -> Result<usize> {
+        let content = self.read_csv_text(filename)?;
+        Ok(content.lines().skip(1).filter(|line| row_matches(line, filter)).count())
+    }
+
+    /// Raw text of a CSV context file
+    fn read_csv_text(&self, filename: &str) -> Result<String> {
         let info = self
             .get_file_info(filename)
             .ok_or_else(|| ZError::Config(format!("File not found: {filename}")))?;
@@ -169,39 +399,178 @@ impl ContextManager {
             return Err(ZError::Config(format!("{filename} is not a CSV file")));
         }
 
-        let path = self.context_dir.join(filename);
-        let content = fs::read_to_string(&path)?;
+        Ok(fs::read_to_string(resolve_within(&self.context_dir, filename)?)?)
+    }
+
+    /// Inner join of two CSV context files on a shared key column, as CSV text.
+    /// Output columns are the left file's, then the right file's except the key;
+    /// right columns whose name clashes are prefixed with the file stem (`anomalies.score`).
+    /// `filter` keeps joined rows containing the text, as in `query_csv`.
+    ///
+    /// # Errors
+    /// Returns error if either file isn't a CSV or lacks the key column
+    pub fn join_csv(
+        &self,
+        left: &str,
+        right: &str,
+        key: &str,
+        filter: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<String> {
+        use std::fmt::Write as _;
+
+        let left_data = self.load_csv(left)?;
+        let right_data = self.load_csv(right)?;
+        let left_key = csv_column_index(&left_data, left, key)?;
+        let right_key = csv_column_index(&right_data, right, key)?;
 
         let limit = limit.unwrap_or(MAX_CSV_ROWS).min(MAX_CSV_ROWS);
 
-        let mut lines: Vec<&str> = content.lines().collect();
-        if lines.is_empty() {
-            return Ok(String::new());
+        // Hash the right side, keeping its row order per key
+        let mut index: HashMap<&str, Vec<&Vec<String>>> = HashMap::new();
+        for row in right_data.rows() {
+            index.entry(row[right_key].as_str()).or_default().push(row);
         }
 
-        let header = lines.remove(0);
-
-        // Apply filter if provided
-        let filtered: Vec<&str> = if let Some(filter_str) = filter {
-            lines
-                .into_iter()
-                .filter(|line| line.contains(filter_str))
-                .take(limit)
-                .collect()
-        } else {
-            lines.into_iter().take(limit).collect()
-        };
+        let stem = Path::new(right).file_stem().and_then(|s| s.to_str()).unwrap_or(right);
+        let mut headers = left_data.headers.clone();
+        for (i, name) in right_data.headers.iter().enumerate() {
+            if i == right_key {
+                continue;
+            }
+            if left_data.headers.contains(name) {
+                headers.push(format!("{stem}.{name}"));
+            } else {
+                headers.push(name.clone());
+            }
+        }
 
-        let mut result = String::from(header);
-        result.push('\n');
-        for line in filtered {
-            result.push_str(line);
-            result.push('\n');
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(&headers)?;
+        let mut matched = 0;
+        for left_row in left_data.rows() {
+            let Some(right_rows) = index.get(left_row[left_key].as_str()) else {
+                continue;
+            };
+            for right_row in right_rows {
+                let joined: Vec<&str> = left_row
+                    .iter()
+                    .chain(
+                        right_row
+                            .iter()
+                            .enumerate()
+                            .filter(|&(i, _)| i != right_key)
+                            .map(|(_, v)| v),
+                    )
+                    .map(String::as_str)
+                    .collect();
+                if filter.is_some_and(|f| !joined.join(",").contains(f)) {
+                    continue;
+                }
+                if matched < limit {
+                    writer.write_record(&joined)?;
+                }
+                matched += 1;
+            }
         }
 
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| ZError::Config(format!("Failed to write joined rows: {e}")))?;
+        let mut result = String::from_utf8_lossy(&bytes).into_owned();
+        if matched > limit {
+            let _ = writeln!(result, "[Showing {limit} of {matched} joined rows]");
+        }
         Ok(result)
     }
 
+    /// Descriptive statistics for one numeric column of a CSV context file.
+    /// Empty and non-numeric cells are skipped.
+    ///
+    /// # Errors
+    /// Returns error if the file isn't a CSV, the column doesn't exist, or it has no numeric values
+    pub fn column_stats(&self, filename: &str, column: &str) -> Result<ColumnStats> {
+        let data = self.load_csv(filename)?;
+        let index = csv_column_index(&data, filename, column)?;
+
+        let values: Vec<f64> = data
+            .column(index)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|v| v.trim().parse::<f64>().ok())
+            .collect();
+
+        if values.is_empty() {
+            return Err(ZError::Config(format!(
+                "Column '{column}' in {filename} has no numeric values"
+            )));
+        }
+
+        ColumnStats::calculate(column, &values)
+    }
+
+    /// `(key, value)` cell pairs from two columns of a CSV context file, in row order
+    ///
+    /// # Errors
+    /// Returns error if the file isn't a CSV or either column doesn't exist
+    pub fn column_pairs(
+        &self,
+        filename: &str,
+        key_column: &str,
+        value_column: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let data = self.load_csv(filename)?;
+        let key = csv_column_index(&data, filename, key_column)?;
+        let value = csv_column_index(&data, filename, value_column)?;
+
+        Ok(data
+            .rows()
+            .iter()
+            .map(|row| (row[key].clone(), row[value].clone()))
+            .collect())
+    }
+
+    /// Parse a CSV context file, detecting its delimiter
+    fn load_csv(&self, filename: &str) -> Result<CsvData> {
+        let info = self
+            .get_file_info(filename)
+            .ok_or_else(|| ZError::Config(format!("File not found: {filename}")))?;
+
+        if info.file_type != FileType::Csv {
+            return Err(ZError::Config(format!("{filename} is not a CSV file")));
+        }
+
+        let path = resolve_within(&self.context_dir, filename)?;
+        let delimiter = crate::csv_reader::detect_delimiter(&path)?;
+        CsvData::from_file_with_delimiter(&path, delimiter)
+    }
+
+    /// Build dataset summaries of all CSV context files, bounded to `MAX_CSV_SUMMARY` chars
+    #[must_use]
+    pub fn build_csv_summaries(&self) -> String {
+        use std::fmt::Write as _;
+
+        let mut summaries = String::new();
+        for info in self.file_index.iter().filter(|f| f.file_type == FileType::Csv) {
+            let parsed = resolve_within(&self.context_dir, &info.filename).and_then(|path| {
+                crate::csv_reader::detect_delimiter(&path)
+                    .and_then(|d| CsvData::from_file_with_delimiter(&path, d))
+            });
+            match parsed {
+                Ok(data) => {
+                    let _ = writeln!(summaries, "### {}\n{}", info.filename, data.summary());
+                }
+                Err(e) => eprintln!("Warning: Could not summarize {}: {e}", info.filename),
+            }
+        }
+
+        if summaries.chars().count() > MAX_CSV_SUMMARY {
+            format!("{}...\n", truncate_string(&summaries, MAX_CSV_SUMMARY))
+        } else {
+            summaries
+        }
+    }
+
     /// Build file index summary for system prompt
     #[must_use]
     pub fn build_file_index_summary(&self) -> String {
@@ -214,6 +583,120 @@ impl ContextManager {
     }
 }
 
+/// Whether a raw CSV line passes a `query_csv` text filter (no filter keeps every line)
+fn row_matches(line: &str, filter: Option<&str>) -> bool {
+    filter.is_none_or(|f| line.contains(f))
+}
+
+/// Index of a named column in a CSV context file
+fn csv_column_index(data: &CsvData, filename: &str, column: &str) -> Result<usize> {
+    data.headers.iter().position(|h| h == column).ok_or_else(|| {
+        ZError::Config(format!(
+            "Column '{column}' not found in {filename}. Columns: {}",
+            data.headers.join(", ")
+        ))
+    })
+}
+
+/// ATX headings in Markdown lines as `(line_index, depth, text)`, skipping fenced code blocks
+fn markdown_headings<'a>(lines: &[&'a str]) -> Vec<(usize, usize, &'a str)> {
+    let mut headings = Vec::new();
+    let mut in_fence = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let depth = trimmed.bytes().take_while(|&b| b == b'#').count();
+        let rest = &trimmed[depth..];
+        if (1..=6).contains(&depth) && (rest.is_empty() || rest.starts_with([' ', '\t'])) {
+            headings.push((i, depth, rest.trim().trim_end_matches('#').trim_end()));
+        }
+    }
+
+    headings
+}
+
+/// Parse `.zignore` lines into `(pattern, negated)` pairs, dropping comments and blanks
+fn parse_ignore_patterns(content: &str) -> Vec<(Vec<char>, bool)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let (line, negated) = line.strip_prefix('!').map_or((line, false), |l| (l, true));
+            let line = line.strip_prefix('/').unwrap_or(line);
+            Some((line.chars().collect(), negated))
+        })
+        .collect()
+}
+
+/// Whether the last pattern matching `filename` excludes it
+fn is_ignored(filename: &str, patterns: &[(Vec<char>, bool)]) -> bool {
+    let name: Vec<char> = filename.chars().collect();
+    patterns
+        .iter()
+        .rev()
+        .find(|(pattern, _)| glob_match(pattern, &name))
+        .is_some_and(|(_, negated)| !negated)
+}
+
+/// Match a filename against a glob with `*`, `?`, `[...]` classes and `\` escapes
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = &pattern[1..];
+            (0..=name.len()).any(|skip| glob_match(rest, &name[skip..]))
+        }
+        Some('?') => !name.is_empty() && name[0] != '/' && glob_match(&pattern[1..], &name[1..]),
+        Some('[') => match (class_match(&pattern[1..], name.first().copied()), name.len()) {
+            (Some((true, consumed)), 1..) => glob_match(&pattern[1 + consumed..], &name[1..]),
+            (Some(_), _) => false,
+            // Unterminated class: treat `[` literally
+            (None, _) => name.first() == Some(&'[') && glob_match(&pattern[1..], &name[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Match `c` against a bracket class body (after `[`), returning the result and
+/// the number of pattern chars consumed including `]`, or `None` if unterminated
+fn class_match(body: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let negated = matches!(body.first(), Some('!' | '^'));
+    let mut i = usize::from(negated);
+    let mut matched = false;
+    let mut first = true;
+
+    while i < body.len() {
+        let lo = body[i];
+        if lo == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        if body.get(i + 1) == Some(&'-') && body.get(i + 2).is_some_and(|&hi| hi != ']') {
+            matched |= c.is_some_and(|c| (lo..=body[i + 2]).contains(&c));
+            i += 3;
+        } else {
+            matched |= c == Some(lo);
+            i += 1;
+        }
+    }
+
+    None
+}
+
 /// Truncate a string to max chars (UTF-8 safe), breaking at word boundary if possible
 fn truncate_string(s: &str, max_chars: usize) -> String {
     if s.chars().count() <= max_chars {
@@ -273,6 +756,43 @@ mod tests {
         assert_eq!(cm.file_count(), 3);
     }
 
+    #[test]
+    fn test_zignore() {
+        let dir = create_test_context();
+        for name in ["scratch.txt", "scratch2.txt", "keep_scratch.txt", "notes[1].md"] {
+            fs::write(dir.path().join(name), "x").expect("write");
+        }
+        fs::write(
+            dir.path().join(IGNORE_FILE),
+            "# scratch files\n\n/scratch*.txt\n*_scratch.txt\n!keep_*\nnotes\\[[0-9]].md\n",
+        )
+        .expect("write");
+
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+        let names: Vec<&str> = cm.list_files().iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["clusters.csv", "instructions.txt", "keep_scratch.txt", "summary.txt"]
+        );
+        assert!(cm.read_file("scratch.txt").is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let m = |p: &str, n: &str| {
+            glob_match(&p.chars().collect::<Vec<_>>(), &n.chars().collect::<Vec<_>>())
+        };
+        assert!(m("*.csv", "clusters.csv"));
+        assert!(!m("*.csv", "clusters.csv.bak"));
+        assert!(m("file?.txt", "file1.txt"));
+        assert!(m("[!a-c]x", "dx"));
+        assert!(!m("[!a-c]x", "bx"));
+        assert!(m("[]]", "]"));
+        assert!(m("a[b", "a[b"));
+        assert!(m("\\*", "*"));
+        assert!(!m("\\*", "a"));
+    }
+
     #[test]
     fn test_list_files() {
         let dir = create_test_context();
@@ -314,6 +834,155 @@ mod tests {
         assert!(!filtered.contains("1,1,0.2"));
     }
 
+    #[test]
+    fn test_count_csv() {
+        let dir = create_test_context();
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        assert_eq!(cm.count_csv("clusters.csv", None).expect("count"), 3);
+        assert_eq!(cm.count_csv("clusters.csv", Some(",0,")).expect("count"), 2);
+        assert_eq!(cm.count_csv("clusters.csv", Some("missing")).expect("count"), 0);
+        assert!(cm.count_csv("summary.txt", None).is_err());
+    }
+
+    #[test]
+    fn test_join_csv() {
+        let dir = create_test_context();
+        fs::write(
+            dir.path().join("anomalies.csv"),
+            "row_id,distance,details\n2,0.9,\"far, isolated\"\n1,0.8,high\n7,0.5,gone\n",
+        )
+        .expect("write");
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let joined = cm
+            .join_csv("clusters.csv", "anomalies.csv", "row_id", None, None)
+            .expect("join");
+        assert_eq!(
+            joined,
+            "row_id,cluster,distance,anomalies.distance,details\n\
+             1,1,0.2,0.8,high\n\
+             2,0,0.15,0.9,\"far, isolated\"\n"
+        );
+
+        let filtered = cm
+            .join_csv("clusters.csv", "anomalies.csv", "row_id", Some(",0,"), None)
+            .expect("join");
+        assert!(filtered.contains("2,0,0.15"));
+        assert!(!filtered.contains("1,1,0.2"));
+
+        let capped = cm
+            .join_csv("clusters.csv", "anomalies.csv", "row_id", None, Some(1))
+            .expect("join");
+        assert!(capped.ends_with("[Showing 1 of 2 joined rows]\n"));
+
+        assert!(cm.join_csv("clusters.csv", "anomalies.csv", "cluster", None, None).is_err());
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let dir = create_test_context();
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let window = cm.read_lines("clusters.csv", 2, 2).expect("read lines");
+        assert!(window.contains("[Lines 2-3 of 4]"));
+        assert!(window.contains("2: 0,0,0.1"));
+        assert!(window.contains("3: 1,1,0.2"));
+        assert!(!window.contains("row_id"));
+
+        let past_end = cm.read_lines("clusters.csv", 10, 5).expect("read lines");
+        assert!(past_end.contains("past the end"));
+    }
+
+    #[test]
+    fn test_build_csv_summaries() {
+        let dir = create_test_context();
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let summaries = cm.build_csv_summaries();
+        assert!(summaries.contains("### clusters.csv"));
+        assert!(summaries.contains("3 rows x 3 columns"));
+        assert!(!summaries.contains("summary.txt"));
+    }
+
+    #[test]
+    fn test_search() {
+        let dir = create_test_context();
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let hits = cm.search("anomalies", false);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "instructions.txt");
+        assert_eq!(hits[0].1, 1);
+        assert_eq!(hits[0].2, "Flag all anomalies in the XML");
+
+        assert!(cm.search("ML ANALYSIS", false).is_empty());
+        let hits = cm.search("ML ANALYSIS", true);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0, "summary.txt");
+    }
+
+    #[test]
+    fn test_read_section() {
+        let dir = create_test_context();
+        fs::write(
+            dir.path().join("guide.md"),
+            "# Guide\nIntro\n\n## Rules\n- Flag outliers\n### Details\nUse z-scores\n\n\
+             ```\n# not a heading\n```\n## Examples\nExample text\n",
+        )
+        .expect("write");
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let rules = cm.read_section("guide.md", "## rules").expect("read section");
+        assert_eq!(
+            rules,
+            "## Rules\n- Flag outliers\n### Details\nUse z-scores\n\n```\n# not a heading\n```"
+        );
+
+        let examples = cm.read_section("guide.md", "Examples").expect("read section");
+        assert_eq!(examples, "## Examples\nExample text");
+
+        let missing = cm.read_section("guide.md", "Setup").expect("read section");
+        assert!(missing.contains("not found"));
+        assert!(missing.contains("## Rules\n### Details\n## Examples"));
+        assert!(!missing.contains("not a heading"));
+
+        assert!(cm.read_section("summary.txt", "Rules").is_err());
+
+        // The limit counts characters, so multi-byte text under it comes back whole
+        let body = "é".repeat(MAX_FILE_CONTENT - 20);
+        fs::write(dir.path().join("accents.md"), format!("## Notes\n{body}\n")).expect("write");
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+        let notes = cm.read_section("accents.md", "Notes").expect("read section");
+        assert_eq!(notes, format!("## Notes\n{body}"));
+    }
+
+    #[test]
+    fn test_column_stats() {
+        let dir = create_test_context();
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let stats = cm.column_stats("clusters.csv", "distance").expect("stats");
+        assert_eq!(stats.count, 3);
+        assert!((stats.mean - 0.15).abs() < 1e-9);
+        assert!((stats.max - 0.2).abs() < 1e-9);
+
+        assert!(cm.column_stats("clusters.csv", "missing").is_err());
+        assert!(cm.column_stats("summary.txt", "distance").is_err());
+    }
+
+    #[test]
+    fn test_column_pairs() {
+        let dir = create_test_context();
+        let cm = ContextManager::from_directory(dir.path()).expect("create context manager");
+
+        let pairs = cm.column_pairs("clusters.csv", "row_id", "cluster").expect("pairs");
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[1], ("1".to_string(), "1".to_string()));
+
+        assert!(cm.column_pairs("clusters.csv", "row_id", "missing").is_err());
+    }
+
     #[test]
     fn test_file_not_found() {
         let dir = create_test_context();
@@ -333,4 +1002,42 @@ mod tests {
         let not_truncated = truncate_string(short, 20);
         assert_eq!(not_truncated, "Short");
     }
+
+    #[test]
+    fn test_truncate_string_multibyte() {
+        let text = "naïve café résumé 😀😀😀 déjà vu";
+        for max in 0..=text.chars().count() {
+            let truncated = truncate_string(text, max);
+            assert!(truncated.chars().count() <= max);
+            assert!(text.starts_with(&truncated));
+        }
+
+        // Cut lands right after an accented char and right inside an emoji run
+        assert_eq!(truncate_string("ééééé", 3), "ééé");
+        assert_eq!(truncate_string("ab😀😀😀", 3), "ab😀");
+    }
+
+    #[test]
+    fn test_resolve_within() {
+        let dir = create_test_context();
+        let base = dir.path();
+        assert_eq!(resolve_within(base, "summary.txt").expect("plain"), base.join("summary.txt"));
+        assert_eq!(resolve_within(base, "new.csv").expect("missing"), base.join("new.csv"));
+        for name in ["../summary.txt", "sub/file.txt", "/etc/passwd", "..", ".", ""] {
+            assert!(resolve_within(base, name).is_err(), "{name}");
+        }
+
+        #[cfg(unix)]
+        {
+            let outside = TempDir::new().expect("create temp dir");
+            fs::write(outside.path().join("secret.txt"), "secret").expect("write");
+            std::os::unix::fs::symlink(outside.path().join("secret.txt"), base.join("link.txt"))
+                .expect("symlink");
+            assert!(resolve_within(base, "link.txt").is_err());
+
+            let cm = ContextManager::from_directory(base).expect("create context manager");
+            assert!(cm.get_file_info("link.txt").is_none());
+            assert!(cm.read_file("link.txt").is_err());
+        }
+    }
 }
@@ -1,34 +1,621 @@
 #![allow(clippy::module_name_repetitions)]
 
-use crate::structs::{CsvData, Result};
+use crate::structs::{ColumnCoercion, CsvData, CsvPreamble, InferredType, Result, ZError};
 use csv::ReaderBuilder;
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader};
+use std::num::NonZeroUsize;
 use std::path::Path;
 
+/// Candidate delimiters tried by `detect_delimiter`, in order of preference on ties
+const DELIMITER_CANDIDATES: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Number of lines sniffed when detecting the delimiter
+const SNIFF_LINES: usize = 10;
+
+/// Cell count below which numeric parsing stays on one thread
+const PARALLEL_PARSE_MIN_CELLS: usize = 100_000;
+
+/// Cells read as 1.0 / 0.0 by `--encode-booleans` (case-insensitive)
+pub const TRUE_TOKENS: &[&str] = &["true", "yes", "y"];
+pub const FALSE_TOKENS: &[&str] = &["false", "no", "n"];
+
+/// Distinct values and rows listed per column in the coercion report
+const COERCION_SAMPLES: usize = 5;
+
+/// Share of a column's non-empty cells that must be boolean tokens to encode it
+const BOOLEAN_COLUMN_FRACTION: f64 = 0.9;
+
+/// Quoted fields longer than this are taken as an unbalanced quote swallowing the file
+const MAX_FIELD_BYTES: usize = 1 << 20;
+
+/// Skipped records whose parse error is kept in `CsvData::parse_errors`; the rest are
+/// only counted, so a pervasively broken file doesn't flood the log
+pub const MAX_LOGGED_PARSE_ERRORS: usize = 10;
+
 impl CsvData {
     /// Parse a CSV or TSV file
     ///
     /// # Errors
     /// Returns error if file cannot be read or parsed
+    #[cfg(test)]
     pub fn from_file(path: &Path, is_tsv: bool) -> Result<Self> {
         let delimiter = if is_tsv { b'\t' } else { b',' };
+        Self::from_file_with_delimiter(path, delimiter)
+    }
+
+    /// Parse a delimited file using an explicit delimiter byte.
+    /// Short rows are padded with empty fields up to the header count.
+    ///
+    /// # Errors
+    /// Returns error if file cannot be read or parsed
+    pub fn from_file_with_delimiter(path: &Path, delimiter: u8) -> Result<Self> {
+        Self::from_file_with_options(path, delimiter, false, None, CsvPreamble::default(), false)
+    }
 
+    /// Parse a delimited file. With `strict_columns`, any row whose field count
+    /// differs from the header count is an error; otherwise short rows are padded
+    /// with empty fields and counted in `ragged_rows`. With `max_rows`, reading
+    /// stops after that many records and `truncated` records whether any were left.
+    /// Lines matching `preamble` are skipped before the header and counted in `skipped_lines`.
+    /// With `continue_on_parse_error`, a record that fails to parse (e.g. invalid UTF-8)
+    /// is dropped and counted in `skipped_records` instead of failing the whole read.
+    /// So is a record whose quoted field looks like an unbalanced quote ran on over later
+    /// rows (see `check_quotes`); those rows are lost with it. A full record with such a
+    /// field is kept and noted in `quote_warnings`.
+    ///
+    /// # Errors
+    /// Returns error if file cannot be read or parsed, or a row is ragged under `strict_columns`
+    pub fn from_file_with_options(
+        path: &Path,
+        delimiter: u8,
+        strict_columns: bool,
+        max_rows: Option<usize>,
+        preamble: CsvPreamble,
+        continue_on_parse_error: bool,
+    ) -> Result<Self> {
+        let mut file = BufReader::new(std::fs::File::open(path)?);
+        let skipped_lines = skip_preamble(&mut file, preamble)?;
         let mut reader = ReaderBuilder::new()
             .delimiter(delimiter)
             .has_headers(true)
             .flexible(true)
-            .from_path(path)?;
+            .comment(preamble.comment)
+            .from_reader(file);
 
         let headers: Vec<String> = reader.headers()?.iter().map(String::from).collect();
 
         let mut rows = Vec::new();
+        let mut ragged_rows = 0;
+        let mut truncated = false;
+        let mut skipped_records = 0;
+        let mut parse_errors = Vec::new();
+        let mut quote_warnings = Vec::new();
         for result in reader.records() {
+            if max_rows.is_some_and(|max| rows.len() >= max) {
+                // Stop at the first record past the cap without parsing the rest
+                truncated = true;
+                break;
+            }
+            let skip = match &result {
+                // I/O errors aren't tied to one record, so they still end the read
+                Err(err) if continue_on_parse_error && !err.is_io_error() => {
+                    Some((err.position().map_or(0, csv::Position::line), err.to_string()))
+                }
+                Ok(record) if continue_on_parse_error => {
+                    let line = record.position().map_or(0, csv::Position::line);
+                    match check_quotes(record, delimiter, headers.len()) {
+                        QuoteCheck::Balanced => None,
+                        QuoteCheck::Suspect(reason) => {
+                            if quote_warnings.len() < MAX_LOGGED_PARSE_ERRORS {
+                                quote_warnings.push(format!("line {line}: {reason}"));
+                            }
+                            None
+                        }
+                        QuoteCheck::Runaway(reason) => Some((line, reason)),
+                    }
+                }
+                _ => None,
+            };
+            if let Some((line, reason)) = skip {
+                skipped_records += 1;
+                if parse_errors.len() < MAX_LOGGED_PARSE_ERRORS {
+                    parse_errors.push(format!("line {line}: {reason}"));
+                }
+                continue;
+            }
             let record = result?;
-            let row: Vec<String> = record.iter().map(String::from).collect();
+            let mut row: Vec<String> = record.iter().map(String::from).collect();
+
+            if row.len() != headers.len() {
+                if strict_columns {
+                    return Err(ZError::Config(format!(
+                        "Row {} has {} fields, expected {}",
+                        rows.len() + 1,
+                        row.len(),
+                        headers.len()
+                    )));
+                }
+                ragged_rows += 1;
+                if row.len() < headers.len() {
+                    row.resize(headers.len(), String::new());
+                }
+            }
             rows.push(row);
         }
 
-        Ok(Self { headers, rows })
+        let mut data = Self::new(headers, rows, delimiter);
+        data.ragged_rows = ragged_rows;
+        data.truncated = truncated;
+        data.skipped_lines = skipped_lines;
+        data.skipped_records = skipped_records;
+        data.parse_errors = parse_errors;
+        data.quote_warnings = quote_warnings;
+        Ok(data)
+    }
+
+    /// Numeric columns holding non-empty cells that don't parse as numbers, which
+    /// `FeatureMatrix::from_csv` drops the rows of. Columns without any are left out.
+    #[must_use]
+    pub fn non_numeric_cells(&self) -> Vec<ColumnCoercion> {
+        let parsed = self.numeric_columns();
+        self.numeric_column_indices()
+            .into_iter()
+            .filter_map(|i| {
+                let mut coercion = ColumnCoercion {
+                    column: self.headers[i].clone(),
+                    count: 0,
+                    samples: Vec::new(),
+                    first_rows: Vec::new(),
+                };
+                for (row, cells) in self.rows().iter().enumerate() {
+                    let cell = cells.get(i).map_or("", String::as_str);
+                    if cell.is_empty() || parsed[i][row].is_some() {
+                        continue;
+                    }
+                    coercion.count += 1;
+                    if coercion.first_rows.len() < COERCION_SAMPLES {
+                        coercion.first_rows.push(row);
+                    }
+                    if coercion.samples.len() < COERCION_SAMPLES
+                        && !coercion.samples.iter().any(|s| s == cell)
+                    {
+                        coercion.samples.push(cell.to_string());
+                    }
+                }
+                (coercion.count > 0).then_some(coercion)
+            })
+            .collect()
+    }
+
+    /// Non-numeric columns whose non-empty cells are at least `BOOLEAN_COLUMN_FRACTION`
+    /// boolean tokens (`TRUE_TOKENS`/`FALSE_TOKENS`)
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn boolean_column_indices(&self) -> Vec<usize> {
+        let numeric = self.numeric_column_indices();
+        (0..self.col_count())
+            .filter(|i| !numeric.contains(i))
+            .filter(|&i| {
+                let col = self.column(i).unwrap_or_default();
+                let non_empty = col.iter().filter(|s| !s.trim().is_empty()).count();
+                let booleans = col.iter().filter(|s| parse_boolean(s).is_some()).count();
+                non_empty > 0 && booleans as f64 / non_empty as f64 >= BOOLEAN_COLUMN_FRACTION
+            })
+            .collect()
+    }
+
+    /// Columns that become features, in header order, with their cells parsed
+    /// (`None` where empty or unparseable): the numeric columns, plus the boolean
+    /// columns coded 1.0/0.0 when `encode_booleans` is set
+    #[must_use]
+    pub fn feature_columns(&self, encode_booleans: bool) -> Vec<(usize, Cow<'_, [Option<f64>]>)> {
+        let parsed = self.numeric_columns();
+        let mut columns: Vec<(usize, Cow<'_, [Option<f64>]>)> = self
+            .numeric_column_indices()
+            .into_iter()
+            .map(|i| (i, Cow::Borrowed(parsed[i].as_slice())))
+            .collect();
+        if encode_booleans {
+            for i in self.boolean_column_indices() {
+                let cells = self
+                    .rows()
+                    .iter()
+                    .map(|row| row.get(i).and_then(|v| parse_boolean(v)))
+                    .collect();
+                columns.push((i, Cow::Owned(cells)));
+            }
+            columns.sort_by_key(|&(i, _)| i);
+        }
+        columns
+    }
+
+    /// Values of the named column for the given source rows, e.g. to label
+    /// samples via `row_indices`. Missing cells become empty strings.
+    ///
+    /// # Errors
+    /// Returns error if the column doesn't exist
+    pub fn labels_for_rows(&self, column: &str, row_indices: &[usize]) -> Result<Vec<String>> {
+        let index = self.headers.iter().position(|h| h == column).ok_or_else(|| {
+            ZError::Config(format!(
+                "Label column '{column}' not found. Columns: {}",
+                self.headers.join(", ")
+            ))
+        })?;
+
+        Ok(row_indices
+            .iter()
+            .map(|&r| {
+                self.rows()
+                    .get(r)
+                    .and_then(|row| row.get(index))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect())
+    }
+
+    /// Numeric values of the named column across all rows, skipping empty and
+    /// non-numeric cells
+    ///
+    /// # Errors
+    /// Returns error if the column doesn't exist or isn't numeric
+    pub fn numeric_column_values(&self, column: &str) -> Result<Vec<f64>> {
+        let index = self.headers.iter().position(|h| h == column).ok_or_else(|| {
+            ZError::Config(format!(
+                "Column '{column}' not found. Columns: {}",
+                self.headers.join(", ")
+            ))
+        })?;
+        if !self.numeric_column_indices().contains(&index) {
+            return Err(ZError::Config(format!("Column '{column}' is not numeric")));
+        }
+
+        Ok(self.numeric_columns()[index].iter().flatten().copied().collect())
+    }
+
+    /// Inferred type of every column, in header order
+    #[must_use]
+    pub fn column_types(&self) -> Vec<InferredType> {
+        (0..self.col_count())
+            .map(|i| infer_type(&self.column(i).unwrap_or_default()))
+            .collect()
+    }
+
+    /// Human-readable name of the delimiter
+    #[must_use]
+    pub fn delimiter_name(&self) -> String {
+        match self.delimiter {
+            b'\t' => "tab".to_string(),
+            d => format!("'{}'", d as char),
+        }
+    }
+}
+
+/// A boolean token as 1.0 or 0.0
+#[must_use]
+pub fn parse_boolean(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if TRUE_TOKENS.iter().any(|t| value.eq_ignore_ascii_case(t)) {
+        Some(1.0)
+    } else if FALSE_TOKENS.iter().any(|t| value.eq_ignore_ascii_case(t)) {
+        Some(0.0)
+    } else {
+        None
+    }
+}
+
+/// One column's cells parsed as numbers
+fn parse_column(rows: &[Vec<String>], index: usize) -> Vec<Option<f64>> {
+    rows.iter()
+        .map(|row| row.get(index).and_then(|v| v.parse::<f64>().ok()))
+        .collect()
+}
+
+/// Parse every column, splitting the columns across threads for large inputs.
+/// Backs the cache behind `CsvData::numeric_columns`.
+pub fn parse_columns(rows: &[Vec<String>], n_cols: usize) -> Vec<Vec<Option<f64>>> {
+    let threads = std::thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(n_cols);
+    if threads <= 1 || rows.len() * n_cols < PARALLEL_PARSE_MIN_CELLS {
+        return (0..n_cols).map(|i| parse_column(rows, i)).collect();
+    }
+
+    let chunk = n_cols.div_ceil(threads);
+    std::thread::scope(|scope| {
+        // Spawn every chunk before joining any of them
+        #[allow(clippy::needless_collect)]
+        let handles: Vec<_> = (0..n_cols)
+            .step_by(chunk)
+            .map(|start| {
+                scope.spawn(move || {
+                    (start..(start + chunk).min(n_cols))
+                        .map(|i| parse_column(rows, i))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    })
+}
+
+/// Header names taken as an id column, matched case-insensitively
+const ID_COLUMN_NAMES: &[&str] = &["id", "row_id", "key"];
+
+/// Fewest rows for an unnamed column of distinct values to count as an id column
+const MIN_ID_ROWS: usize = 10;
+
+/// Guess which column identifies rows: the first one named like `ID_COLUMN_NAMES`,
+/// else the first integer or text column whose cells are all present and distinct
+/// (with at least `MIN_ID_ROWS` rows, so a short file doesn't make every column unique)
+#[must_use]
+pub fn infer_id_column(csv: &CsvData) -> Option<usize> {
+    if let Some(index) = csv
+        .headers
+        .iter()
+        .position(|h| ID_COLUMN_NAMES.iter().any(|n| h.trim().eq_ignore_ascii_case(n)))
+    {
+        return Some(index);
+    }
+    if csv.row_count() < MIN_ID_ROWS {
+        return None;
+    }
+
+    (0..csv.col_count()).find(|&i| {
+        let Some(column) = csv.column(i) else {
+            return false;
+        };
+        let mut seen = std::collections::HashSet::new();
+        matches!(infer_type(&column), InferredType::Integer | InferredType::Text)
+            && column.iter().all(|v| !v.trim().is_empty() && seen.insert(v.trim()))
+    })
+}
+
+/// Whether a record's quoted fields look like an unbalanced quote ran on over later rows
+enum QuoteCheck {
+    Balanced,
+    /// A field spans lines shaped like rows, but the record is complete, as with a
+    /// quoted address or notes value holding delimiters
+    Suspect(String),
+    /// The record swallowed the rows after it
+    Runaway(String),
+}
+
+/// Check `record` for a field run on over later rows: one over `MAX_FIELD_BYTES`, or
+/// a multi-line field with a later line holding a whole row's worth of delimiters.
+/// The latter is only a runaway when it leaves the record short of fields; a runaway
+/// that still ends on a full record is kept as `Suspect`.
+fn check_quotes(record: &csv::StringRecord, delimiter: u8, columns: usize) -> QuoteCheck {
+    let describe = |field: &str| {
+        format!(
+            "quoted field spans {} line(s) ({} bytes), likely an unbalanced quote",
+            field.lines().count(),
+            field.len()
+        )
+    };
+    if let Some(field) = record.iter().find(|field| field.len() > MAX_FIELD_BYTES) {
+        return QuoteCheck::Runaway(describe(field));
+    }
+    let spans_rows = |field: &&str| {
+        columns > 1
+            && field.lines().skip(1).any(|line| {
+                line.bytes().filter(|&b| b == delimiter).count() + 1 >= columns
+            })
+    };
+    match record.iter().find(spans_rows) {
+        None => QuoteCheck::Balanced,
+        Some(field) if record.len() < columns => QuoteCheck::Runaway(describe(field)),
+        Some(field) => QuoteCheck::Suspect(describe(field)),
+    }
+}
+
+/// Header names taken as latitude / longitude, matched case-insensitively, alone
+/// or after a `_` (e.g. `pickup_lat`)
+const LATITUDE_NAMES: &[&str] = &["lat", "latitude"];
+const LONGITUDE_NAMES: &[&str] = &["lon", "lng", "long", "longitude"];
+
+/// Find a latitude and a longitude column: the first numeric column named like
+/// `LATITUDE_NAMES` with every value within ±90, and likewise for longitude within ±180
+#[must_use]
+pub fn infer_lat_lon_columns(csv: &CsvData) -> Option<(usize, usize)> {
+    let parsed = csv.numeric_columns();
+    let numeric = csv.numeric_column_indices();
+    let find = |names: &[&str], limit: f64| {
+        numeric.iter().copied().find(|&i| {
+            let header = csv.headers[i].trim().to_ascii_lowercase();
+            let named = names.iter().any(|n| {
+                header == *n || header.strip_suffix(n).is_some_and(|p| p.ends_with('_'))
+            });
+            named && parsed[i].iter().flatten().all(|v| v.abs() <= limit)
+        })
+    };
+    Some((find(LATITUDE_NAMES, 90.0)?, find(LONGITUDE_NAMES, 180.0)?))
+}
+
+/// Classify a column by the narrowest type all of its non-empty cells fit:
+/// boolean, then integer, float, date, falling back to text
+#[must_use]
+pub fn infer_type(column: &[&str]) -> InferredType {
+    let values: Vec<&str> = column.iter().map(|v| v.trim()).filter(|v| !v.is_empty()).collect();
+    if values.is_empty() {
+        return InferredType::Empty;
+    }
+
+    let all = |check: fn(&str) -> bool| values.iter().all(|v| check(v));
+    if all(|v| ["true", "false", "0", "1"].iter().any(|b| v.eq_ignore_ascii_case(b))) {
+        InferredType::Boolean
+    } else if all(|v| v.parse::<i64>().is_ok()) {
+        InferredType::Integer
+    } else if all(|v| v.parse::<f64>().is_ok()) {
+        InferredType::Float
+    } else if all(is_date) {
+        InferredType::Date
+    } else {
+        InferredType::Text
+    }
+}
+
+/// Whether a cell is a date (`YYYY-MM-DD`, `YYYY/MM/DD`, `DD/MM/YYYY` or `MM/DD/YYYY`),
+/// optionally followed by a `T` or space and `HH:MM[:SS[.fff]]` with an optional
+/// `Z` or `+HH:MM` offset
+fn is_date(value: &str) -> bool {
+    let (date, time) = value
+        .split_once(['T', ' '])
+        .map_or((value, None), |(date, time)| (date, Some(time)));
+
+    let parts: Vec<&str> = date.split(['-', '/']).collect();
+    let [a, b, c] = parts[..] else {
+        return false;
+    };
+    let number = |s: &str, digits: usize| {
+        (s.len() == digits && s.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| s.parse::<u32>().ok())
+            .flatten()
+    };
+    let valid_day_month = |day: Option<u32>, month: Option<u32>| {
+        matches!((day, month), (Some(1..=31), Some(1..=12)))
+    };
+
+    let date_ok = if date.contains('-') {
+        number(a, 4).is_some() && valid_day_month(number(c, 2), number(b, 2))
+    } else if number(a, 4).is_some() {
+        valid_day_month(number(c, 2), number(b, 2))
+    } else {
+        number(c, 4).is_some()
+            && (valid_day_month(number(a, 2), number(b, 2))
+                || valid_day_month(number(b, 2), number(a, 2)))
+    };
+
+    date_ok && time.is_none_or(is_time)
+}
+
+/// Whether a string is `HH:MM[:SS[.fff]]` with an optional `Z` or `+HH:MM`/`-HH:MM` offset
+fn is_time(value: &str) -> bool {
+    let value = value.strip_suffix('Z').unwrap_or(value);
+    let clock = match value.rfind(['+', '-']) {
+        Some(i) if is_clock(&value[i + 1..], false) => &value[..i],
+        Some(_) => return false,
+        None => value,
+    };
+    is_clock(clock, true)
+}
+
+/// Whether a string is `HH:MM`, or with `allow_seconds` also `HH:MM:SS[.fff]`
+fn is_clock(value: &str, allow_seconds: bool) -> bool {
+    let two_digits = |s: &str, max: u32| {
+        s.len() == 2
+            && s.bytes().all(|b| b.is_ascii_digit())
+            && s.parse::<u32>().is_ok_and(|n| n <= max)
+    };
+
+    let mut parts = value.split(':');
+    let (Some(hours), Some(minutes)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    if !two_digits(hours, 23) || !two_digits(minutes, 59) {
+        return false;
+    }
+
+    match (parts.next(), parts.next()) {
+        (None, _) => true,
+        (Some(seconds), None) if allow_seconds => {
+            let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, "0"));
+            two_digits(whole, 60)
+                && !fraction.is_empty()
+                && fraction.bytes().all(|b| b.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// Sniff the first lines of a file and pick the delimiter with the most
+/// consistent field count across lines. Falls back to `,`.
+///
+/// # Errors
+/// Returns error if the file cannot be read
+pub fn detect_delimiter(path: &Path) -> Result<u8> {
+    detect_delimiter_after(path, CsvPreamble::default())
+}
+
+/// `detect_delimiter`, sniffing the lines after those `preamble` skips
+///
+/// # Errors
+/// Returns error if the file cannot be read
+pub fn detect_delimiter_after(path: &Path, preamble: CsvPreamble) -> Result<u8> {
+    let mut file = BufReader::new(std::fs::File::open(path)?);
+    skip_preamble(&mut file, preamble)?;
+    let lines: Vec<String> = file
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter(|l| !l.trim().is_empty())
+        .take(SNIFF_LINES)
+        .collect();
+
+    Ok(detect_delimiter_in(&lines))
+}
+
+/// Consume the lines `preamble` skips from the start of `reader`, returning how many
+fn skip_preamble(reader: &mut impl BufRead, preamble: CsvPreamble) -> Result<usize> {
+    let mut skipped = 0;
+    let mut line = Vec::new();
+    loop {
+        let commented = match preamble.comment {
+            Some(c) => reader.fill_buf()?.first() == Some(&c),
+            None => false,
+        };
+        if skipped >= preamble.skip_lines && !commented {
+            return Ok(skipped);
+        }
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(skipped);
+        }
+        skipped += 1;
+    }
+}
+
+/// Pick the best delimiter for a set of sample lines
+fn detect_delimiter_in(lines: &[String]) -> u8 {
+    let mut best = (b',', 0usize, 0usize);
+
+    for &candidate in &DELIMITER_CANDIDATES {
+        let counts: Vec<usize> = lines
+            .iter()
+            .map(|l| count_unquoted(l, candidate))
+            .collect();
+
+        let Some(&header_count) = counts.first() else {
+            continue;
+        };
+        if header_count == 0 {
+            continue;
+        }
+
+        let consistent = counts.iter().filter(|&&c| c == header_count).count();
+        if (consistent, header_count) > (best.1, best.2) {
+            best = (candidate, consistent, header_count);
+        }
     }
+
+    best.0
+}
+
+/// Count occurrences of a delimiter outside double-quoted fields
+fn count_unquoted(line: &str, delimiter: u8) -> usize {
+    let mut in_quotes = false;
+    let mut count = 0;
+    for b in line.bytes() {
+        if b == b'"' {
+            in_quotes = !in_quotes;
+        } else if b == delimiter && !in_quotes {
+            count += 1;
+        }
+    }
+    count
 }
 
 #[cfg(test)]
@@ -65,6 +652,231 @@ mod tests {
 
         // "value" and "count" should be numeric
         assert_eq!(numeric, vec![1, 2]);
+
+        assert_eq!(data.numeric_column_values("value").expect("values"), vec![1.5, 2.5, 3.5]);
+        assert!(data.numeric_column_values("name").is_err());
+        assert!(data.numeric_column_values("missing").is_err());
+    }
+
+    #[test]
+    fn test_numeric_columns_cache() {
+        let file = create_test_csv("name,value\nalpha,1.5\nbeta,\ngamma,x");
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+
+        let parsed = data.numeric_columns();
+        assert_eq!(parsed[0], vec![None, None, None]);
+        assert_eq!(parsed[1], vec![Some(1.5), None, None]);
+        assert!(std::ptr::eq(parsed, data.numeric_columns()));
+    }
+
+    #[test]
+    fn test_parse_columns_parallel() {
+        let n_cols = 50;
+        let rows: Vec<Vec<String>> = (0..PARALLEL_PARSE_MIN_CELLS / n_cols)
+            .map(|r| (0..n_cols).map(|c| (r * c).to_string()).collect())
+            .collect();
+
+        let parallel = parse_columns(&rows, n_cols);
+        let sequential: Vec<_> = (0..n_cols).map(|i| parse_column(&rows, i)).collect();
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let file = create_test_csv("name,value,count\nalpha,1.5\nbeta,2.5,20,extra\ngamma,3.5,30");
+
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(data.ragged_rows, 2);
+        assert_eq!(data.rows()[0], vec!["alpha", "1.5", ""]);
+        assert_eq!(data.column(2), Some(vec!["", "20", "30"]));
+
+        let preamble = CsvPreamble::default();
+        let strict =
+            CsvData::from_file_with_options(file.path(), b',', true, None, preamble, false);
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn test_max_rows() {
+        let file = create_test_csv("name,value\nalpha,1\nbeta,2\ngamma,3");
+
+        let preamble = CsvPreamble::default();
+        let data =
+            CsvData::from_file_with_options(file.path(), b',', false, Some(2), preamble, false)
+                .expect("parse csv");
+        assert_eq!(data.row_count(), 2);
+        assert_eq!(data.rows()[1], vec!["beta", "2"]);
+        assert!(data.truncated);
+
+        let exact =
+            CsvData::from_file_with_options(file.path(), b',', false, Some(3), preamble, false)
+                .expect("parse csv");
+        assert_eq!(exact.row_count(), 3);
+        assert!(!exact.truncated);
+    }
+
+    #[test]
+    fn test_continue_on_parse_error() {
+        let mut file = NamedTempFile::new().expect("create temp file");
+        file.write_all(b"name,value\nalpha,1\nbad\xff,2\ngamma,3\n\xfe,4\n")
+            .expect("write content");
+        let preamble = CsvPreamble::default();
+
+        let aborted =
+            CsvData::from_file_with_options(file.path(), b',', false, None, preamble, false);
+        assert!(aborted.is_err());
+
+        let data = CsvData::from_file_with_options(file.path(), b',', false, None, preamble, true)
+            .expect("parse csv");
+        assert_eq!(data.row_count(), 2);
+        assert_eq!(data.rows()[1], vec!["gamma", "3"]);
+        assert_eq!(data.skipped_records, 2);
+        assert_eq!(data.parse_errors.len(), 2);
+        assert!(data.parse_errors[0].starts_with("line 3: "));
+
+        // An unescaped quote runs the field on over the following rows
+        let file = create_test_csv("a,b\n1,2\n\"3,4\n5,6\n7,8\n");
+        let data = CsvData::from_file_with_options(file.path(), b',', false, None, preamble, true)
+            .expect("parse csv");
+        assert_eq!(data.row_count(), 1);
+        assert_eq!(data.skipped_records, 1);
+        assert!(data.parse_errors[0].starts_with("line 3: quoted field spans 3 line(s)"));
+
+        // A quoted multi-line value that doesn't look like rows is kept
+        let file = create_test_csv("a,b,c\n1,\"x\ny\",2\n");
+        let data = CsvData::from_file_with_options(file.path(), b',', false, None, preamble, true)
+            .expect("parse csv");
+        assert_eq!(data.row_count(), 1);
+        assert_eq!(data.skipped_records, 0);
+
+        // So is one shaped like rows when the record is complete, with a warning
+        let file =
+            create_test_csv("id,address,note\n1,\"1 Main St, Apt 2\nSpringfield, IL, 62701\",ok\n");
+        let data = CsvData::from_file_with_options(file.path(), b',', false, None, preamble, true)
+            .expect("parse csv");
+        assert_eq!(data.rows(), vec![vec!["1", "1 Main St, Apt 2\nSpringfield, IL, 62701", "ok"]]);
+        assert_eq!(data.skipped_records, 0);
+        assert_eq!(data.quote_warnings.len(), 1);
+        assert!(data.quote_warnings[0].starts_with("line 2: quoted field spans 2 line(s)"));
+    }
+
+    #[test]
+    fn test_skip_preamble() {
+        let file = create_test_csv(
+            "Sensor export\n# generated 2024-05-01\n#units: mm\nname;value\nalpha;1\n#note\nbeta;2",
+        );
+        let preamble = CsvPreamble {
+            skip_lines: 1,
+            comment: Some(b'#'),
+        };
+        assert_eq!(detect_delimiter_after(file.path(), preamble).expect("detect"), b';');
+
+        let data = CsvData::from_file_with_options(file.path(), b';', false, None, preamble, false)
+            .expect("parse csv");
+        assert_eq!(data.skipped_lines, 3);
+        assert_eq!(data.headers, vec!["name", "value"]);
+        assert_eq!(data.rows(), vec![vec!["alpha", "1"], vec!["beta", "2"]]);
+
+        let title_only = CsvPreamble {
+            skip_lines: 1,
+            comment: None,
+        };
+        let data =
+            CsvData::from_file_with_options(file.path(), b';', false, None, title_only, false)
+                .expect("parse csv");
+        assert_eq!(data.skipped_lines, 1);
+        assert_eq!(data.headers, vec!["# generated 2024-05-01"]);
+    }
+
+    #[test]
+    fn test_non_numeric_cells() {
+        let file = create_test_csv(
+            "name,price,qty\na,1.5,1\nb,n/a,2\nc,2.5,\nd,n/a,4\ne,?,5\nf,3.0,6",
+        );
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+
+        let coercions = data.non_numeric_cells();
+        assert_eq!(coercions.len(), 1);
+        assert_eq!(coercions[0].column, "price");
+        assert_eq!(coercions[0].count, 3);
+        assert_eq!(coercions[0].samples, vec!["n/a", "?"]);
+        assert_eq!(coercions[0].first_rows, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_infer_lat_lon_columns() {
+        let file = create_test_csv(
+            "name,Latitude,pickup_lng,long_note\na,52.5,13.4,x\nb,-33.9,151.2,y\nc,,-0.1,z",
+        );
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(infer_lat_lon_columns(&data), Some((1, 2)));
+
+        // Out-of-range values rule a column out
+        let file = create_test_csv("lat,lon\n95.0,10.0\n10.0,20.0");
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(infer_lat_lon_columns(&data), None);
+    }
+
+    #[test]
+    fn test_infer_type() {
+        assert_eq!(infer_type(&["1", "0", "TRUE", ""]), InferredType::Boolean);
+        assert_eq!(infer_type(&["1", "-20", "300"]), InferredType::Integer);
+        assert_eq!(infer_type(&["1", "2.5", "1e3"]), InferredType::Float);
+        assert_eq!(infer_type(&["2024-01-31", "2024/02/29"]), InferredType::Date);
+        assert_eq!(infer_type(&["31/12/2023", "12/31/2023"]), InferredType::Date);
+        assert_eq!(
+            infer_type(&["2024-01-31T08:30:00Z", "2024-01-31 23:59:59.125+02:00"]),
+            InferredType::Date
+        );
+        assert_eq!(infer_type(&["2024-13-01"]), InferredType::Text);
+        assert_eq!(infer_type(&["2024-01-31T25:00"]), InferredType::Text);
+        assert_eq!(infer_type(&["1", "apple"]), InferredType::Text);
+        assert_eq!(infer_type(&["", " "]), InferredType::Empty);
+    }
+
+    #[test]
+    fn test_infer_id_column() {
+        use std::fmt::Write as _;
+
+        let file = create_test_csv("name,Row_ID,price\na,7,1.5\nb,9,2.5\n");
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(infer_id_column(&data), Some(1));
+
+        // Unnamed: the first column of distinct integers or text, floats don't count
+        let mut content = String::from("price,group,code\n");
+        for i in 0..12 {
+            let _ = writeln!(content, "{i}.5,{},a{i}", i % 3);
+        }
+        let file = create_test_csv(&content);
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(infer_id_column(&data), Some(2));
+
+        // Too few rows to tell an id from a column that happens to be unique
+        let file = create_test_csv("code,price\na,1\nb,2\n");
+        let data = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(infer_id_column(&data), None);
+    }
+
+    #[test]
+    fn test_detect_comma() {
+        let file = create_test_csv("name,value,count\nalpha,1.5,10\n\"b;c\",2.5,20");
+        assert_eq!(detect_delimiter(file.path()).expect("detect"), b',');
     }
 
+    #[test]
+    fn test_detect_tab() {
+        let file = create_test_csv("name\tvalue\tcount\nalpha,x\t1.5\t10\nbeta\t2.5\t20");
+        assert_eq!(detect_delimiter(file.path()).expect("detect"), b'\t');
+    }
+
+    #[test]
+    fn test_detect_semicolon() {
+        let file = create_test_csv("name;value;count\nalpha;1,5;10\nbeta;2,5;20");
+        let delimiter = detect_delimiter(file.path()).expect("detect");
+        assert_eq!(delimiter, b';');
+
+        let data = CsvData::from_file_with_delimiter(file.path(), delimiter).expect("parse");
+        assert_eq!(data.col_count(), 3);
+        assert_eq!(data.delimiter_name(), "';'");
+    }
 }
@@ -1,4 +1,6 @@
+pub mod batch;
 pub mod client;
+pub mod replay;
 pub mod server;
 pub mod tools;
 
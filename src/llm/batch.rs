@@ -0,0 +1,170 @@
+//! Scripted access to the XML tools without the model (`z xml --batch`)
+
+use super::tools::ModifyToolHandler;
+use crate::context::ContextManager;
+use crate::structs::{FunctionCall, Result, ToolCall, ZError};
+use crate::xml::XmlModifier;
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, Write};
+
+/// Tools a batch command may call; all of them only touch the XML
+const BATCH_TOOLS: &[&str] = &[
+    "get_xml_structure",
+    "query_xml",
+    "get_xml_source",
+    "get_element",
+    "get_attribute",
+    "get_context",
+    "modify_xml",
+    "conditional_modify",
+    "append_records",
+    "format_xml",
+    "show_changes",
+];
+
+/// `modify_xml` operations, accepted as ops of their own
+const MODIFY_OPERATIONS: &[&str] = &[
+    "update_text",
+    "set_cdata",
+    "set_attribute",
+    "set_attributes",
+    "delete",
+    "insert",
+    "wrap",
+    "rename",
+];
+
+/// Run JSON commands from `input`, one object per line, against `xml`, writing one
+/// JSON result per line to `output`.
+///
+/// `op` names a tool (`query_xml`, or `query` for short) or a `modify_xml` operation
+/// (`set_attribute`); the command's other fields are the tool's arguments. An `id`
+/// is echoed in the result. `{"op": "save"}` writes the final document and ends the
+/// batch. A bad command gets an `"ok": false` result and the batch continues.
+///
+/// # Errors
+/// Returns error if reading `input` or writing `output` fails
+pub fn run_batch(xml: &XmlModifier, input: impl BufRead, mut output: impl Write) -> Result<()> {
+    let context = ContextManager::empty();
+    let mut handler = ModifyToolHandler::new(&context, xml);
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let n = i + 1;
+        let (id, response) = match serde_json::from_str::<Map<String, Value>>(&line) {
+            Ok(mut command) => {
+                let id = command.remove("id");
+                let op = command.remove("op");
+                let response = match op.as_ref().and_then(Value::as_str) {
+                    Some("save") => {
+                        let mut response = json!({
+                            "ok": true,
+                            "modifications": handler.get_modifications(),
+                            "document": xml.get_content(),
+                        });
+                        write_response(&mut output, id, &mut response)?;
+                        return Ok(());
+                    }
+                    Some(op) => run_command(&mut handler, n, op, command),
+                    None => Err(ZError::ToolCall("Command has no \"op\"".into())),
+                };
+                (id, response)
+            }
+            Err(e) => (None, Err(ZError::ToolCall(format!("Line {n} is not a JSON object: {e}")))),
+        };
+
+        let mut response = match response {
+            Ok(result) => json!({"ok": true, "result": result}),
+            Err(e) => json!({"ok": false, "error": e.to_string()}),
+        };
+        write_response(&mut output, id, &mut response)?;
+    }
+
+    eprintln!("Input ended without {{\"op\": \"save\"}}; the document was not written");
+    Ok(())
+}
+
+/// Call the tool `op` resolves to with the rest of the command as its arguments
+fn run_command(
+    handler: &mut ModifyToolHandler<'_>,
+    n: usize,
+    op: &str,
+    mut arguments: Map<String, Value>,
+) -> Result<String> {
+    let tool = if MODIFY_OPERATIONS.contains(&op) {
+        arguments.insert("operation".to_string(), json!(op));
+        "modify_xml".to_string()
+    } else if BATCH_TOOLS.contains(&op) {
+        op.to_string()
+    } else if BATCH_TOOLS.contains(&format!("{op}_xml").as_str()) {
+        format!("{op}_xml")
+    } else {
+        return Err(ZError::ToolCall(format!("Unknown op '{op}'")));
+    };
+
+    let call = ToolCall {
+        id: format!("batch_{n}"),
+        call_type: "function".to_string(),
+        function: FunctionCall {
+            name: tool,
+            arguments: Value::Object(arguments).to_string(),
+        },
+    };
+    Ok(handler.execute(&call)?.content)
+}
+
+/// Write one result line, tagged with the command's `id` if it had one
+fn write_response(output: &mut impl Write, id: Option<Value>, response: &mut Value) -> Result<()> {
+    if let Some(id) = id {
+        response["id"] = id;
+    }
+    writeln!(output, "{response}")?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_batch() {
+        let xml = XmlModifier::from_string(
+            r#"<items><item id="1">a</item><item id="2">b</item></items>"#.to_string(),
+        );
+        let input = [
+            r#"{"id": 1, "op": "query", "pattern": "item"}"#,
+            r#"{"op": "set_attribute", "path": "item[@id='2']", "attr_name": "x", "value": "y"}"#,
+            r#"{"op": "read_file", "filename": "a.csv"}"#,
+            "not json",
+            r#"{"op": "save"}"#,
+            r#"{"op": "delete", "path": "item"}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        run_batch(&xml, input.as_bytes(), &mut output).expect("run batch");
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .expect("utf8")
+            .lines()
+            .map(|l| serde_json::from_str(l).expect("json line"))
+            .collect();
+        assert_eq!(responses.len(), 5);
+        assert_eq!(responses[0]["id"], 1);
+        assert!(responses[0]["result"].as_str().expect("result").contains("id=\"2\""));
+        assert_eq!(responses[1]["ok"], true);
+        assert_eq!(responses[2]["ok"], false);
+        assert!(responses[2]["error"].as_str().expect("error").contains("Unknown op"));
+        assert_eq!(responses[3]["ok"], false);
+        assert_eq!(
+            responses[4]["document"],
+            r#"<items><item id="1">a</item><item id="2" x="y">b</item></items>"#
+        );
+        assert_eq!(responses[4]["modifications"].as_array().map(Vec::len), Some(1));
+        // Nothing after the save runs
+        assert!(xml.get_content().contains("<item id=\"1\">"));
+    }
+}
@@ -1,10 +1,21 @@
 use crate::context::ContextManager;
 use crate::llm::server::LlamaServer;
-use crate::llm::tools::{get_modify_tool_definitions, ModifyToolHandler};
-use crate::structs::{Message, Result, ToolCall, ToolDefinition, Usage, ZError};
-use crate::xml::XmlModifier;
+use crate::llm::tools::ModifyToolHandler;
+use crate::structs::{
+    FunctionCall, Message, Result, ToolCall, ToolDefinition, ToolFormat, Usage, ZError,
+};
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// How many times a crashed server is respawned during one conversation
+const MAX_RESPAWNS: u32 = 3;
+
+/// Delay before the first respawn; doubles on each further attempt
+const RESPAWN_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Per-request timeout for chat completions when none is configured
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_mins(2);
 
 /// Response from the LLM (private)
 #[derive(Debug, Deserialize)]
@@ -31,15 +42,19 @@ struct ResponseMessage {
 
 /// LLM client for conversation with tool calling
 pub struct LlmClient<'a> {
-    server: &'a LlamaServer,
+    server: &'a mut LlamaServer,
     messages: Vec<Message>,
     max_turns: usize,
     total_usage: Usage,
+    tool_format: ToolFormat,
+    request_timeout: Duration,
+    /// Prose of the latest assistant message that had any
+    final_message: Option<String>,
 }
 
 impl<'a> LlmClient<'a> {
     #[must_use]
-    pub fn new(server: &'a LlamaServer, system_prompt: &str, max_turns: usize) -> Self {
+    pub fn new(server: &'a mut LlamaServer, system_prompt: &str, max_turns: usize) -> Self {
         let messages = vec![Message {
             role: "system".to_string(),
             content: Some(system_prompt.to_string()),
@@ -52,15 +67,40 @@ impl<'a> LlmClient<'a> {
             messages,
             max_turns,
             total_usage: Usage::default(),
+            tool_format: ToolFormat::default(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            final_message: None,
         }
     }
 
+    /// Set how long one chat completion request may take. Separate from the server
+    /// startup timeout; a request retried after a server respawn gets the full timeout again.
+    #[must_use]
+    pub const fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Set how tool calls are read from responses
+    #[must_use]
+    pub const fn with_tool_format(mut self, tool_format: ToolFormat) -> Self {
+        self.tool_format = tool_format;
+        self
+    }
+
     /// Get total token usage
     #[must_use]
     pub const fn total_usage(&self) -> Usage {
         self.total_usage
     }
 
+    /// Text of the model's latest assistant message with prose, alongside its tool
+    /// calls or in place of them. Content carrying text-format tool calls is skipped.
+    #[must_use]
+    pub fn final_message(&self) -> Option<&str> {
+        self.final_message.as_deref()
+    }
+
     /// Add a user message
     pub fn add_user_message(&mut self, content: &str) {
         self.messages.push(Message {
@@ -77,20 +117,25 @@ impl<'a> LlmClient<'a> {
     /// Returns error if LLM communication fails
     pub fn run_modify_conversation(
         &mut self,
-        context: &ContextManager,
-        xml: &XmlModifier,
+        handler: &mut ModifyToolHandler<'_>,
     ) -> Result<Vec<String>> {
-        let mut handler = ModifyToolHandler::new(context, xml);
-        let tools = get_modify_tool_definitions();
+        let tools = handler.tool_definitions().to_vec();
 
         for turn in 0..self.max_turns {
             eprintln!("LLM turn {}/{}...", turn + 1, self.max_turns);
 
             // Make request to LLM
-            let response = self.send_request(tools)?;
+            let response = self.send_with_recovery(&tools)?;
+            let tool_calls = extract_tool_calls(self.tool_format, &response, turn);
+            // Text-format tool calls are embedded in the content, which is then not prose
+            let carries_calls = tool_calls.is_some() && native_tool_calls(&response).is_none();
+            let prose = response.content.as_deref().map(str::trim).filter(|c| !c.is_empty());
+            if let Some(prose) = prose.filter(|_| !carries_calls) {
+                self.final_message = Some(prose.to_string());
+            }
 
             // Check for tool calls
-            if let Some(tool_calls) = &response.tool_calls {
+            if let Some(tool_calls) = &tool_calls {
                 // Add assistant message with tool calls
                 self.messages.push(Message {
                     role: "assistant".to_string(),
@@ -99,11 +144,11 @@ impl<'a> LlmClient<'a> {
                     tool_call_id: None,
                 });
 
-                // Execute each tool call
+                // Execute tool calls (read-only ones in parallel)
                 for tool_call in tool_calls {
                     eprintln!("  Tool: {}()", tool_call.function.name);
-                    let result = handler.execute(tool_call)?;
-
+                }
+                for result in handler.execute_all(tool_calls)? {
                     // Add tool result message
                     self.messages.push(Message {
                         role: "tool".to_string(),
@@ -132,6 +177,34 @@ impl<'a> LlmClient<'a> {
         Ok(handler.get_modifications().to_vec())
     }
 
+    /// Send a request, respawning the server with exponential backoff if the
+    /// connection fails and the server no longer answers health checks.
+    /// The conversation so far is kept, so the current turn is simply retried.
+    fn send_with_recovery(&mut self, tools: &[ToolDefinition]) -> Result<ResponseMessage> {
+        let mut respawns = 0;
+        loop {
+            match self.send_request(tools) {
+                Err(e)
+                    if respawns < MAX_RESPAWNS
+                        && is_connection_error(&e)
+                        && !self.server.is_healthy() =>
+                {
+                    let delay = RESPAWN_BACKOFF * 2u32.pow(respawns);
+                    respawns += 1;
+                    eprintln!(
+                        "Warning: LLM server is down ({e}); respawning in {delay:?} \
+                         (attempt {respawns}/{MAX_RESPAWNS})"
+                    );
+                    std::thread::sleep(delay);
+                    if let Err(e) = self.server.respawn() {
+                        eprintln!("Warning: respawn failed: {e}");
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Send a request to the LLM
     fn send_request(&mut self, tools: &[ToolDefinition]) -> Result<ResponseMessage> {
         let body = json!({
@@ -145,7 +218,7 @@ impl<'a> LlmClient<'a> {
 
         let response = ureq::post(&self.server.completions_url())
             .set("Content-Type", "application/json")
-            .timeout(std::time::Duration::from_secs(120))
+            .timeout(self.request_timeout)
             .send_json(&body)?;
 
         let chat_response: ChatResponse = response
@@ -168,16 +241,147 @@ impl<'a> LlmClient<'a> {
     }
 }
 
-/// Build a minimal system prompt for the modify phase (~400 tokens)
+/// One-line tool summaries for the system prompt, in presentation order
+const TOOL_SUMMARIES: &[(&str, &str)] = &[
+    ("list_files", "See available context files"),
+    ("read_file", "Read a file's content"),
+    ("read_lines", "Read a line range of a long file"),
+    ("read_section", "Read one heading's section of a Markdown file"),
+    ("query_csv", "Filter/search CSV rows"),
+    ("count_csv", "Count CSV rows matching a filter"),
+    ("join_csv", "Combine two CSVs' rows on a shared key column"),
+    ("csv_stats", "Get statistics for a CSV column"),
+    ("search_context", "Find which files mention some text"),
+    ("get_xml_structure", "See XML hierarchy"),
+    ("query_xml", "Find elements by pattern"),
+    ("get_element", "Get specific element"),
+    ("get_attribute", "Get an attribute value"),
+    ("get_context", "Get an element's parent and siblings"),
+    ("get_xml_source", "Get an element's literal XML source"),
+    ("show_changes", "Review your modifications so far"),
+    ("modify_xml", "Insert/update/delete elements"),
+    ("apply_csv_mapping", "Set many elements' text/attribute from a CSV key-value mapping"),
+    ("conditional_modify", "Modify every matching element whose text/attribute meets a condition"),
+    ("append_records", "Add many new child elements in one call"),
+    ("format_xml", "Re-indent the XML consistently"),
+    ("finish", "Signal completion"),
+];
+
+/// Whether an error means the server couldn't be reached (as opposed to an HTTP error status)
+fn is_connection_error(e: &ZError) -> bool {
+    matches!(e, ZError::Http(err) if matches!(**err, ureq::Error::Transport(_)))
+}
+
+/// Get tool calls from a response according to `format`
+fn extract_tool_calls(
+    format: ToolFormat,
+    response: &ResponseMessage,
+    turn: usize,
+) -> Option<Vec<ToolCall>> {
+    let from_text = || {
+        let calls = parse_text_tool_calls(response.content.as_deref()?, turn);
+        (!calls.is_empty()).then_some(calls)
+    };
+
+    match format {
+        ToolFormat::Native => native_tool_calls(response),
+        ToolFormat::Text => from_text(),
+        ToolFormat::Auto => native_tool_calls(response).or_else(from_text),
+    }
+}
+
+/// The structured `tool_calls` of a response; an empty array counts as none, as some
+/// servers send one alongside text-format calls
+fn native_tool_calls(response: &ResponseMessage) -> Option<Vec<ToolCall>> {
+    response.tool_calls.clone().filter(|calls| !calls.is_empty())
+}
+
+/// Parse `<tool_call>{"name": ..., "arguments": {...}}</tool_call>` blocks from message content.
+/// Blocks that aren't valid JSON or lack a name are skipped.
+fn parse_text_tool_calls(content: &str, turn: usize) -> Vec<ToolCall> {
+    const OPEN: &str = "<tool_call>";
+    const CLOSE: &str = "</tool_call>";
+
+    let mut calls = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find(OPEN) {
+        let after_open = &rest[start + OPEN.len()..];
+        let Some(end) = after_open.find(CLOSE) else {
+            break;
+        };
+        let block = after_open[..end].trim();
+        rest = &after_open[end + CLOSE.len()..];
+
+        let Ok(parsed) = serde_json::from_str::<Value>(block) else {
+            eprintln!("Warning: could not parse text tool call: {block}");
+            continue;
+        };
+        let Some(name) = parsed.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let arguments = match parsed.get("arguments") {
+            Some(Value::String(s)) => s.clone(),
+            Some(v) => v.to_string(),
+            None => "{}".to_string(),
+        };
+
+        calls.push(ToolCall {
+            id: format!("text_call_{turn}_{}", calls.len()),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments,
+            },
+        });
+    }
+
+    calls
+}
+
+/// Build a minimal system prompt for the modify phase (~400 tokens).
+/// Only `tools` are listed.
+/// With `include_csv_summaries`, a bounded overview of each CSV context file is appended.
+/// Inline `instructions` are added as the primary task, ahead of any instructions file.
 #[must_use]
-pub fn build_modify_system_prompt(context: &ContextManager) -> String {
+pub fn build_modify_system_prompt(
+    context: &ContextManager,
+    include_csv_summaries: bool,
+    instructions: Option<&str>,
+    tools: &[ToolDefinition],
+) -> String {
+    use std::fmt::Write as _;
+
     let file_index = context.build_file_index_summary();
+    let instructions_section = instructions.map_or_else(String::new, |text| {
+        format!(
+            "\n## Primary Task\n{}\nThis task takes precedence over any instructions file in the context.\n",
+            text.trim()
+        )
+    });
+    let csv_section = if include_csv_summaries {
+        let summaries = context.build_csv_summaries();
+        if summaries.is_empty() {
+            String::new()
+        } else {
+            format!("\n## CSV Datasets\n{summaries}")
+        }
+    } else {
+        String::new()
+    };
+
+    let mut tool_list = String::new();
+    for (name, summary) in TOOL_SUMMARIES {
+        if tools.iter().any(|t| t.function.name == *name) {
+            let _ = writeln!(tool_list, "- {name}: {summary}");
+        }
+    }
 
     format!(
         r"You are an AI that modifies XML files based on ML analysis results.
 
 ## Available Context Files
-{file_index}
+{file_index}{csv_section}{instructions_section}
 ## Your Task
 1. Read context files to understand the ML analysis
 2. Explore the XML structure
@@ -186,15 +390,67 @@ pub fn build_modify_system_prompt(context: &ContextManager) -> String {
 5. Call finish when done
 
 ## Tools
-- list_files: See available context files
-- read_file: Read a file's content
-- query_csv: Filter/search CSV rows
-- get_xml_structure: See XML hierarchy
-- query_xml: Find elements by pattern
-- get_element: Get specific element
-- modify_xml: Insert/update/delete elements
-- finish: Signal completion
-
+{tool_list}
 Start by reading summary.txt to understand the analysis."
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_tool_calls() {
+        let content = r#"Let me look.
+<tool_call>{"name": "read_file", "arguments": {"filename": "summary.txt"}}</tool_call>
+<tool_call>not json</tool_call>
+<tool_call>
+{"name": "list_files", "arguments": "{}"}
+</tool_call>"#;
+
+        let calls = parse_text_tool_calls(content, 3);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(calls[0].function.arguments, r#"{"filename":"summary.txt"}"#);
+        assert_eq!(calls[0].id, "text_call_3_0");
+        assert_eq!(calls[1].function.name, "list_files");
+        assert_eq!(calls[1].function.arguments, "{}");
+
+        assert!(parse_text_tool_calls("plain answer", 0).is_empty());
+    }
+
+    #[test]
+    fn test_empty_native_tool_calls_fall_back_to_text() {
+        let content = r#"<tool_call>{"name": "list_files", "arguments": {}}</tool_call>"#;
+        let response: ResponseMessage = serde_json::from_value(
+            serde_json::json!({"role": "assistant", "content": content, "tool_calls": []}),
+        )
+        .expect("parse response");
+
+        let calls = extract_tool_calls(ToolFormat::Auto, &response, 0).expect("text calls");
+        assert_eq!(calls[0].function.name, "list_files");
+        assert!(extract_tool_calls(ToolFormat::Native, &response, 0).is_none());
+    }
+
+    #[test]
+    fn test_system_prompt_instructions() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+
+        let tools = crate::llm::tools::get_modify_tool_definitions();
+
+        let prompt =
+            build_modify_system_prompt(&context, false, Some("Mark item 3 as sold"), tools);
+        assert!(prompt.contains("## Primary Task\nMark item 3 as sold"));
+        assert!(prompt.contains("- modify_xml: Insert/update/delete elements\n"));
+
+        let prompt = build_modify_system_prompt(&context, false, None, tools);
+        assert!(!prompt.contains("Primary Task"));
+
+        let read_only = crate::llm::tools::select_tool_definitions(&[], &["modify_xml".into()])
+            .expect("select tools");
+        let prompt = build_modify_system_prompt(&context, false, None, &read_only);
+        assert!(!prompt.contains("- modify_xml"));
+        assert!(prompt.contains("- finish: Signal completion"));
+    }
+}
@@ -0,0 +1,152 @@
+//! Recorded modify-run edits (`--record-script`) and their replay (`z replay`)
+
+use super::tools::ModifyToolHandler;
+use crate::context::ContextManager;
+use crate::structs::{FunctionCall, ReplayScript, Result, ToolCall, ZError};
+use crate::xml::XmlModifier;
+use std::fs;
+use std::path::Path;
+
+/// Tools a script step may call; all of them only touch the XML
+const REPLAYABLE_TOOLS: &[&str] =
+    &["modify_xml", "format_xml", "conditional_modify", "append_records"];
+
+impl ReplayScript {
+    /// Load a script written by `--record-script`
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be read or is not a valid script
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| {
+            ZError::Config(format!("Invalid replay script {}: {e}", path.display()))
+        })
+    }
+
+    /// Write the script as pretty-printed JSON
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be written
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Apply every step of `script` to `xml` in order, returning the modifications made
+///
+/// # Errors
+/// Returns error if a step calls a tool other than `modify_xml`/`format_xml`/
+/// `conditional_modify`/`append_records`, or changes nothing (the XML doesn't match the one the
+/// script was recorded on)
+pub fn replay_script(xml: &XmlModifier, script: &ReplayScript) -> Result<Vec<String>> {
+    let context = ContextManager::empty();
+    let mut handler = ModifyToolHandler::new(&context, xml);
+
+    for (i, step) in script.steps.iter().enumerate() {
+        let n = i + 1;
+        if !REPLAYABLE_TOOLS.contains(&step.tool.as_str()) {
+            return Err(ZError::Config(format!(
+                "Replay step {n} uses unsupported tool '{}'",
+                step.tool
+            )));
+        }
+        let before = handler.get_modifications().len();
+        let call = ToolCall {
+            id: format!("replay_{n}"),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: step.tool.clone(),
+                arguments: step.arguments.to_string(),
+            },
+        };
+        let result = handler.execute(&call)?;
+        if handler.get_modifications().len() == before {
+            return Err(ZError::ToolCall(format!(
+                "Replay step {n} ({}) made no change: {}",
+                step.tool, result.content
+            )));
+        }
+    }
+
+    Ok(handler.get_modifications().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::ScriptStep;
+    use serde_json::json;
+
+    fn step(tool: &str, arguments: serde_json::Value) -> ScriptStep {
+        ScriptStep {
+            tool: tool.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        std::fs::write(dir.path().join("map.csv"), "id,status\n1,done\n").expect("write csv");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let original = r#"<root><item id="1"/><item id="2"/></root>"#;
+        let xml = XmlModifier::from_string(original.to_string());
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        let calls = [
+            ("get_element", json!({"path": "root"})),
+            ("modify_xml", json!({"operation": "delete", "path": "item[@id='2']"})),
+            ("modify_xml", json!({"operation": "delete", "path": "missing"})),
+            (
+                "apply_csv_mapping",
+                json!({"filename": "map.csv", "key_column": "id", "value_column": "status",
+                       "pattern": "item", "key_attr": "id", "attr_name": "status"}),
+            ),
+        ];
+        for (n, (name, args)) in calls.iter().enumerate() {
+            let call = ToolCall {
+                id: format!("call_{n}"),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: (*name).to_string(),
+                    arguments: args.to_string(),
+                },
+            };
+            handler.execute(&call).expect("execute");
+        }
+
+        let script = handler.script();
+        assert_eq!(
+            script.steps,
+            vec![
+                step("modify_xml", json!({"operation": "delete", "path": "item[@id='2']"})),
+                step(
+                    "modify_xml",
+                    json!({"operation": "set_attribute", "path": "item[@id='1']",
+                           "attr_name": "status", "value": "done"})
+                ),
+            ]
+        );
+
+        let path = dir.path().join("script.json");
+        script.write(&path).expect("write script");
+        let fresh = XmlModifier::from_string(original.to_string());
+        let applied = replay_script(&fresh, &ReplayScript::from_file(&path).expect("load"))
+            .expect("replay");
+        assert_eq!(applied.len(), 2);
+        assert_eq!(fresh.get_content(), xml.get_content());
+
+        // Replaying onto the already-edited XML fails: item 2 is gone
+        assert!(replay_script(&fresh, &script).is_err());
+    }
+
+    #[test]
+    fn test_replay_rejects_other_tools() {
+        let xml = XmlModifier::from_string("<root/>".to_string());
+        let script = ReplayScript {
+            steps: vec![step("finish", json!({}))],
+        };
+        assert!(replay_script(&xml, &script).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::color;
 use crate::structs::{Result, ZError};
 use std::net::TcpListener;
 use std::process::{Child, Command, Stdio};
@@ -6,10 +7,55 @@ use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// llama-server flags `LlamaServer` sets itself and extra arguments may not override
+const MANAGED_FLAGS: &[&str] = &["-m", "--model", "--port", "--host"];
+
+/// llama-server flags with a dedicated `z` option that extra arguments would shadow
+const SHADOWED_FLAGS: &[(&str, &str)] = &[
+    ("-c", "--context-size"),
+    ("--ctx-size", "--context-size"),
+    ("-ngl", "--gpu-layers"),
+    ("--gpu-layers", "--gpu-layers"),
+    ("--n-gpu-layers", "--gpu-layers"),
+];
+
+/// Check extra llama-server arguments, rejecting flags that would break the managed
+/// model and port and warning about ones that shadow a dedicated option
+///
+/// # Errors
+/// Returns error if an argument overrides the model, port or host
+pub fn validate_extra_args(args: &[String]) -> Result<()> {
+    for arg in args {
+        // `--port=8080` style values name the flag before the `=`
+        let flag = arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag);
+        if MANAGED_FLAGS.contains(&flag) {
+            return Err(ZError::Config(format!(
+                "Server argument '{arg}' is managed by z and cannot be overridden"
+            )));
+        }
+        if let Some((_, option)) = SHADOWED_FLAGS.iter().find(|(f, _)| *f == flag) {
+            eprintln!("Warning: server argument '{arg}' overrides {option}; prefer {option}");
+        }
+    }
+    Ok(())
+}
+
+/// Arguments the server was started with, kept so it can be respawned
+struct SpawnArgs {
+    server_path: String,
+    model_path: String,
+    context_size: u32,
+    gpu_layers: u32,
+    extra_args: Vec<String>,
+}
+
 /// Manages the llama-server child process
 pub struct LlamaServer {
     child: Option<Child>,
     port: u16,
+    args: SpawnArgs,
+    /// Leave the process running when dropped instead of killing it
+    leak_on_drop: bool,
 }
 
 impl LlamaServer {
@@ -23,38 +69,83 @@ impl LlamaServer {
         Ok(port)
     }
 
-    /// Spawn llama-server with the given model
+    /// Spawn llama-server with the given model, appending `extra_args` to the standard arguments.
+    /// With `leak_on_drop` the process outlives this handle (and `z`) for manual debugging.
     ///
     /// # Errors
     /// Returns error if server fails to start
-    pub fn spawn(server_path: &str, model_path: &str, context_size: u32, gpu_layers: u32) -> Result<Self> {
+    pub fn spawn(
+        server_path: &str,
+        model_path: &str,
+        context_size: u32,
+        gpu_layers: u32,
+        extra_args: &[String],
+        leak_on_drop: bool,
+    ) -> Result<Self> {
+        let mut server = Self {
+            child: None,
+            port: 0,
+            args: SpawnArgs {
+                server_path: server_path.to_string(),
+                model_path: model_path.to_string(),
+                context_size,
+                gpu_layers,
+                extra_args: extra_args.to_vec(),
+            },
+            leak_on_drop,
+        };
+        server.start()?;
+        Ok(server)
+    }
+
+    /// Start the process on a fresh port and wait until it is healthy
+    fn start(&mut self) -> Result<()> {
         let port = Self::find_available_port()?;
 
         eprintln!("Starting llama-server on port {port}...");
 
-        let child = Command::new(server_path)
+        let child = Command::new(&self.args.server_path)
             .args([
-                "-m", model_path,
+                "-m", &self.args.model_path,
                 "--port", &port.to_string(),
-                "-c", &context_size.to_string(),
-                "-ngl", &gpu_layers.to_string(),
+                "-c", &self.args.context_size.to_string(),
+                "-ngl", &self.args.gpu_layers.to_string(),
                 "--log-disable",
             ])
+            .args(&self.args.extra_args)
             .stdout(Stdio::null())
-            .stderr(Stdio::piped())
+            // A kept server must not write into a pipe that closes when z exits
+            .stderr(if self.leak_on_drop { Stdio::null() } else { Stdio::piped() })
             .spawn()
             .map_err(|e| ZError::LlmServer(format!("Failed to spawn llama-server: {e}")))?;
 
-        let server = Self {
-            child: Some(child),
-            port,
-        };
+        self.child = Some(child);
+        self.port = port;
 
         // Wait for server to be ready
-        server.wait_for_health(Duration::from_secs(30))?;
+        self.wait_for_health(Duration::from_secs(30))?;
 
         eprintln!("llama-server ready on port {port}");
-        Ok(server)
+        Ok(())
+    }
+
+    /// Restart the server process (e.g. after it crashed) with the original arguments.
+    /// A previous failed start leaves no process, and is simply retried.
+    ///
+    /// # Errors
+    /// Returns error if the new process fails to start
+    pub fn respawn(&mut self) -> Result<()> {
+        self.kill();
+        self.start()
+    }
+
+    /// Whether the server answers its health endpoint
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        ureq::get(&format!("{}/health", self.url()))
+            .timeout(Duration::from_secs(2))
+            .call()
+            .is_ok_and(|response| response.status() == 200)
     }
 
     /// Get the server URL
@@ -101,11 +192,36 @@ impl LlamaServer {
             let _ = child.wait();
         }
     }
+
+    /// Let the server process outlive this handle, printing how to stop it
+    fn detach(&mut self) -> Option<Child> {
+        let child = self.child.take()?;
+        let pid = child.id();
+        eprintln!(
+            "{} --keep-server: llama-server is still running at {} (PID {pid}).\n\
+             It will not exit on its own; stop it with: kill {pid}",
+            color::warning(),
+            self.url()
+        );
+        Some(child)
+    }
+
+    /// Stop the server process, or with `leak_on_drop` detach it and hand back
+    /// the still-running child
+    fn release(&mut self) -> Option<Child> {
+        if self.leak_on_drop {
+            self.detach()
+        } else {
+            self.kill();
+            None
+        }
+    }
 }
 
 impl Drop for LlamaServer {
     fn drop(&mut self) {
-        self.kill();
+        // Dropping a Child leaves its process running
+        drop(self.release());
     }
 }
 
@@ -122,9 +238,66 @@ pub fn setup_panic_hook(shutdown_flag: Arc<AtomicBool>) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_validate_extra_args() {
+        let args = |list: &[&str]| list.iter().map(|s| (*s).to_string()).collect::<Vec<_>>();
+
+        assert!(validate_extra_args(&args(&["--flash-attn", "--parallel", "2"])).is_ok());
+        assert!(validate_extra_args(&args(&["-c", "4096"])).is_ok());
+        assert!(validate_extra_args(&args(&["--port", "8080"])).is_err());
+        assert!(validate_extra_args(&args(&["--port=8080"])).is_err());
+        assert!(validate_extra_args(&args(&["-m", "other.gguf"])).is_err());
+    }
+
     #[test]
     fn test_find_available_port() {
         let port = LlamaServer::find_available_port().expect("find port");
         assert!(port > 0);
     }
+
+    #[test]
+    fn test_respawn_retries_failed_spawn() {
+        let mut server = LlamaServer {
+            child: None,
+            port: LlamaServer::find_available_port().expect("find port"),
+            args: SpawnArgs {
+                server_path: "/nonexistent/llama-server".to_string(),
+                model_path: "model.gguf".to_string(),
+                context_size: 512,
+                gpu_layers: 0,
+                extra_args: Vec::new(),
+            },
+            leak_on_drop: false,
+        };
+
+        assert!(!server.is_healthy());
+        // Each attempt spawns again rather than giving up once no process is left
+        for _ in 0..2 {
+            let err = server.respawn().expect_err("spawn fails");
+            assert!(err.to_string().contains("Failed to spawn"));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_leak_on_drop_keeps_process() {
+        let mut server = LlamaServer {
+            child: Some(Command::new("sleep").arg("30").spawn().expect("spawn sleep")),
+            port: 0,
+            args: SpawnArgs {
+                server_path: "sleep".to_string(),
+                model_path: String::new(),
+                context_size: 0,
+                gpu_layers: 0,
+                extra_args: Vec::new(),
+            },
+            leak_on_drop: true,
+        };
+
+        let mut child = server.release().expect("detached child");
+        drop(server);
+        assert!(child.try_wait().expect("try_wait").is_none());
+        child.kill().expect("kill");
+        child.wait().expect("wait");
+    }
 }
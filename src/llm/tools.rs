@@ -2,7 +2,8 @@
 
 use crate::context::ContextManager;
 use crate::structs::{
-    FunctionDefinition, Result, ToolCall, ToolDefinition, ToolResult, ZError,
+    ConditionalOperation, ElementCondition, FunctionDefinition, NewElement, ReplayScript, Result,
+    ScriptStep, ToolCall, ToolDefinition, ToolResult, ZError,
 };
 use crate::xml::XmlModifier;
 use serde_json::{json, Value};
@@ -24,6 +25,7 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                     "required": []
                 }),
             },
+            read_only: true,
         },
         ToolDefinition {
             tool_type: "function".to_string(),
@@ -42,6 +44,57 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                     "required": ["filename"]
                 }),
             },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_lines".to_string(),
+                description: "Read a range of lines from a context file. Use this to page through files too long for read_file."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "filename": {
+                            "type": "string",
+                            "description": "The filename to read"
+                        },
+                        "start": {
+                            "type": "integer",
+                            "description": "First line to return, 1-based (default: 1)"
+                        },
+                        "count": {
+                            "type": "integer",
+                            "description": "Number of lines to return (default and max: 50)"
+                        }
+                    },
+                    "required": ["filename"]
+                }),
+            },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "read_section".to_string(),
+                description: "Read the content under one heading of a Markdown context file, including its subsections. Lists the available headings if not found."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "filename": {
+                            "type": "string",
+                            "description": "The Markdown filename"
+                        },
+                        "heading": {
+                            "type": "string",
+                            "description": "Heading text, e.g. 'Rules' or '## Rules'"
+                        }
+                    },
+                    "required": ["filename", "heading"]
+                }),
+            },
+            read_only: true,
         },
         ToolDefinition {
             tool_type: "function".to_string(),
@@ -68,6 +121,111 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                     "required": ["filename"]
                 }),
             },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "count_csv".to_string(),
+                description: "Count the rows of a CSV context file matching a filter, without returning them. Uses the same filter as query_csv."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "filename": {
+                            "type": "string",
+                            "description": "The CSV filename to count rows in"
+                        },
+                        "filter": {
+                            "type": "string",
+                            "description": "Optional text filter to match rows (default: count all rows)"
+                        }
+                    },
+                    "required": ["filename"]
+                }),
+            },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "join_csv".to_string(),
+                description: "Inner-join two CSV context files on a shared key column (e.g. clusters.csv and anomalies.csv on row_id) and return the combined rows."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "left": {
+                            "type": "string",
+                            "description": "First CSV filename; its row order is kept"
+                        },
+                        "right": {
+                            "type": "string",
+                            "description": "Second CSV filename"
+                        },
+                        "key": {
+                            "type": "string",
+                            "description": "Column present in both files to join on"
+                        },
+                        "filter": {
+                            "type": "string",
+                            "description": "Optional text filter to match joined rows"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum rows to return (default: 20)"
+                        }
+                    },
+                    "required": ["left", "right", "key"]
+                }),
+            },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "csv_stats".to_string(),
+                description: "Get descriptive statistics (count, mean, std, min, quartiles, max) for a numeric column of a CSV context file."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "filename": {
+                            "type": "string",
+                            "description": "The CSV filename"
+                        },
+                        "column": {
+                            "type": "string",
+                            "description": "Column header name"
+                        }
+                    },
+                    "required": ["filename", "column"]
+                }),
+            },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "search_context".to_string(),
+                description: "Search all context files for a text substring. Returns matching filename, line number and line."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "Text to search for"
+                        },
+                        "case_insensitive": {
+                            "type": "boolean",
+                            "description": "Ignore case when matching (default: true)"
+                        }
+                    },
+                    "required": ["query"]
+                }),
+            },
+            read_only: true,
         },
         // XML tools
         ToolDefinition {
@@ -81,23 +239,56 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                     "required": []
                 }),
             },
+            read_only: true,
         },
         ToolDefinition {
             tool_type: "function".to_string(),
             function: FunctionDefinition {
                 name: "query_xml".to_string(),
-                description: "Find XML elements matching a path pattern. Supports: element, parent/child, element[@attr='value']".to_string(),
+                description: "Find XML elements matching a path pattern and/or at a given depth. Supports: element, parent/child, element[@attr='value']. Reports the total match count; use offset to page through large results.".to_string(),
                 parameters: json!({
                     "type": "object",
                     "properties": {
                         "pattern": {
                             "type": "string",
                             "description": "Path pattern to match (e.g., 'item', 'items/item', 'item[@id=\"1\"]')"
+                        },
+                        "depth": {
+                            "type": "integer",
+                            "description": "Only elements at exactly this depth (root is 0); with pattern, elements matching both"
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "Number of matches to skip, for paging (default: 0)"
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum matches to return (default and max: the configured page size)"
                         }
                     },
-                    "required": ["pattern"]
+                    "required": []
                 }),
             },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "get_xml_source".to_string(),
+                description: "Get the literal XML source of the first element matching a pattern, including its children, comments and formatting. Use before editing mixed content or nested structure."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Path pattern of the element (e.g., 'items/item', 'item[@id=\"1\"]')"
+                        }
+                    },
+                    "required": ["path"]
+                }),
+            },
+            read_only: true,
         },
         ToolDefinition {
             tool_type: "function".to_string(),
@@ -115,19 +306,60 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                     "required": ["path"]
                 }),
             },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "get_attribute".to_string(),
+                description: "Get the value of one attribute on the first element matching a path pattern.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Path pattern of the element (e.g., 'item[@id=\"1\"]')"
+                        },
+                        "attr_name": {
+                            "type": "string",
+                            "description": "Name of the attribute to read"
+                        }
+                    },
+                    "required": ["path", "attr_name"]
+                }),
+            },
+            read_only: true,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "get_context".to_string(),
+                description: "Get the first element matching a pattern along with its parent and its preceding and following siblings.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Path pattern of the element (e.g., 'item[@id=\"1\"]')"
+                        }
+                    },
+                    "required": ["path"]
+                }),
+            },
+            read_only: true,
         },
         // Modification tool
         ToolDefinition {
             tool_type: "function".to_string(),
             function: FunctionDefinition {
                 name: "modify_xml".to_string(),
-                description: "Modify the XML file. Operations: update_text, set_attribute, delete, insert".to_string(),
+                description: "Modify the XML file. Operations: update_text, set_cdata, set_attribute, set_attributes, delete, insert, wrap, rename".to_string(),
                 parameters: json!({
                     "type": "object",
                     "properties": {
                         "operation": {
                             "type": "string",
-                            "enum": ["update_text", "set_attribute", "delete", "insert"],
+                            "enum": ["update_text", "set_cdata", "set_attribute", "set_attributes", "delete", "insert", "wrap", "rename"],
                             "description": "The modification operation"
                         },
                         "path": {
@@ -136,7 +368,7 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                         },
                         "value": {
                             "type": "string",
-                            "description": "New text value (for update_text) or attribute value (for set_attribute)"
+                            "description": "New text value (for update_text or set_cdata) or attribute value (for set_attribute). Use set_cdata for text containing markup"
                         },
                         "attr_name": {
                             "type": "string",
@@ -144,20 +376,186 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                         },
                         "element_name": {
                             "type": "string",
-                            "description": "Name of new element (for insert)"
+                            "description": "Name of new element (for insert), of the new parent (for wrap), or the element's new tag name (for rename)"
                         },
                         "attributes": {
                             "type": "object",
-                            "description": "Attributes for new element (for insert)"
+                            "description": "Attributes for new element (for insert or wrap) or attributes to write (for set_attributes)"
+                        },
+                        "replace": {
+                            "type": "boolean",
+                            "description": "Discard existing attributes instead of merging (for set_attributes, default: false)"
                         },
                         "text": {
                             "type": "string",
                             "description": "Text content for new element (for insert)"
+                        },
+                        "all": {
+                            "type": "boolean",
+                            "description": "Wrap every matching element, not just the first (for wrap, default: false)"
+                        },
+                        "group": {
+                            "type": "boolean",
+                            "description": "Wrap consecutive matching siblings together in one parent (for wrap, default: false)"
                         }
                     },
                     "required": ["operation", "path"]
                 }),
             },
+            read_only: false,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "apply_csv_mapping".to_string(),
+                description: "For each row of a CSV context file, find the XML element whose key attribute equals the row's key column and set its text (or an attribute) to the row's value column. Reports how many elements were updated and which keys had no match."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "filename": {
+                            "type": "string",
+                            "description": "The CSV filename holding the mapping"
+                        },
+                        "key_column": {
+                            "type": "string",
+                            "description": "CSV column with the key to look up"
+                        },
+                        "value_column": {
+                            "type": "string",
+                            "description": "CSV column with the value to write"
+                        },
+                        "pattern": {
+                            "type": "string",
+                            "description": "Path pattern of the elements to update, without an [@...] filter (e.g., 'items/item')"
+                        },
+                        "key_attr": {
+                            "type": "string",
+                            "description": "XML attribute compared against the key (e.g., 'id')"
+                        },
+                        "attr_name": {
+                            "type": "string",
+                            "description": "Attribute to set to the value (default: replace the element's text)"
+                        }
+                    },
+                    "required": ["filename", "key_column", "value_column", "pattern", "key_attr"]
+                }),
+            },
+            read_only: false,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "conditional_modify".to_string(),
+                description: "Apply one operation to every element matching a pattern whose text or attribute meets a condition, e.g. set status=\"high\" on every item whose price text is > 1000. Reports how many elements were changed."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": {
+                            "type": "string",
+                            "description": "Path pattern of the candidate elements (e.g., 'items/item')"
+                        },
+                        "condition": {
+                            "type": "string",
+                            "description": "Condition on the element's text or an attribute, compared numerically when both sides are numbers: 'text > 1000', \"@status == 'open'\", '@qty <= 0'. Operators: < <= > >= == !="
+                        },
+                        "operation": {
+                            "type": "string",
+                            "enum": ["set_attribute", "update_text", "delete"],
+                            "description": "What to do to each element that meets the condition"
+                        },
+                        "attr_name": {
+                            "type": "string",
+                            "description": "Attribute name (for set_attribute)"
+                        },
+                        "value": {
+                            "type": "string",
+                            "description": "Attribute value (for set_attribute) or new text (for update_text)"
+                        }
+                    },
+                    "required": ["pattern", "condition", "operation"]
+                }),
+            },
+            read_only: false,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "append_records".to_string(),
+                description: "Append many new elements, in order, as the last children of the first element matching a parent pattern, in one call. Prefer this over repeated insert operations when adding several records."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "parent": {
+                            "type": "string",
+                            "description": "Path pattern of the parent element (e.g., 'log')"
+                        },
+                        "records": {
+                            "type": "array",
+                            "description": "Elements to append",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": {
+                                        "type": "string",
+                                        "description": "Element name"
+                                    },
+                                    "attributes": {
+                                        "type": "object",
+                                        "description": "Attribute names and values"
+                                    },
+                                    "text": {
+                                        "type": "string",
+                                        "description": "Text content (default: a self-closing element)"
+                                    }
+                                },
+                                "required": ["name"]
+                            }
+                        }
+                    },
+                    "required": ["parent", "records"]
+                }),
+            },
+            read_only: false,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "format_xml".to_string(),
+                description: "Re-indent the whole XML document consistently. Text, comments and CDATA are kept. Use as a final cleanup before finish."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "indent": {
+                            "type": "string",
+                            "description": "One level of indentation: spaces or a tab (default: two spaces)"
+                        }
+                    }
+                }),
+            },
+            read_only: false,
+        },
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: "show_changes".to_string(),
+                description: "List the modifications made so far, optionally with a line diff of the current XML against the original. Check this before editing to avoid repeating or undoing earlier changes."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "diff": {
+                            "type": "boolean",
+                            "description": "Include a diff against the original XML (default: false)"
+                        }
+                    },
+                    "required": []
+                }),
+            },
+            read_only: true,
         },
         // Completion tool
         ToolDefinition {
@@ -171,11 +569,16 @@ static MODIFY_TOOL_DEFINITIONS: LazyLock<Vec<ToolDefinition>> = LazyLock::new(||
                         "summary": {
                             "type": "string",
                             "description": "Brief summary of modifications made"
+                        },
+                        "no_changes_needed": {
+                            "type": "boolean",
+                            "description": "Set to true to confirm that the XML intentionally needs no modifications"
                         }
                     },
                     "required": ["summary"]
                 }),
             },
+            read_only: false,
         },
     ]
 });
@@ -186,46 +589,345 @@ pub fn get_modify_tool_definitions() -> &'static [ToolDefinition] {
     &MODIFY_TOOL_DEFINITIONS
 }
 
+/// Tool definitions narrowed to `enable` (all tools when empty) minus `disable`.
+/// `finish` is always kept so the model can end the run.
+///
+/// # Errors
+/// Returns error if a name doesn't match any tool
+pub fn select_tool_definitions(
+    enable: &[String],
+    disable: &[String],
+) -> Result<Vec<ToolDefinition>> {
+    if let Some(unknown) = enable
+        .iter()
+        .chain(disable)
+        .find(|name| !MODIFY_TOOL_DEFINITIONS.iter().any(|t| t.function.name == **name))
+    {
+        let known: Vec<&str> = MODIFY_TOOL_DEFINITIONS
+            .iter()
+            .map(|t| t.function.name.as_str())
+            .collect();
+        return Err(ZError::Config(format!(
+            "Unknown tool: {unknown}. Tools: {}",
+            known.join(", ")
+        )));
+    }
+
+    Ok(MODIFY_TOOL_DEFINITIONS
+        .iter()
+        .filter(|t| {
+            let name = &t.function.name;
+            name == "finish"
+                || ((enable.is_empty() || enable.contains(name)) && !disable.contains(name))
+        })
+        .cloned()
+        .collect())
+}
+
+/// Whether a tool is marked read-only in its definition
+#[must_use]
+pub fn is_read_only_tool(name: &str) -> bool {
+    MODIFY_TOOL_DEFINITIONS
+        .iter()
+        .any(|t| t.read_only && t.function.name == name)
+}
+
+/// Consecutive calls to nonexistent tools after which the run is aborted
+const MAX_UNKNOWN_TOOL_STREAK: usize = 3;
+
+/// Calls in a row with unparseable arguments after which the run is aborted
+const MAX_INVALID_ARGS_STREAK: usize = 3;
+
+/// Maximum characters of malformed arguments echoed back to the model
+const MAX_ECHOED_ARGS: usize = 1000;
+
+/// Unmatched keys listed by name in an `apply_csv_mapping` result
+const MAX_UNMATCHED_KEYS: usize = 20;
+
+/// Maximum characters of diff returned by `show_changes`
+const MAX_DIFF_OUTPUT: usize = 2000;
+
+/// Largest old x new line count compared line-by-line; bigger changes diff as one block
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// Parse a tool call's JSON arguments; blank arguments count as `{}`
+fn parse_tool_args(tool_call: &ToolCall) -> serde_json::Result<Value> {
+    let raw = tool_call.function.arguments.trim();
+    if raw.is_empty() {
+        return Ok(json!({}));
+    }
+    serde_json::from_str(raw)
+}
+
 /// Tool handler for the modify phase
 pub struct ModifyToolHandler<'a> {
     context: &'a ContextManager,
     xml: &'a XmlModifier,
     modifications: Vec<String>,
+    /// Successful XML-changing calls, in order, for `--record-script`
+    script: Vec<ScriptStep>,
+    original: String,
     finished: bool,
+    /// The `summary` the model passed to an accepted `finish`
+    finish_summary: Option<String>,
+    require_modifications: bool,
+    no_changes_confirmed: bool,
+    tools: Vec<ToolDefinition>,
+    elements_per_query: usize,
+    /// Calls in a row to tools that don't exist
+    unknown_tool_streak: usize,
+    /// Calls in a row whose arguments weren't valid JSON
+    invalid_args_streak: usize,
 }
 
 impl<'a> ModifyToolHandler<'a> {
     #[must_use]
-    pub const fn new(context: &'a ContextManager, xml: &'a XmlModifier) -> Self {
+    pub fn new(context: &'a ContextManager, xml: &'a XmlModifier) -> Self {
         Self {
             context,
             xml,
             modifications: Vec::new(),
+            script: Vec::new(),
+            original: xml.get_content(),
             finished: false,
+            finish_summary: None,
+            require_modifications: false,
+            no_changes_confirmed: false,
+            tools: get_modify_tool_definitions().to_vec(),
+            elements_per_query: crate::xml::modifier::MAX_XML_ELEMENTS,
+            unknown_tool_streak: 0,
+            invalid_args_streak: 0,
         }
     }
 
+    /// Page size (and maximum `limit`) for `query_xml` results
+    #[must_use]
+    pub const fn with_elements_per_query(mut self, elements: usize) -> Self {
+        self.elements_per_query = elements;
+        self
+    }
+
+    /// Restrict the tools offered to the model; calls to any other tool are rejected
+    #[must_use]
+    pub fn with_tools(mut self, tools: Vec<ToolDefinition>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Tool definitions offered to the model in this run
+    #[must_use]
+    pub fn tool_definitions(&self) -> &[ToolDefinition] {
+        &self.tools
+    }
+
+    /// Result for a call to a tool that isn't enabled, or `None` if it is
+    fn reject_disabled(&self, tool_call: &ToolCall) -> Option<ToolResult> {
+        let name = &tool_call.function.name;
+        if self.tools.iter().any(|t| t.function.name == *name) {
+            return None;
+        }
+        Some(ToolResult {
+            tool_call_id: tool_call.id.clone(),
+            content: format!("Tool '{name}' is disabled for this run"),
+        })
+    }
+
+    /// Result listing the available tools for a call to a tool that doesn't exist, so the
+    /// model can correct itself
+    ///
+    /// # Errors
+    /// Returns error once `MAX_UNKNOWN_TOOL_STREAK` such calls happen in a row
+    fn reject_unknown(&mut self, tool_call: &ToolCall) -> Result<ToolResult> {
+        let name = &tool_call.function.name;
+        self.unknown_tool_streak += 1;
+        if self.unknown_tool_streak >= MAX_UNKNOWN_TOOL_STREAK {
+            return Err(ZError::ToolCall(format!(
+                "Model called unknown tools {MAX_UNKNOWN_TOOL_STREAK} times in a row \
+                 (last: '{name}')"
+            )));
+        }
+        let available: Vec<&str> = self.tools.iter().map(|t| t.function.name.as_str()).collect();
+        Ok(ToolResult {
+            tool_call_id: tool_call.id.clone(),
+            content: format!(
+                "Unknown tool '{name}'; available tools are: {}",
+                available.join(", ")
+            ),
+        })
+    }
+
+    /// Result echoing malformed arguments back so the model can resend the call
+    ///
+    /// # Errors
+    /// Returns error once `MAX_INVALID_ARGS_STREAK` such calls happen in a row
+    fn reject_invalid_args(
+        &mut self,
+        tool_call: &ToolCall,
+        error: &serde_json::Error,
+    ) -> Result<ToolResult> {
+        let name = &tool_call.function.name;
+        self.invalid_args_streak += 1;
+        if self.invalid_args_streak >= MAX_INVALID_ARGS_STREAK {
+            return Err(ZError::ToolCall(format!(
+                "Model sent invalid JSON arguments {MAX_INVALID_ARGS_STREAK} times in a row \
+                 (last: '{name}': {error})"
+            )));
+        }
+        let raw = &tool_call.function.arguments;
+        let echoed = match raw.char_indices().nth(MAX_ECHOED_ARGS) {
+            Some((end, _)) => format!("{}...", &raw[..end]),
+            None => raw.clone(),
+        };
+        Ok(ToolResult {
+            tool_call_id: tool_call.id.clone(),
+            content: format!(
+                "Arguments for '{name}' were not valid JSON ({error}), please resend the call \
+                 with the arguments as a JSON object. Received: {echoed}"
+            ),
+        })
+    }
+
+    /// Reject `finish` with no modifications unless the model sets `no_changes_needed`
+    #[must_use]
+    pub const fn with_require_modifications(mut self, require: bool) -> Self {
+        self.require_modifications = require;
+        self
+    }
+
     /// Execute a tool call and return the result
     ///
     /// # Errors
     /// Returns error if tool execution fails
     pub fn execute(&mut self, tool_call: &ToolCall) -> Result<ToolResult> {
-        let args: Value =
-            serde_json::from_str(&tool_call.function.arguments).unwrap_or_else(|e| {
-                eprintln!("Warning: malformed tool args: {e}");
-                json!({})
+        if !MODIFY_TOOL_DEFINITIONS
+            .iter()
+            .any(|t| t.function.name == tool_call.function.name)
+        {
+            return self.reject_unknown(tool_call);
+        }
+        self.unknown_tool_streak = 0;
+
+        let args = match parse_tool_args(tool_call) {
+            Ok(args) => args,
+            Err(e) => return self.reject_invalid_args(tool_call, &e),
+        };
+        self.invalid_args_streak = 0;
+
+        if is_read_only_tool(&tool_call.function.name) {
+            return self.execute_read_only(tool_call);
+        }
+        if let Some(rejected) = self.reject_disabled(tool_call) {
+            return Ok(rejected);
+        }
+
+        let before = self.modifications.len();
+        let name = tool_call.function.name.as_str();
+        let content = match name {
+            "modify_xml" => self.handle_modify_xml(&args)?,
+            "format_xml" => self.handle_format_xml(&args)?,
+            "apply_csv_mapping" => self.handle_apply_csv_mapping(&args)?,
+            "conditional_modify" => self.handle_conditional_modify(&args)?,
+            "append_records" => self.handle_append_records(&args)?,
+            "finish" => self.handle_finish(&args),
+            name => return Err(ZError::ToolCall(format!("Unknown tool: {name}"))),
+        };
+        // apply_csv_mapping records its own per-element steps
+        if matches!(
+            name,
+            "modify_xml" | "format_xml" | "conditional_modify" | "append_records"
+        )
+            && self.modifications.len() > before
+        {
+            self.script.push(ScriptStep {
+                tool: name.to_string(),
+                arguments: args,
             });
+        }
+
+        Ok(ToolResult {
+            tool_call_id: tool_call.id.clone(),
+            content,
+        })
+    }
 
+    /// Execute all tool calls from one assistant turn.
+    ///
+    /// Runs of consecutive read-only calls execute in parallel; mutating calls and
+    /// calls with malformed arguments run one at a time in the order given. Results
+    /// keep the order of `tool_calls`.
+    ///
+    /// # Errors
+    /// Returns the first error from any tool call
+    pub fn execute_all(&mut self, tool_calls: &[ToolCall]) -> Result<Vec<ToolResult>> {
+        let mut results = Vec::with_capacity(tool_calls.len());
+        let mut i = 0;
+
+        while i < tool_calls.len() {
+            let batch_len = tool_calls[i..]
+                .iter()
+                .take_while(|c| is_read_only_tool(&c.function.name) && parse_tool_args(c).is_ok())
+                .count();
+
+            if batch_len > 1 {
+                self.unknown_tool_streak = 0;
+                self.invalid_args_streak = 0;
+                let batch = &tool_calls[i..i + batch_len];
+                let this: &Self = self;
+                let batch_results: Vec<Result<ToolResult>> = std::thread::scope(|scope| {
+                    // Spawn every call before joining any of them
+                    #[allow(clippy::needless_collect)]
+                    let handles: Vec<_> = batch
+                        .iter()
+                        .map(|call| scope.spawn(move || this.execute_read_only(call)))
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|h| {
+                            h.join().unwrap_or_else(|_| {
+                                Err(ZError::ToolCall("Tool execution panicked".into()))
+                            })
+                        })
+                        .collect()
+                });
+                for result in batch_results {
+                    results.push(result?);
+                }
+                i += batch_len;
+            } else {
+                results.push(self.execute(&tool_calls[i])?);
+                i += 1;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Execute a tool call that doesn't mutate handler or XML state
+    fn execute_read_only(&self, tool_call: &ToolCall) -> Result<ToolResult> {
+        if let Some(rejected) = self.reject_disabled(tool_call) {
+            return Ok(rejected);
+        }
+
+        // Callers route malformed arguments to `reject_invalid_args` first
+        let args = parse_tool_args(tool_call)?;
         let content = match tool_call.function.name.as_str() {
             "list_files" => self.handle_list_files(),
             "read_file" => self.handle_read_file(&args)?,
+            "read_lines" => self.handle_read_lines(&args)?,
+            "read_section" => self.handle_read_section(&args)?,
             "query_csv" => self.handle_query_csv(&args)?,
+            "join_csv" => self.handle_join_csv(&args)?,
+            "count_csv" => self.handle_count_csv(&args)?,
+            "search_context" => self.handle_search_context(&args)?,
+            "csv_stats" => self.handle_csv_stats(&args)?,
             "get_xml_structure" => self.handle_get_xml_structure()?,
             "query_xml" => self.handle_query_xml(&args)?,
             "get_element" => self.handle_get_element(&args)?,
-            "modify_xml" => self.handle_modify_xml(&args)?,
-            "finish" => self.handle_finish(&args),
-            name => return Err(ZError::ToolCall(format!("Unknown tool: {name}"))),
+            "get_attribute" => self.handle_get_attribute(&args)?,
+            "get_context" => self.handle_get_context(&args)?,
+            "get_xml_source" => self.handle_get_xml_source(&args)?,
+            "show_changes" => self.handle_show_changes(&args),
+            name => return Err(ZError::ToolCall(format!("Not a read-only tool: {name}"))),
         };
 
         Ok(ToolResult {
@@ -246,6 +948,27 @@ impl<'a> ModifyToolHandler<'a> {
         &self.modifications
     }
 
+    /// The successful XML-changing calls so far as a replayable script. `apply_csv_mapping`
+    /// is recorded as one `modify_xml` step per updated element, so replay needs no context.
+    #[must_use]
+    pub fn script(&self) -> ReplayScript {
+        ReplayScript {
+            steps: self.script.clone(),
+        }
+    }
+
+    /// The summary the model gave when it finished, if any
+    #[must_use]
+    pub fn finish_summary(&self) -> Option<&str> {
+        self.finish_summary.as_deref()
+    }
+
+    /// Whether the model finished while explicitly confirming no changes were needed
+    #[must_use]
+    pub const fn no_changes_confirmed(&self) -> bool {
+        self.no_changes_confirmed
+    }
+
     fn handle_list_files(&self) -> String {
         use std::fmt::Write as _;
 
@@ -268,6 +991,39 @@ impl<'a> ModifyToolHandler<'a> {
         self.context.read_file(filename)
     }
 
+    fn handle_read_lines(&self, args: &Value) -> Result<String> {
+        let filename = args
+            .get("filename")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing filename parameter".into()))?;
+
+        let start = args
+            .get("start")
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(1);
+        let count = args
+            .get("count")
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(crate::context::MAX_READ_LINES);
+
+        self.context.read_lines(filename, start, count)
+    }
+
+    fn handle_read_section(&self, args: &Value) -> Result<String> {
+        let filename = args
+            .get("filename")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing filename parameter".into()))?;
+        let heading = args
+            .get("heading")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing heading parameter".into()))?;
+
+        self.context.read_section(filename, heading)
+    }
+
     fn handle_query_csv(&self, args: &Value) -> Result<String> {
         let filename = args
             .get("filename")
@@ -283,6 +1039,80 @@ impl<'a> ModifyToolHandler<'a> {
         self.context.query_csv(filename, filter, limit)
     }
 
+    fn handle_count_csv(&self, args: &Value) -> Result<String> {
+        let filename = args
+            .get("filename")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing filename parameter".into()))?;
+        let filter = args.get("filter").and_then(Value::as_str);
+
+        let count = self.context.count_csv(filename, filter)?;
+        Ok(filter.map_or_else(
+            || format!("{count} row(s) in {filename}"),
+            |f| format!("{count} row(s) in {filename} matching '{f}'"),
+        ))
+    }
+
+    fn handle_join_csv(&self, args: &Value) -> Result<String> {
+        let param = |name: &str| {
+            args.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| ZError::ToolCall(format!("Missing {name} parameter")))
+        };
+        let left = param("left")?;
+        let right = param("right")?;
+        let key = param("key")?;
+
+        let filter = args.get("filter").and_then(Value::as_str);
+        let limit = args
+            .get("limit")
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok());
+
+        self.context.join_csv(left, right, key, filter, limit)
+    }
+
+    fn handle_csv_stats(&self, args: &Value) -> Result<String> {
+        let filename = args
+            .get("filename")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing filename parameter".into()))?;
+        let column = args
+            .get("column")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing column parameter".into()))?;
+
+        Ok(self.context.column_stats(filename, column)?.summary())
+    }
+
+    fn handle_search_context(&self, args: &Value) -> Result<String> {
+        use std::fmt::Write as _;
+
+        let query = args
+            .get("query")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing query parameter".into()))?;
+        let case_insensitive = args
+            .get("case_insensitive")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+
+        let matches = self.context.search(query, case_insensitive);
+        if matches.is_empty() {
+            return Ok(format!("No matches for '{query}'"));
+        }
+
+        let mut output = format!("Found {} match(es) for '{query}':\n", matches.len());
+        for (filename, line_no, line) in &matches {
+            let _ = writeln!(output, "{filename}:{line_no}: {line}");
+        }
+        if matches.len() >= crate::context::MAX_SEARCH_RESULTS {
+            let _ = writeln!(output, "[Results capped at {}]", crate::context::MAX_SEARCH_RESULTS);
+        }
+
+        Ok(output)
+    }
+
     fn handle_get_xml_structure(&self) -> Result<String> {
         use std::fmt::Write as _;
 
@@ -308,25 +1138,98 @@ impl<'a> ModifyToolHandler<'a> {
     fn handle_query_xml(&self, args: &Value) -> Result<String> {
         use std::fmt::Write as _;
 
-        let pattern = args
-            .get("pattern")
-            .and_then(Value::as_str)
-            .ok_or_else(|| ZError::ToolCall("Missing pattern parameter".into()))?;
+        let pattern = args.get("pattern").and_then(Value::as_str);
+        let depth = args
+            .get("depth")
+            .map(|d| {
+                d.as_u64()
+                    .and_then(|n| usize::try_from(n).ok())
+                    .ok_or_else(|| ZError::ToolCall(format!("depth out of range: {d}")))
+            })
+            .transpose()?;
+
+        let offset = args
+            .get("offset")
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(0);
+        let limit = args
+            .get("limit")
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(self.elements_per_query)
+            .clamp(1, self.elements_per_query);
 
-        let elements = self.xml.query(pattern)?;
+        let (elements, total, criteria) = match (pattern, depth) {
+            (Some(pattern), None) => {
+                let (elements, total) = self.xml.query(pattern, offset, limit)?;
+                (elements, total, format!("matching '{pattern}'"))
+            }
+            (Some(pattern), Some(depth)) => {
+                let (all, _) = self.xml.query(pattern, 0, usize::MAX)?;
+                let at_depth: Vec<_> = all.into_iter().filter(|e| e.depth == depth).collect();
+                let total = at_depth.len();
+                let page = at_depth.into_iter().skip(offset).take(limit).collect();
+                (page, total, format!("matching '{pattern}' at depth {depth}"))
+            }
+            (None, Some(depth)) => {
+                let at_depth = self.xml.query_by_depth(depth)?;
+                let total = at_depth.len();
+                let page = at_depth.into_iter().skip(offset).take(limit).collect();
+                (page, total, format!("at depth {depth}"))
+            }
+            (None, None) => {
+                return Err(ZError::ToolCall("Provide a pattern, a depth, or both".into()))
+            }
+        };
 
+        if total == 0 {
+            return Ok(format!("No elements {criteria}"));
+        }
         if elements.is_empty() {
-            return Ok(format!("No elements matching '{pattern}'"));
+            return Ok(format!(
+                "Offset {offset} is past the last of {total} element(s) {criteria}"
+            ));
         }
 
-        let mut output = format!("Found {} element(s) matching '{}':\n", elements.len(), pattern);
+        let end = offset + elements.len();
+        let mut output = if offset == 0 && end == total {
+            format!("Found {total} element(s) {criteria}:\n")
+        } else {
+            format!("Found {total} element(s) {criteria}, showing {}-{end}:\n", offset + 1)
+        };
         for elem in &elements {
             let _ = writeln!(output, "- {}", elem.display());
         }
+        if end < total {
+            let _ = writeln!(output, "[{} more; use offset {end} for the next page]", total - end);
+        }
 
         Ok(output)
     }
 
+    fn handle_get_xml_source(&self, args: &Value) -> Result<String> {
+        use crate::xml::modifier::MAX_XML_SOURCE;
+
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing path parameter".into()))?;
+
+        let Some(source) = self.xml.get_source(path)? else {
+            return Ok(format!("No element matching '{path}'"));
+        };
+
+        match source.char_indices().nth(MAX_XML_SOURCE) {
+            Some((end, _)) => Ok(format!(
+                "{}\n[Truncated: {} chars total]",
+                &source[..end],
+                source.chars().count()
+            )),
+            None => Ok(source),
+        }
+    }
+
     fn handle_get_element(&self, args: &Value) -> Result<String> {
         let path = args
             .get("path")
@@ -339,6 +1242,74 @@ impl<'a> ModifyToolHandler<'a> {
         )
     }
 
+    fn handle_get_attribute(&self, args: &Value) -> Result<String> {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing path parameter".into()))?;
+        let attr_name = args
+            .get("attr_name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing attr_name parameter".into()))?;
+
+        if let Some(value) = self.xml.get_attribute(path, attr_name)? {
+            return Ok(value);
+        }
+        let (_, total) = self.xml.query(path, 0, 0)?;
+        if total == 0 {
+            Ok(format!("No element matching '{path}'"))
+        } else {
+            Ok(format!("Attribute '{attr_name}' not present on element matching '{path}'"))
+        }
+    }
+
+    fn handle_get_context(&self, args: &Value) -> Result<String> {
+        let path = args
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing path parameter".into()))?;
+
+        let Some(ctx) = self.xml.element_context(path)? else {
+            return Ok(format!("No element matching '{path}'"));
+        };
+
+        let describe = |e: Option<&crate::structs::XmlElement>| {
+            e.map_or_else(|| "(none)".to_string(), crate::structs::XmlElement::display)
+        };
+
+        Ok(format!(
+            "Element: {}\nParent: {}\nPreceding sibling: {}\nFollowing sibling: {}",
+            ctx.element.display(),
+            describe(ctx.parent.as_ref()),
+            describe(ctx.preceding_sibling.as_ref()),
+            describe(ctx.following_sibling.as_ref()),
+        ))
+    }
+
+    fn handle_show_changes(&self, args: &Value) -> String {
+        use std::fmt::Write as _;
+
+        let mut output = if self.modifications.is_empty() {
+            "No modifications made yet.\n".to_string()
+        } else {
+            format!("Modifications so far ({}):\n", self.modifications.len())
+        };
+        for (i, modification) in self.modifications.iter().enumerate() {
+            let _ = writeln!(output, "{}. {modification}", i + 1);
+        }
+
+        if args.get("diff").and_then(Value::as_bool).unwrap_or(false) {
+            let diff = line_diff(&self.original, &self.xml.get_content());
+            if diff.is_empty() {
+                output.push_str("\nXML is unchanged from the original.\n");
+            } else {
+                let _ = write!(output, "\nDiff against the original XML:\n{diff}");
+            }
+        }
+
+        output
+    }
+
     fn handle_modify_xml(&mut self, args: &Value) -> Result<String> {
         let operation = args
             .get("operation")
@@ -352,15 +1323,198 @@ impl<'a> ModifyToolHandler<'a> {
 
         let result = match operation {
             "update_text" => self.handle_update_text(args, path)?,
+            "set_cdata" => self.handle_set_cdata(args, path)?,
             "set_attribute" => self.handle_set_attribute(args, path)?,
+            "set_attributes" => self.handle_set_attributes(args, path)?,
             "delete" => self.handle_delete(path)?,
             "insert" => self.handle_insert(args, path)?,
+            "wrap" => self.handle_wrap(args, path)?,
+            "rename" => self.handle_rename(args, path)?,
             _ => return Err(ZError::ToolCall(format!("Unknown operation: {operation}"))),
         };
 
         Ok(result)
     }
 
+    fn handle_format_xml(&mut self, args: &Value) -> Result<String> {
+        let indent = args.get("indent").and_then(Value::as_str).unwrap_or("  ");
+
+        let before = self.xml.get_content();
+        self.xml.reformat(indent)?;
+        if self.xml.get_content() == before {
+            return Ok("XML already formatted".to_string());
+        }
+
+        self.modifications.push("format_xml".to_string());
+        Ok("XML reformatted".to_string())
+    }
+
+    fn handle_apply_csv_mapping(&mut self, args: &Value) -> Result<String> {
+        use std::fmt::Write as _;
+
+        let param = |name: &str| {
+            args.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| ZError::ToolCall(format!("Missing {name} parameter")))
+        };
+        let filename = param("filename")?;
+        let key_column = param("key_column")?;
+        let value_column = param("value_column")?;
+        let pattern = param("pattern")?;
+        let key_attr = param("key_attr")?;
+        let attr_name = args.get("attr_name").and_then(Value::as_str);
+        // Each row's key becomes a `[@key_attr='key']` filter, and a pattern holds only one
+        if pattern.contains('[') {
+            return Err(ZError::ToolCall(format!(
+                "apply_csv_mapping pattern can't have its own [@...] filter: {pattern}"
+            )));
+        }
+
+        let pairs = self.context.column_pairs(filename, key_column, value_column)?;
+
+        let mut updated = 0;
+        let mut unmatched = Vec::new();
+        let mut unsupported = Vec::new();
+        for (key, value) in &pairs {
+            // The filter syntax strips quotes and ends at the first `]`, so such keys
+            // would match a different value
+            if key.contains(['\'', '"', ']']) {
+                unsupported.push(key.as_str());
+                continue;
+            }
+            let path = format!("{pattern}[@{key_attr}='{key}']");
+            let modified = match attr_name {
+                Some(attr) => self.xml.set_attribute(&path, attr, value)?,
+                None => self.xml.update_text(&path, value)?,
+            };
+            if modified {
+                updated += 1;
+                let mut arguments =
+                    json!({"operation": "update_text", "path": path, "value": value});
+                if let Some(attr) = attr_name {
+                    arguments["operation"] = json!("set_attribute");
+                    arguments["attr_name"] = json!(attr);
+                }
+                self.script.push(ScriptStep {
+                    tool: "modify_xml".to_string(),
+                    arguments,
+                });
+            } else {
+                unmatched.push(key.as_str());
+            }
+        }
+
+        let target = attr_name.map_or_else(|| "text".to_string(), |a| format!("@{a}"));
+        if updated > 0 {
+            self.modifications.push(format!(
+                "apply_csv_mapping: {filename} {key_column}->{value_column} onto \
+                 {pattern}[@{key_attr}] {target} ({updated} element(s))"
+            ));
+        }
+
+        let mut output = format!(
+            "Updated {target} of {updated} element(s) from {} row(s) of {filename}",
+            pairs.len()
+        );
+        for (keys, reason) in [
+            (&unmatched, "had no matching element"),
+            (&unsupported, "contain quotes or ']' and were skipped"),
+        ] {
+            if keys.is_empty() {
+                continue;
+            }
+            let listed: Vec<&str> = keys.iter().take(MAX_UNMATCHED_KEYS).copied().collect();
+            let _ = write!(output, "\n{} key(s) {reason}: {}", keys.len(), listed.join(", "));
+            if keys.len() > MAX_UNMATCHED_KEYS {
+                let _ = write!(output, ", ... and {} more", keys.len() - MAX_UNMATCHED_KEYS);
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn handle_conditional_modify(&mut self, args: &Value) -> Result<String> {
+        let param = |name: &str| {
+            args.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| ZError::ToolCall(format!("Missing {name} parameter")))
+        };
+        let pattern = param("pattern")?;
+        let condition_text = param("condition")?;
+        let operation_name = param("operation")?;
+
+        let condition = ElementCondition::parse(condition_text)
+            .map_err(|e| ZError::ToolCall(e.to_string()))?;
+        let operation = match operation_name {
+            "set_attribute" => ConditionalOperation::SetAttribute {
+                name: param("attr_name")?.to_string(),
+                value: param("value")?.to_string(),
+            },
+            "update_text" => ConditionalOperation::UpdateText(param("value")?.to_string()),
+            "delete" => ConditionalOperation::Delete,
+            _ => return Err(ZError::ToolCall(format!("Unknown operation: {operation_name}"))),
+        };
+
+        let count = self.xml.conditional_modify(pattern, &condition, &operation)?;
+        if count == 0 {
+            return Ok(format!("No elements matching '{pattern}' where {condition_text}"));
+        }
+
+        self.modifications.push(format!(
+            "conditional_modify: {pattern} where {condition_text}: {operation_name} \
+             ({count} element(s))"
+        ));
+        Ok(format!(
+            "Applied {operation_name} to {count} element(s) matching '{pattern}' where \
+             {condition_text}"
+        ))
+    }
+
+    fn handle_append_records(&mut self, args: &Value) -> Result<String> {
+        let parent = args
+            .get("parent")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing parent parameter".into()))?;
+        let records = args
+            .get("records")
+            .and_then(Value::as_array)
+            .filter(|records| !records.is_empty())
+            .ok_or_else(|| ZError::ToolCall("records must be a non-empty array".into()))?;
+
+        let records = records
+            .iter()
+            .enumerate()
+            .map(|(i, record)| {
+                let name = record.get("name").and_then(Value::as_str).ok_or_else(|| {
+                    ZError::ToolCall(format!("Record {} has no name", i + 1))
+                })?;
+                let attributes = record
+                    .get("attributes")
+                    .and_then(Value::as_object)
+                    .map(|obj| {
+                        obj.iter()
+                            .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Ok(NewElement {
+                    name: name.to_string(),
+                    attributes,
+                    text: record.get("text").and_then(Value::as_str).map(String::from),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if !self.xml.append_records(parent, &records)? {
+            return Ok("No matching parent element found".to_string());
+        }
+        self.modifications.push(format!(
+            "append_records: {parent} <- {} record(s)",
+            records.len()
+        ));
+        Ok(format!("Appended {} record(s) to {parent}", records.len()))
+    }
+
     fn handle_update_text(&mut self, args: &Value, path: &str) -> Result<String> {
         let value = args
             .get("value")
@@ -377,6 +1531,22 @@ impl<'a> ModifyToolHandler<'a> {
         }
     }
 
+    fn handle_set_cdata(&mut self, args: &Value, path: &str) -> Result<String> {
+        let value = args
+            .get("value")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing value for set_cdata".into()))?;
+
+        let modified = self.xml.set_cdata(path, value)?;
+        if modified {
+            self.modifications
+                .push(format!("set_cdata: {path} = '{value}'"));
+            Ok("CDATA content set successfully".to_string())
+        } else {
+            Ok("No matching element found".to_string())
+        }
+    }
+
     fn handle_set_attribute(&mut self, args: &Value, path: &str) -> Result<String> {
         let attr_name = args
             .get("attr_name")
@@ -398,6 +1568,32 @@ impl<'a> ModifyToolHandler<'a> {
         }
     }
 
+    fn handle_set_attributes(&mut self, args: &Value, path: &str) -> Result<String> {
+        let attributes: Vec<(String, String)> = args
+            .get("attributes")
+            .and_then(Value::as_object)
+            .map(|obj| {
+                obj.iter()
+                    .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                    .collect()
+            })
+            .ok_or_else(|| ZError::ToolCall("Missing attributes for set_attributes".into()))?;
+
+        let replace = args.get("replace").and_then(Value::as_bool).unwrap_or(false);
+
+        let modified = self.xml.set_attributes(path, &attributes, replace)?;
+        if modified {
+            let mode = if replace { "replace" } else { "merge" };
+            self.modifications.push(format!(
+                "set_attributes ({mode}): {path} {} attribute(s)",
+                attributes.len()
+            ));
+            Ok("Attributes set successfully".to_string())
+        } else {
+            Ok("No matching element found".to_string())
+        }
+    }
+
     fn handle_delete(&mut self, path: &str) -> Result<String> {
         let modified = self.xml.delete_element(path)?;
         if modified {
@@ -436,12 +1632,75 @@ impl<'a> ModifyToolHandler<'a> {
         }
     }
 
+    fn handle_wrap(&mut self, args: &Value, path: &str) -> Result<String> {
+        let wrapper_name = args
+            .get("element_name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing element_name for wrap".into()))?;
+
+        let attributes: Vec<(String, String)> = args
+            .get("attributes")
+            .and_then(Value::as_object)
+            .map(|obj| {
+                obj.iter()
+                    .filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let all = args.get("all").and_then(Value::as_bool).unwrap_or(false);
+        let group = args.get("group").and_then(Value::as_bool).unwrap_or(false);
+
+        let wrapped = self
+            .xml
+            .wrap_elements(path, wrapper_name, &attributes, all, group)?;
+        if wrapped > 0 {
+            self.modifications
+                .push(format!("wrap: {path} in <{wrapper_name}> ({wrapped} element(s))"));
+            Ok(format!("Wrapped {wrapped} element(s) in <{wrapper_name}>"))
+        } else {
+            Ok("No matching element found".to_string())
+        }
+    }
+
+    fn handle_rename(&mut self, args: &Value, path: &str) -> Result<String> {
+        let new_name = args
+            .get("element_name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ZError::ToolCall("Missing element_name for rename".into()))?;
+
+        let modified = self.xml.rename_element(path, new_name)?;
+        if modified {
+            self.modifications.push(format!("rename: {path} -> <{new_name}>"));
+            Ok(format!("Element renamed to <{new_name}>"))
+        } else {
+            Ok("No matching element found".to_string())
+        }
+    }
+
     fn handle_finish(&mut self, args: &Value) -> String {
+        let no_changes_needed = args
+            .get("no_changes_needed")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        if self.modifications.is_empty() {
+            if self.require_modifications && !no_changes_needed {
+                return "Finish rejected: no modifications have been made. Either make the \
+                        required changes with modify_xml, or call finish again with \
+                        no_changes_needed=true if the XML intentionally needs no changes."
+                    .to_string();
+            }
+            self.no_changes_confirmed = no_changes_needed;
+        }
+
         self.finished = true;
-        let summary = args
+        self.finish_summary = args
             .get("summary")
             .and_then(Value::as_str)
-            .unwrap_or("Modifications complete");
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from);
+        let summary = self.finish_summary.as_deref().unwrap_or("Modifications complete");
 
         format!(
             "Finished: {summary}\nTotal modifications: {}",
@@ -450,6 +1709,94 @@ impl<'a> ModifyToolHandler<'a> {
     }
 }
 
+/// Line diff of `old` to `new` as `@@ -line +line @@` hunks of `-`/`+` lines,
+/// truncated to `MAX_DIFF_OUTPUT` characters. Empty when the texts match.
+fn line_diff(old: &str, new: &str) -> String {
+    use std::fmt::Write as _;
+
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    // Edit script over the differing middle: (' ' | '-' | '+', line)
+    let mut ops: Vec<(char, &str)> = Vec::new();
+    if old_mid.len().saturating_mul(new_mid.len()) <= MAX_DIFF_CELLS {
+        // lcs[i][j] = LCS length of old_mid[i..] and new_mid[j..]
+        let width = new_mid.len() + 1;
+        let mut lcs = vec![0usize; (old_mid.len() + 1) * width];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                lcs[i * width + j] = if old_mid[i] == new_mid[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < old_mid.len() || j < new_mid.len() {
+            if i < old_mid.len() && j < new_mid.len() && old_mid[i] == new_mid[j] {
+                ops.push((' ', old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if j == new_mid.len()
+                || (i < old_mid.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                ops.push(('-', old_mid[i]));
+                i += 1;
+            } else {
+                ops.push(('+', new_mid[j]));
+                j += 1;
+            }
+        }
+    } else {
+        ops.extend(old_mid.iter().map(|line| ('-', *line)));
+        ops.extend(new_mid.iter().map(|line| ('+', *line)));
+    }
+
+    let mut diff = String::new();
+    let (mut old_line, mut new_line) = (prefix + 1, prefix + 1);
+    let mut in_hunk = false;
+    for (op, line) in ops {
+        if op == ' ' {
+            old_line += 1;
+            new_line += 1;
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            let _ = writeln!(diff, "@@ -{old_line} +{new_line} @@");
+            in_hunk = true;
+        }
+        let _ = writeln!(diff, "{op}{line}");
+        if op == '-' {
+            old_line += 1;
+        } else {
+            new_line += 1;
+        }
+    }
+
+    if diff.len() > MAX_DIFF_OUTPUT {
+        let mut end = MAX_DIFF_OUTPUT;
+        while !diff.is_char_boundary(end) {
+            end -= 1;
+        }
+        end = diff[..end].rfind('\n').map_or(end, |i| i + 1);
+        diff.truncate(end);
+        diff.push_str("... (diff truncated)\n");
+    }
+    diff
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -462,10 +1809,465 @@ mod tests {
         let names: Vec<_> = tools.iter().map(|t| t.function.name.as_str()).collect();
         assert!(names.contains(&"list_files"));
         assert!(names.contains(&"read_file"));
+        assert!(names.contains(&"read_lines"));
         assert!(names.contains(&"query_csv"));
+        assert!(names.contains(&"join_csv"));
+        assert!(names.contains(&"count_csv"));
+        assert!(names.contains(&"search_context"));
+        assert!(names.contains(&"csv_stats"));
+        assert!(names.contains(&"read_section"));
+        assert!(names.contains(&"format_xml"));
         assert!(names.contains(&"get_xml_structure"));
         assert!(names.contains(&"query_xml"));
+        assert!(names.contains(&"get_context"));
+        assert!(names.contains(&"get_attribute"));
+        assert!(names.contains(&"show_changes"));
+        assert!(names.contains(&"get_xml_source"));
         assert!(names.contains(&"modify_xml"));
+        assert!(names.contains(&"apply_csv_mapping"));
+        assert!(names.contains(&"conditional_modify"));
+        assert!(names.contains(&"append_records"));
         assert!(names.contains(&"finish"));
     }
+
+    fn tool_call(id: &str, name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: crate::structs::FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    fn finish_call(arguments: &str) -> ToolCall {
+        tool_call("call_1", "finish", arguments)
+    }
+
+    #[test]
+    fn test_read_only_flags() {
+        assert!(is_read_only_tool("query_xml"));
+        assert!(is_read_only_tool("read_file"));
+        assert!(!is_read_only_tool("modify_xml"));
+        assert!(!is_read_only_tool("finish"));
+        assert!(!is_read_only_tool("unknown"));
+    }
+
+    #[test]
+    fn test_execute_all_preserves_order() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string("<root><a>1</a><b>2</b></root>".to_string());
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        let calls = vec![
+            tool_call("c1", "query_xml", r#"{"pattern": "a"}"#),
+            tool_call("c2", "query_xml", r#"{"pattern": "b"}"#),
+            tool_call(
+                "c3",
+                "modify_xml",
+                r#"{"operation": "update_text", "path": "a", "value": "9"}"#,
+            ),
+            tool_call("c4", "query_xml", r#"{"pattern": "a"}"#),
+        ];
+
+        let results = handler.execute_all(&calls).expect("execute");
+        let ids: Vec<_> = results.iter().map(|r| r.tool_call_id.as_str()).collect();
+        assert_eq!(ids, vec!["c1", "c2", "c3", "c4"]);
+        assert!(results[0].content.contains("root/a: \"1\""));
+        assert!(results[1].content.contains("root/b: \"2\""));
+        assert!(results[3].content.contains("root/a: \"9\""));
+    }
+
+    #[test]
+    fn test_finish_requires_modifications() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string("<root/>".to_string());
+        let mut handler = ModifyToolHandler::new(&context, &xml).with_require_modifications(true);
+
+        let result = handler
+            .execute(&finish_call(r#"{"summary": "done"}"#))
+            .expect("execute");
+        assert!(result.content.starts_with("Finish rejected"));
+        assert!(!handler.is_finished());
+        assert_eq!(handler.finish_summary(), None);
+
+        handler
+            .execute(&finish_call(r#"{"summary": "nothing to do", "no_changes_needed": true}"#))
+            .expect("execute");
+        assert!(handler.is_finished());
+        assert!(handler.no_changes_confirmed());
+        assert_eq!(handler.finish_summary(), Some("nothing to do"));
+    }
+
+    #[test]
+    fn test_tool_selection() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string("<root><a/></root>".to_string());
+
+        let tools = select_tool_definitions(&["get_xml_structure".into()], &[]).expect("select");
+        let names: Vec<&str> = tools.iter().map(|t| t.function.name.as_str()).collect();
+        assert_eq!(names, vec!["get_xml_structure", "finish"]);
+
+        let tools = select_tool_definitions(&[], &["modify_xml".into(), "finish".into()])
+            .expect("select");
+        assert!(tools.iter().any(|t| t.function.name == "finish"));
+        assert!(!tools.iter().any(|t| t.function.name == "modify_xml"));
+        assert!(select_tool_definitions(&["nope".into()], &[]).is_err());
+
+        let mut handler = ModifyToolHandler::new(&context, &xml).with_tools(tools);
+        let result = handler
+            .execute(&tool_call(
+                "call_1",
+                "modify_xml",
+                r#"{"operation": "delete", "path": "a"}"#,
+            ))
+            .expect("execute");
+        assert_eq!(result.content, "Tool 'modify_xml' is disabled for this run");
+        assert!(handler.get_modifications().is_empty());
+        assert!(xml.get_content().contains("<a/>"));
+    }
+
+    #[test]
+    fn test_unknown_tool() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string("<root/>".to_string());
+        let tools = select_tool_definitions(&["get_xml_structure".into()], &[]).expect("select");
+        let mut handler = ModifyToolHandler::new(&context, &xml).with_tools(tools);
+        let mut call = |name: &str| handler.execute(&tool_call("call_1", name, "{}"));
+
+        let result = call("edit_xml").expect("recoverable");
+        assert_eq!(
+            result.content,
+            "Unknown tool 'edit_xml'; available tools are: get_xml_structure, finish"
+        );
+        // A valid call in between resets the streak
+        call("edit_xml").expect("recoverable");
+        call("get_xml_structure").expect("execute");
+        call("edit_xml").expect("recoverable");
+        call("edit_xml").expect("recoverable");
+        assert!(call("edit_xml").is_err());
+    }
+
+    #[test]
+    fn test_invalid_tool_args() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string("<root><a/></root>".to_string());
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+        let bad = r#"{operation: "delete", "path": "a",}"#;
+
+        let result = handler
+            .execute(&tool_call("call_1", "modify_xml", bad))
+            .expect("recoverable");
+        assert!(result.content.starts_with("Arguments for 'modify_xml' were not valid JSON"));
+        assert!(result.content.ends_with(&format!("Received: {bad}")));
+        assert!(xml.get_content().contains("<a/>"));
+
+        // Blank arguments are an empty object, and a valid call resets the streak
+        let results = handler
+            .execute_all(&[
+                tool_call("call_2", "get_xml_structure", ""),
+                tool_call("call_3", "query_xml", bad),
+                tool_call("call_4", "get_xml_structure", "{}"),
+            ])
+            .expect("execute");
+        assert!(!results[0].content.contains("not valid JSON"));
+        assert!(results[1].content.contains("not valid JSON"));
+        assert!(!results[2].content.contains("not valid JSON"));
+
+        let mut call = || handler.execute(&tool_call("call_5", "query_xml", bad));
+        call().expect("recoverable");
+        call().expect("recoverable");
+        assert!(call().is_err());
+    }
+
+    #[test]
+    fn test_show_changes() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string("<root>\n  <a>1</a>\n  <b>2</b>\n</root>\n".to_string());
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        let show = tool_call("call_1", "show_changes", r#"{"diff": true}"#);
+        let result = handler.execute(&show).expect("execute");
+        assert!(result.content.contains("No modifications made yet"));
+        assert!(result.content.contains("unchanged"));
+
+        handler
+            .execute(&tool_call(
+                "call_2",
+                "modify_xml",
+                r#"{"operation": "update_text", "path": "a", "value": "9"}"#,
+            ))
+            .expect("execute");
+        let result = handler.execute(&show).expect("execute");
+        assert!(result.content.contains("Modifications so far (1):\n1. "));
+        assert!(result.content.contains("@@ -2 +2 @@\n-  <a>1</a>\n+  <a>9</a>\n"));
+
+        let result = handler.execute(&tool_call("call_3", "show_changes", "{}")).expect("execute");
+        assert!(!result.content.contains("@@"));
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(line_diff("a\nb\nc", "a\nb\nc"), "");
+        assert_eq!(
+            line_diff("a\nb\nc", "a\nx\nc\nd"),
+            "@@ -2 +2 @@\n-b\n+x\n@@ -4 +4 @@\n+d\n"
+        );
+
+        let old = (0..1000).map(|i| format!("line {i}")).collect::<Vec<_>>().join("\n");
+        let diff = line_diff(&old, "");
+        assert!(diff.len() <= MAX_DIFF_OUTPUT + 30);
+        assert!(diff.ends_with("... (diff truncated)\n"));
+    }
+
+    #[test]
+    fn test_query_xml_paging() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string(
+            "<root><item>1</item><item>2</item><item>3</item><item>4</item><item>5</item></root>"
+                .to_string(),
+        );
+        let handler = ModifyToolHandler::new(&context, &xml).with_elements_per_query(2);
+        let query = |args: &str| {
+            handler
+                .execute_read_only(&tool_call("call_1", "query_xml", args))
+                .expect("execute")
+                .content
+        };
+
+        let first = query(r#"{"pattern": "item"}"#);
+        assert!(first.starts_with("Found 5 element(s) matching 'item', showing 1-2:"));
+        assert!(first.contains("[3 more; use offset 2 for the next page]"));
+
+        // limit is capped at the configured page size
+        let next = query(r#"{"pattern": "item", "offset": 2, "limit": 50}"#);
+        assert!(next.contains("showing 3-4:"));
+        assert!(next.contains(": \"3\"") && next.contains(": \"4\""));
+
+        let last = query(r#"{"pattern": "item", "offset": 4}"#);
+        assert!(last.contains("showing 5-5:"));
+        assert!(!last.contains("more;"));
+
+        assert!(query(r#"{"pattern": "item", "offset": 9}"#).starts_with("Offset 9 is past"));
+        assert!(query(r#"{"pattern": "nope"}"#).starts_with("No elements"));
+    }
+
+    #[test]
+    fn test_query_xml_depth() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string(
+            "<root><a><item>1</item></a><item>2</item><b/></root>".to_string(),
+        );
+        let handler = ModifyToolHandler::new(&context, &xml);
+        let query = |args: &str| handler.execute_read_only(&tool_call("call_1", "query_xml", args));
+
+        let level1 = query(r#"{"depth": 1}"#).expect("execute").content;
+        assert!(level1.starts_with("Found 3 element(s) at depth 1:"));
+
+        let both = query(r#"{"pattern": "item", "depth": 2}"#).expect("execute").content;
+        assert!(both.starts_with("Found 1 element(s) matching 'item' at depth 2:"));
+        assert!(both.contains(": \"1\""));
+
+        let none = query(r#"{"pattern": "b", "depth": 2}"#).expect("execute").content;
+        assert_eq!(none, "No elements matching 'b' at depth 2");
+
+        assert!(query("{}").is_err());
+        assert!(query(r#"{"depth": -1}"#).is_err());
+    }
+
+    #[test]
+    fn test_get_xml_source() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let long = "x".repeat(crate::xml::modifier::MAX_XML_SOURCE);
+        let xml =
+            XmlModifier::from_string(format!("<root><a k=\"v\"><b/></a><c>{long}</c></root>"));
+        let handler = ModifyToolHandler::new(&context, &xml);
+        let source = |path: &str| {
+            let args = format!(r#"{{"path": "{path}"}}"#);
+            handler
+                .execute_read_only(&tool_call("call_1", "get_xml_source", &args))
+                .expect("execute")
+                .content
+        };
+
+        assert_eq!(source("a"), "<a k=\"v\"><b/></a>");
+        assert_eq!(source("nope"), "No element matching 'nope'");
+        let truncated = source("c");
+        assert!(truncated.starts_with("<c>xxx"));
+        assert!(truncated.ends_with(&format!(
+            "[Truncated: {} chars total]",
+            crate::xml::modifier::MAX_XML_SOURCE + 7
+        )));
+    }
+
+    #[test]
+    fn test_get_attribute() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string(r#"<root><a status="open" id=""/></root>"#.into());
+        let handler = ModifyToolHandler::new(&context, &xml);
+        let attribute = |path: &str, attr: &str| {
+            let args = format!(r#"{{"path": "{path}", "attr_name": "{attr}"}}"#);
+            handler
+                .execute_read_only(&tool_call("call_1", "get_attribute", &args))
+                .expect("execute")
+                .content
+        };
+
+        assert_eq!(attribute("a", "status"), "open");
+        assert_eq!(attribute("a", "id"), "");
+        assert_eq!(attribute("a", "kind"), "Attribute 'kind' not present on element matching 'a'");
+        assert_eq!(attribute("b", "status"), "No element matching 'b'");
+    }
+
+    #[test]
+    fn test_apply_csv_mapping() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        std::fs::write(
+            dir.path().join("status.csv"),
+            "sku,status\nA1,active\nB2,retired\nZ9,active\n",
+        )
+        .expect("write csv");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string(
+            "<items><item sku=\"A1\">new</item><item sku=\"B2\">new</item></items>".to_string(),
+        );
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        let result = handler
+            .execute(&tool_call(
+                "c1",
+                "apply_csv_mapping",
+                r#"{"filename": "status.csv", "key_column": "sku", "value_column": "status",
+                    "pattern": "item", "key_attr": "sku"}"#,
+            ))
+            .expect("execute");
+        assert!(result.content.contains("Updated text of 2 element(s) from 3 row(s)"));
+        assert!(result.content.contains("1 key(s) had no matching element: Z9"));
+        assert!(xml.get_content().contains(r#"<item sku="B2">retired</item>"#));
+        assert_eq!(handler.get_modifications().len(), 1);
+
+        handler
+            .execute(&tool_call(
+                "c2",
+                "apply_csv_mapping",
+                r#"{"filename": "status.csv", "key_column": "sku", "value_column": "status",
+                    "pattern": "item", "key_attr": "sku", "attr_name": "state"}"#,
+            ))
+            .expect("execute");
+        assert!(xml.get_content().contains(r#"<item sku="A1" state="active">"#));
+    }
+
+    #[test]
+    fn test_apply_csv_mapping_rejects_unmatchable_filters() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        std::fs::write(dir.path().join("status.csv"), "sku,status\nA1',bad\nB2,ok\n")
+            .expect("write csv");
+        let context = ContextManager::from_directory(dir.path()).expect("context");
+        let xml = XmlModifier::from_string(
+            "<items><item type=\"x\" sku=\"A1\">new</item>\
+             <item type=\"x\" sku=\"B2\">new</item></items>"
+                .to_string(),
+        );
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        // A pattern's own filter would replace the per-row key filter
+        let err = handler
+            .execute(&tool_call(
+                "c1",
+                "apply_csv_mapping",
+                r#"{"filename": "status.csv", "key_column": "sku", "value_column": "status",
+                    "pattern": "item[@type='x']", "key_attr": "sku"}"#,
+            ))
+            .expect_err("filtered pattern");
+        assert!(err.to_string().contains("can't have its own"));
+
+        // A1' must not update sku="A1"
+        let result = handler
+            .execute(&tool_call(
+                "c2",
+                "apply_csv_mapping",
+                r#"{"filename": "status.csv", "key_column": "sku", "value_column": "status",
+                    "pattern": "item", "key_attr": "sku"}"#,
+            ))
+            .expect("execute");
+        assert!(result.content.contains("Updated text of 1 element(s) from 2 row(s)"));
+        assert!(result.content.contains("1 key(s) contain quotes or ']' and were skipped: A1'"));
+        assert!(xml.get_content().contains(r#"sku="A1">new</item>"#));
+        assert!(xml.get_content().contains(r#"sku="B2">ok</item>"#));
+    }
+
+    #[test]
+    fn test_conditional_modify() {
+        let context = ContextManager::empty();
+        let xml = XmlModifier::from_string(
+            "<items><item><price>1500</price></item><item><price>20</price></item>\
+             <item><price>n/a</price></item></items>"
+                .to_string(),
+        );
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        let result = handler
+            .execute(&tool_call(
+                "c1",
+                "conditional_modify",
+                r#"{"pattern": "item/price", "condition": "text > 1000",
+                    "operation": "set_attribute", "attr_name": "band", "value": "high"}"#,
+            ))
+            .expect("execute");
+        assert!(result.content.contains("to 1 element(s)"));
+        assert!(xml.get_content().contains(r#"<price band="high">1500</price>"#));
+        assert_eq!(handler.script().steps.len(), 1);
+
+        let err = handler
+            .execute(&tool_call(
+                "c2",
+                "conditional_modify",
+                r#"{"pattern": "price", "condition": "text >", "operation": "delete"}"#,
+            ))
+            .expect_err("invalid condition");
+        assert!(err.to_string().contains("Invalid condition"));
+        assert_eq!(handler.get_modifications().len(), 1);
+    }
+
+    #[test]
+    fn test_append_records() {
+        let context = ContextManager::empty();
+        let xml = XmlModifier::from_string("<log></log>".to_string());
+        let mut handler = ModifyToolHandler::new(&context, &xml);
+
+        let result = handler
+            .execute(&tool_call(
+                "c1",
+                "append_records",
+                r#"{"parent": "log", "records": [
+                    {"name": "entry", "attributes": {"level": "info"}, "text": "started"},
+                    {"name": "entry", "attributes": {"level": "warn"}}]}"#,
+            ))
+            .expect("execute");
+        assert_eq!(result.content, "Appended 2 record(s) to log");
+        let written = xml.get_content();
+        assert!(written.contains(r#"<entry level="info">started</entry>"#));
+        assert!(written.contains(r#"<entry level="warn"/>"#));
+        assert_eq!(handler.script().steps.len(), 1);
+
+        let err = handler
+            .execute(&tool_call(
+                "c2",
+                "append_records",
+                r#"{"parent": "log", "records": [{"text": "x"}]}"#,
+            ))
+            .expect_err("record without name");
+        assert!(err.to_string().contains("Record 1 has no name"));
+    }
 }
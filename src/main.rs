@@ -1,6 +1,8 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 #![allow(clippy::uninlined_format_args)]
 
+mod color;
+mod config;
 mod context;
 mod csv_reader;
 mod llm;
@@ -9,10 +11,14 @@ mod structs;
 mod xml;
 
 use clap::{Parser, Subcommand};
-use structs::{CsvData, FeatureMatrix, Result, ZError};
+use structs::{
+    ClusterMethod, CorrelationMethod, CsvData, CsvPreamble, DistanceUnits, EmptyElementStyle,
+    FeatureMatrix, Result, ToolFormat, ZError,
+};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Z - LLM tool for XML modification with ML analysis
 #[derive(Parser, Debug)]
@@ -21,6 +27,10 @@ use std::sync::Arc;
 struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Disable colored output (also disabled when stderr isn't a terminal or `NO_COLOR` is set)
+    #[arg(long, global = true)]
+    no_color: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -35,25 +45,218 @@ enum Commands {
         #[arg(short, long, default_value = "./ml_output")]
         output_dir: PathBuf,
 
-        /// Number of clusters for K-means (0 = auto)
+        /// Number of clusters for K-means or components for GMM (0 = auto; unused by DBSCAN)
         #[arg(short = 'k', long, default_value = "0")]
         clusters: usize,
 
+        /// Clustering algorithm behind `clusters.csv` and the summary's clustering section
+        #[arg(long, value_enum, default_value = "kmeans")]
+        method: ClusterMethod,
+
         /// Treat input as TSV instead of CSV
-        #[arg(long)]
+        #[arg(long, conflicts_with = "delimiter")]
         tsv: bool,
 
-        /// DBSCAN epsilon (0.0 = auto-estimate via k-distance heuristic)
-        #[arg(long, default_value = "0.0")]
+        /// Field delimiter (auto-detected from the file when neither this nor --tsv is given)
+        #[arg(long)]
+        delimiter: Option<char>,
+
+        /// Fail on rows whose field count differs from the header
+        /// (default: pad short rows with empty fields and warn)
+        #[arg(long)]
+        strict_columns: bool,
+
+        /// Read only the first N data rows, stopping early on large files.
+        /// Deterministic, unlike sampling; the summary notes the truncation
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        max_rows: Option<u64>,
+
+        /// Skip records that fail to parse (e.g. invalid UTF-8) instead of aborting,
+        /// logging the first few with their line numbers and reporting the total.
+        /// An unescaped quote isn't a parse error: its field runs on over the rows
+        /// after it. A record whose field spans lines shaped like rows and leaves it
+        /// short of fields (or a field over 1 MiB) is skipped as such, but the rows
+        /// swallowed into it can't be recovered; a complete record is kept with a warning
+        #[arg(long)]
+        continue_on_parse_error: bool,
+
+        /// Skip this many lines before the header, e.g. a title line an export
+        /// writes above the real header
+        #[arg(long, value_name = "N", default_value = "0")]
+        skip_lines: usize,
+
+        /// Skip lines starting with this character: before the header (after
+        /// --skip-lines), and as rows anywhere in the data
+        #[arg(long, value_name = "CHAR")]
+        comment_char: Option<char>,
+
+        /// DBSCAN neighbourhood radius in normalized [0, 1] feature space.
+        /// 0 = auto: each row's distance to its min-points-th nearest neighbour is
+        /// sorted and eps is taken at the knee, where that curve bends upward most
+        /// sharply (falls back to the 90th percentile)
+        #[arg(long = "dbscan-eps", visible_alias = "eps", default_value = "0.0")]
         dbscan_eps: f64,
 
-        /// DBSCAN minimum points per cluster
-        #[arg(long, default_value = "5")]
-        dbscan_min_points: usize,
+        /// DBSCAN minimum neighbours for a core point; also the k used by auto eps
+        #[arg(
+            long = "dbscan-min-points",
+            visible_alias = "min-points",
+            default_value = "5",
+            value_parser = clap::value_parser!(u16).range(1..)
+        )]
+        dbscan_min_points: u16,
 
         /// Number of PCA components (0 = auto)
         #[arg(long, default_value = "0")]
         pca_components: usize,
+
+        /// Correlation coefficient (kendall is O(n^2) per feature pair)
+        #[arg(long, value_enum, default_value = "pearson")]
+        correlation_method: CorrelationMethod,
+
+        /// Also write the sample covariance matrix to `covariance.csv` and `stats.json`
+        #[arg(long)]
+        covariance: bool,
+
+        /// Also write pairwise mutual information to `mi_matrix.csv`, finding nonlinear
+        /// dependence correlation misses. Uses equal-width bins, Sturges' rule for the count
+        #[arg(long)]
+        mutual_info: bool,
+
+        /// Compute bootstrap 95% confidence intervals for column means
+        #[arg(long)]
+        bootstrap: bool,
+
+        /// Score cluster stability as the mean ARI across repeated K-means runs
+        /// (runs clustering several times)
+        #[arg(long)]
+        stability: bool,
+
+        /// Write `cluster_descriptions.txt`, describing each cluster by the features
+        /// whose mean is most above or below the overall mean (e.g. "high price, low rating")
+        #[arg(long)]
+        describe_clusters: bool,
+
+        /// Flag outliers against each row's own K-means cluster instead of the whole
+        /// dataset (e.g. a cheap item in the premium cluster)
+        #[arg(long)]
+        cluster_relative_anomalies: bool,
+
+        /// Drop anomalies of this type, e.g. `dbscan_noise` (repeatable)
+        #[arg(long = "exclude-anomaly-type", value_name = "TYPE")]
+        exclude_anomaly_types: Vec<String>,
+
+        /// Keep only anomalies of this type (repeatable)
+        #[arg(long = "only-anomaly-type", value_name = "TYPE")]
+        only_anomaly_types: Vec<String>,
+
+        /// Re-run the analysis whenever the input file changes, until interrupted.
+        /// Changes are detected by polling the file's size and modification time
+        /// every 500 ms (no filesystem notifications)
+        #[arg(long)]
+        watch: bool,
+
+        /// Print the numeric columns that would become features and how many rows the
+        /// completeness filter keeps and drops, then exit without analyzing
+        #[arg(long, conflicts_with = "watch")]
+        show_features: bool,
+
+        /// Delete output files left by a previous run (other files are kept), so a
+        /// run that no longer writes a file doesn't leave a stale copy behind
+        #[arg(long, conflicts_with = "fail_if_exists")]
+        clean_output: bool,
+
+        /// Error if the output directory already holds output files from a previous run
+        #[arg(long, conflicts_with = "watch")]
+        fail_if_exists: bool,
+
+        /// Decimal places for numbers in the CSV outputs and summary (default: 4 for
+        /// distances, scores and coordinates, 2 for summary statistics). `stats.json`
+        /// always keeps full precision
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(..=17))]
+        precision: Option<u8>,
+
+        /// Units for `distance_to_centroid` in `clusters.csv`: normalized [0, 1] feature
+        /// space, or the original feature units
+        #[arg(long, value_enum, default_value = "normalized")]
+        distance_units: DistanceUnits,
+
+        /// Write pairs of rows within this Euclidean distance of each other in normalized
+        /// feature space to `near_duplicates.csv`
+        #[arg(long, value_name = "DIST", value_parser = parse_positive_f64)]
+        near_dupe_threshold: Option<f64>,
+
+        /// Multiply normalized features by weights before clustering, DBSCAN and PCA,
+        /// e.g. `price:2,count:0.5` (unlisted features keep 1.0; 0 drops a feature; each
+        /// feature at most once). Near-duplicates are found before weighting
+        #[arg(
+            long,
+            value_name = "NAME:WEIGHT,...",
+            value_delimiter = ',',
+            value_parser = parse_feature_weight
+        )]
+        feature_weights: Vec<(String, f64)>,
+
+        /// Also use columns of `true`/`false`, `yes`/`no` or `y`/`n` (any case, at least
+        /// 90% of non-empty cells) as features, coded 1.0/0.0
+        #[arg(long)]
+        encode_booleans: bool,
+
+        /// Analyze only the first row of each group of near-duplicates
+        #[arg(long, requires = "near_dupe_threshold")]
+        collapse_near_dupes: bool,
+
+        /// Append each anomaly's original row columns to `anomalies.csv`
+        #[arg(long)]
+        anomalies_with_data: bool,
+
+        /// Describe only these numeric columns in the summary and `stats.json`
+        /// (comma-separated; the features used for clustering are unchanged)
+        #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+        stats_columns: Vec<String>,
+
+        /// Also pack every output file into this tar archive (gzip-compressed if the
+        /// name ends in .gz or .tgz)
+        #[arg(long, value_name = "PATH")]
+        archive: Option<PathBuf>,
+
+        /// Also write `clusters.geojson`, one Point per row coloured by cluster, when
+        /// latitude/longitude columns are found (named lat/latitude and
+        /// lon/lng/long/longitude, optionally after a `_` prefix, with values in range)
+        #[arg(long)]
+        geojson: bool,
+
+        /// Compare the clusters against this known label column: ARI and NMI in the
+        /// summary, and a cluster-by-class count table in `cluster_vs_truth.csv`
+        #[arg(long, value_name = "NAME")]
+        truth_column: Option<String>,
+
+        /// Include this column's value as a label in `pca_projection.csv`
+        /// (default: an inferred id column, reported in the summary)
+        #[arg(long, value_name = "NAME")]
+        label_column: Option<String>,
+
+        /// Seed for all randomized steps, making output reproducible
+        /// (default: seeded from entropy, so runs may differ)
+        #[arg(long)]
+        seed: Option<u64>,
+
+        /// Save the min-max normalization parameters to this JSON file
+        #[arg(long)]
+        save_norm: Option<PathBuf>,
+
+        /// Normalize with parameters saved by --save-norm (out-of-range values clamp to [0, 1])
+        #[arg(long)]
+        load_norm: Option<PathBuf>,
+
+        /// Also write the complete analysis result (stats, clusters, DBSCAN, anomalies,
+        /// correlation, PCA) as a single JSON file
+        #[arg(long, value_name = "PATH")]
+        dump_result: Option<PathBuf>,
+
+        /// Print the effective settings, after defaults, as JSON and exit without analyzing
+        #[arg(long)]
+        print_config: bool,
     },
 
     /// Use LLM to modify XML based on context files
@@ -62,25 +265,52 @@ enum Commands {
         #[arg(short, long)]
         context_dir: PathBuf,
 
-        /// XML file to modify
-        #[arg(short = 'x', long)]
-        xml: PathBuf,
+        /// XML file to modify, or `-` to read it from stdin and write the result to stdout
+        #[arg(short = 'x', long, required_unless_present = "dump_prompt")]
+        xml: Option<PathBuf>,
 
         /// Path to llama-server executable
-        #[arg(short, long)]
-        server: PathBuf,
+        #[arg(short, long, required_unless_present = "dump_prompt")]
+        server: Option<PathBuf>,
 
         /// Path to GGUF model file
-        #[arg(short, long)]
-        model: PathBuf,
+        #[arg(short, long, required_unless_present = "dump_prompt")]
+        model: Option<PathBuf>,
 
-        /// Context size for LLM (tokens)
-        #[arg(long, default_value = "12000")]
-        context_size: u32,
+        /// Context size for LLM (tokens) [default: the model's --model-config entry, else 12000]
+        #[arg(long)]
+        context_size: Option<u32>,
+
+        /// GPU layers to offload [default: the model's --model-config entry, else 99]
+        #[arg(long)]
+        gpu_layers: Option<u32>,
 
-        /// GPU layers to offload
-        #[arg(long, default_value = "99")]
-        gpu_layers: u32,
+        /// JSON file of per-model llama-server defaults keyed by model filename, e.g.
+        /// `{"models": {"qwen-7b.gguf": {"gpu_layers": 20, "context_size": 8192,
+        /// "extra_args": ["--flash-attn"]}}}`. CLI flags take precedence
+        #[arg(long, value_name = "PATH")]
+        model_config: Option<PathBuf>,
+
+        /// Extra argument passed verbatim to llama-server, after the managed ones and any
+        /// from --model-config (repeatable, e.g. `--server-arg=--flash-attn`).
+        /// The model, port and host can't be overridden
+        #[arg(long = "server-arg", value_name = "ARG", allow_hyphen_values = true)]
+        server_args: Vec<String>,
+
+        /// Seconds one LLM request may take before failing (separate from server startup).
+        /// A request retried after a server respawn gets the full timeout again
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value = "120",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        request_timeout: u64,
+
+        /// Leave llama-server running after z exits, to query it by hand when debugging.
+        /// Prints its URL, PID and the command to stop it; nothing else will
+        #[arg(long)]
+        keep_server: bool,
 
         /// Maximum conversation turns
         #[arg(long, default_value = "10")]
@@ -89,39 +319,313 @@ enum Commands {
         /// Dry run - don't modify XML, just show what would be done
         #[arg(long)]
         dry_run: bool,
+
+        /// How to write empty elements (default: keep each element's original form)
+        #[arg(long, value_enum)]
+        empty_element_style: Option<EmptyElementStyle>,
+
+        /// Re-indent the XML with two spaces before saving, even when the model made no
+        /// edits
+        #[arg(long)]
+        reformat: bool,
+
+        /// Write the run's successful XML edits as a JSON script that `z replay`
+        /// can apply to another copy of the XML without the model
+        #[arg(long, value_name = "PATH")]
+        record_script: Option<PathBuf>,
+
+        /// Print the model's `finish` summary and its final message to stderr at the end
+        #[arg(long)]
+        show_model_summary: bool,
+
+        /// Include a short summary of each CSV context file in the system prompt
+        #[arg(long)]
+        append_system_context: bool,
+
+        /// Reject `finish` until the model makes a change or confirms none are needed
+        #[arg(long)]
+        require_changes: bool,
+
+        /// Print the assembled system prompt to stdout and exit without starting the server
+        #[arg(long)]
+        dump_prompt: bool,
+
+        /// Print the effective settings, after applying --model-config and defaults, as
+        /// JSON and exit without starting the server
+        #[arg(long)]
+        print_config: bool,
+
+        /// How the server returns tool calls
+        #[arg(long, value_enum, default_value = "auto")]
+        tool_format: ToolFormat,
+
+        /// Inline task for the model; takes precedence over an instructions file
+        #[arg(long)]
+        instructions: Option<String>,
+
+        /// Only offer these tools to the model, comma-separated (`finish` is always enabled)
+        #[arg(long, value_delimiter = ',', value_name = "TOOLS")]
+        enable_tools: Vec<String>,
+
+        /// Withhold these tools from the model, comma-separated
+        #[arg(long, value_delimiter = ',', value_name = "TOOLS")]
+        disable_tools: Vec<String>,
+
+        /// Page size for `query_xml` results; the model pages through the rest with an offset
+        #[arg(long, default_value = "10", value_parser = clap::value_parser!(u16).range(1..))]
+        elements_per_query: u16,
+
+        /// Refuse XML input larger than this many bytes. The whole document is held in
+        /// memory and copied by every edit
+        #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_XML_BYTES)]
+        max_xml_bytes: u64,
+    },
+
+    /// Apply a script recorded with `modify --record-script` to an XML file, without the model
+    Replay {
+        /// Script written by `--record-script`
+        script: PathBuf,
+
+        /// XML file to modify, or `-` to read it from stdin and write the result to stdout
+        #[arg(short = 'x', long)]
+        xml: PathBuf,
+
+        /// Dry run - don't modify XML, just show what would be done
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Refuse XML input larger than this many bytes
+        #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_XML_BYTES)]
+        max_xml_bytes: u64,
+    },
+
+    /// Run the XML tools from a script or another program, without the model
+    Xml {
+        /// XML file to load (stdin carries the commands, so `-` is not accepted)
+        #[arg(short = 'x', long)]
+        xml: PathBuf,
+
+        /// Read JSON commands from stdin, one per line (e.g. `{"op": "query", "pattern":
+        /// "item"}`), and write one JSON result per line to stdout. `{"op": "save"}`
+        /// writes the final document and ends the batch; the file itself is not changed
+        #[arg(long)]
+        batch: bool,
+
+        /// Refuse XML input larger than this many bytes
+        #[arg(long, value_name = "BYTES", default_value_t = DEFAULT_MAX_XML_BYTES)]
+        max_xml_bytes: u64,
+    },
+
+    /// Compare two stats.json files from analyze runs and report drift
+    Diff {
+        /// stats.json from the earlier run
+        old: PathBuf,
+
+        /// stats.json from the later run
+        new: PathBuf,
+
+        /// Flag columns whose mean moved by more than this many (old) standard deviations
+        #[arg(long, default_value = "2.0")]
+        threshold: f64,
+
+        /// Also write the diff as JSON to this file
+        #[arg(long)]
+        json: Option<PathBuf>,
+    },
+
+    /// Explain one row's anomalies from an analyze output directory, without the model
+    ExplainAnomaly {
+        /// Output directory written by `z analyze`
+        #[arg(short, long, default_value = "./ml_output")]
+        output_dir: PathBuf,
+
+        /// Source row index, as in the `row_id` column of clusters.csv and anomalies.csv
+        #[arg(long)]
+        row_id: usize,
+    },
+
+    /// Report the environment and check prerequisites without starting the LLM server.
+    /// Exits non-zero if a configured path is missing
+    Doctor {
+        /// Path to llama-server executable to check
+        #[arg(short, long)]
+        server: Option<PathBuf>,
+
+        /// Path to GGUF model file to check
+        #[arg(short, long)]
+        model: Option<PathBuf>,
+
+        /// Context directory to check
+        #[arg(short, long)]
+        context_dir: Option<PathBuf>,
+
+        /// Output directory to report free disk space for
+        #[arg(short, long, default_value = "./ml_output")]
+        output_dir: PathBuf,
     },
 }
 
+/// Parse a `name:weight` pair with a finite, non-negative weight
+fn parse_feature_weight(value: &str) -> std::result::Result<(String, f64), String> {
+    let (name, weight) = value
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected NAME:WEIGHT, got '{value}'"))?;
+    match weight.parse::<f64>() {
+        Ok(w) if w.is_finite() && w >= 0.0 => Ok((name.to_string(), w)),
+        Ok(_) => Err("weight must be a number of at least 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Print a run's resolved settings for `--print-config`
+fn print_effective_config(config: &serde_json::Value) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(config)?);
+    Ok(())
+}
+
+/// Serialize a duration as whole seconds
+fn serialize_secs<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+/// Parse a finite number greater than zero
+fn parse_positive_f64(value: &str) -> std::result::Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        Ok(_) => Err("must be a number greater than 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 fn main() {
     if let Err(e) = run() {
-        eprintln!("Error: {e}");
+        eprintln!("{} {e}", color::red("Error:"));
         std::process::exit(1);
     }
 }
 
+#[allow(clippy::too_many_lines)]
 fn run() -> Result<()> {
     let args = Args::parse();
+    color::init(args.no_color);
 
     match args.command {
         Some(Commands::Analyze {
             csv,
             output_dir,
             clusters,
+            method,
             tsv,
+            delimiter,
+            strict_columns,
+            max_rows,
+            continue_on_parse_error,
+            skip_lines,
+            comment_char,
             dbscan_eps,
             dbscan_min_points,
             pca_components,
-        }) => run_analyze(
-            &csv,
-            &output_dir,
-            &ml::pipeline::AnalysisConfig {
+            correlation_method,
+            covariance,
+            mutual_info,
+            bootstrap,
+            stability,
+            describe_clusters,
+            cluster_relative_anomalies,
+            exclude_anomaly_types,
+            only_anomaly_types,
+            watch,
+            show_features,
+            clean_output,
+            fail_if_exists,
+            precision,
+            distance_units,
+            near_dupe_threshold,
+            feature_weights,
+            encode_booleans,
+            collapse_near_dupes,
+            anomalies_with_data,
+            stats_columns,
+            archive,
+            geojson,
+            truth_column,
+            label_column,
+            seed,
+            save_norm,
+            load_norm,
+            dump_result,
+            print_config,
+        }) => {
+            let config = ml::pipeline::AnalysisConfig {
+                cluster_method: method,
                 clusters,
                 dbscan_eps,
-                dbscan_min_points,
+                dbscan_min_points: usize::from(dbscan_min_points),
                 pca_components,
-            },
-            tsv,
-        ),
+                correlation_method,
+                covariance,
+                mutual_info,
+                bootstrap,
+                stability,
+                describe_clusters,
+                cluster_relative_anomalies,
+                exclude_anomaly_types,
+                only_anomaly_types,
+                seed,
+            };
+            let io = AnalyzeIo {
+                tsv,
+                delimiter,
+                strict_columns,
+                max_rows: max_rows.and_then(|n| usize::try_from(n).ok()),
+                continue_on_parse_error,
+                skip_lines,
+                comment_char,
+                label_column,
+                truth_column,
+                anomalies_with_data,
+                stats_columns,
+                archive,
+                geojson,
+                precision: precision.map(usize::from),
+                distance_units,
+                feature_weights,
+                encode_booleans,
+                near_dupe_threshold,
+                collapse_near_dupes,
+                save_norm,
+                load_norm,
+                dump_result,
+                existing_output: if clean_output {
+                    ExistingOutput::Clean
+                } else if fail_if_exists {
+                    ExistingOutput::Fail
+                } else {
+                    ExistingOutput::Overwrite
+                },
+            };
+            if print_config {
+                return print_effective_config(&serde_json::json!({
+                    "command": "analyze",
+                    "csv": csv,
+                    "output_dir": output_dir,
+                    "watch": watch,
+                    "show_features": show_features,
+                    "analysis": config,
+                    "io": io,
+                }));
+            }
+            if show_features {
+                run_show_features(&csv, &io)
+            } else if watch {
+                run_analyze_watch(&csv, &output_dir, &config, &io)
+            } else {
+                run_analyze(&csv, &output_dir, &config, &io)
+            }
+        }
 
         Some(Commands::Modify {
             context_dir,
@@ -130,18 +634,194 @@ fn run() -> Result<()> {
             model,
             context_size,
             gpu_layers,
+            model_config,
+            server_args,
+            request_timeout,
+            keep_server,
             max_turns,
             dry_run,
-        }) => run_modify(
-            &context_dir,
-            &xml,
-            &server,
-            &model,
-            context_size,
-            gpu_layers,
-            max_turns,
+            empty_element_style,
+            reformat,
+            record_script,
+            show_model_summary,
+            append_system_context,
+            require_changes,
+            dump_prompt,
+            print_config,
+            tool_format,
+            instructions,
+            enable_tools,
+            disable_tools,
+            elements_per_query,
+            max_xml_bytes,
+        }) => {
+            let tools = llm::tools::select_tool_definitions(&enable_tools, &disable_tools)?;
+            if dump_prompt {
+                return run_dump_prompt(
+                    &context_dir,
+                    append_system_context,
+                    instructions.as_deref(),
+                    &tools,
+                );
+            }
+            let (Some(xml), Some(server), Some(model)) = (xml, server, model) else {
+                return Err(ZError::Config("--xml, --server and --model are required".into()));
+            };
+            let profile = match &model_config {
+                Some(path) => {
+                    let config = structs::ModelConfig::from_file(path)?;
+                    let profile = config.profile_for(&model).cloned();
+                    if profile.is_none() {
+                        eprintln!(
+                            "{} no entry for {} in {}; using CLI defaults",
+                            color::warning(),
+                            model.display(),
+                            path.display()
+                        );
+                    }
+                    profile.unwrap_or_default()
+                }
+                None => structs::ModelProfile::default(),
+            };
+            let server_args = ServerArgs {
+                context_size: structs::ModelProfile::resolve(
+                    context_size,
+                    profile.context_size,
+                    DEFAULT_CONTEXT_SIZE,
+                ),
+                gpu_layers: structs::ModelProfile::resolve(
+                    gpu_layers,
+                    profile.gpu_layers,
+                    DEFAULT_GPU_LAYERS,
+                ),
+                extra_args: profile.extra_args.into_iter().chain(server_args).collect(),
+                request_timeout: Duration::from_secs(request_timeout),
+                keep_server,
+            };
+            llm::server::validate_extra_args(&server_args.extra_args)?;
+            let output = ModifyOutput {
+                dry_run,
+                empty_element_style,
+                reformat,
+                record_script,
+                show_model_summary,
+            };
+            if print_config {
+                let tool_names: Vec<&str> =
+                    tools.iter().map(|t| t.function.name.as_str()).collect();
+                return print_effective_config(&serde_json::json!({
+                    "command": "modify",
+                    "context_dir": context_dir,
+                    "xml": xml,
+                    "server": server,
+                    "model": model,
+                    "model_config": model_config,
+                    "server_args": server_args,
+                    "max_turns": max_turns,
+                    "output": output,
+                    "append_system_context": append_system_context,
+                    "require_changes": require_changes,
+                    "tool_format": tool_format,
+                    "instructions": instructions,
+                    "tools": tool_names,
+                    "elements_per_query": elements_per_query,
+                    "max_xml_bytes": max_xml_bytes,
+                }));
+            }
+            run_modify(
+                &context_dir,
+                &xml,
+                &server,
+                &model,
+                &server_args,
+                max_turns,
+                &output,
+                append_system_context,
+                require_changes,
+                tool_format,
+                instructions.as_deref(),
+                tools,
+                usize::from(elements_per_query),
+                max_xml_bytes,
+            )
+        }
+
+        Some(Commands::Replay {
+            script,
+            xml,
             dry_run,
-        ),
+            max_xml_bytes,
+        }) => {
+            let script = structs::ReplayScript::from_file(&script)?;
+            let xml_modifier = load_modify_xml(&xml, max_xml_bytes)?;
+            let modifications = llm::replay::replay_script(&xml_modifier, &script)?;
+            save_modified_xml(
+                &xml_modifier,
+                &xml,
+                &modifications,
+                &ModifyOutput {
+                    dry_run,
+                    empty_element_style: None,
+                    reformat: false,
+                    record_script: None,
+                    show_model_summary: false,
+                },
+            )
+        }
+
+        Some(Commands::Xml {
+            xml,
+            batch,
+            max_xml_bytes,
+        }) => {
+            if !batch {
+                return Err(ZError::Config(
+                    "z xml only runs in --batch mode; pass --batch".into(),
+                ));
+            }
+            if is_stdio(&xml) {
+                return Err(ZError::Config(
+                    "z xml --batch reads commands from stdin; pass the XML as a file".into(),
+                ));
+            }
+            let xml_modifier = load_modify_xml(&xml, max_xml_bytes)?;
+            llm::batch::run_batch(&xml_modifier, std::io::stdin().lock(), std::io::stdout().lock())
+        }
+
+        Some(Commands::Diff {
+            old,
+            new,
+            threshold,
+            json,
+        }) => {
+            let diff = ml::drift::diff_stats(&old, &new, threshold)?;
+            print!("{}", diff.summary());
+            if let Some(path) = json {
+                std::fs::write(&path, serde_json::to_string_pretty(&diff)?)?;
+                eprintln!("Diff written to {}", path.display());
+            }
+            Ok(())
+        }
+
+        Some(Commands::ExplainAnomaly { output_dir, row_id }) => {
+            let explanation = ml::explain::explain_anomaly(&output_dir, row_id)?;
+            print!("{}", explanation.summary());
+            Ok(())
+        }
+
+        Some(Commands::Doctor {
+            server,
+            model,
+            context_dir,
+            output_dir,
+        }) => {
+            run_doctor(
+                server.as_deref(),
+                model.as_deref(),
+                context_dir.as_deref(),
+                &output_dir,
+            )
+        }
 
         None => {
             eprintln!("No subcommand provided. Use 'z analyze' or 'z modify'.");
@@ -151,12 +831,92 @@ fn run() -> Result<()> {
     }
 }
 
+/// Input parsing, normalization file and output directory options for the analyze phase
+#[derive(serde::Serialize)]
+#[allow(clippy::struct_excessive_bools)]
+struct AnalyzeIo {
+    tsv: bool,
+    delimiter: Option<char>,
+    strict_columns: bool,
+    max_rows: Option<usize>,
+    continue_on_parse_error: bool,
+    skip_lines: usize,
+    comment_char: Option<char>,
+    label_column: Option<String>,
+    truth_column: Option<String>,
+    anomalies_with_data: bool,
+    /// `--stats-columns`; empty describes every feature
+    stats_columns: Vec<String>,
+    archive: Option<PathBuf>,
+    geojson: bool,
+    /// Decimal places overriding each output's default
+    precision: Option<usize>,
+    distance_units: DistanceUnits,
+    /// `--feature-weights` pairs in the order given
+    feature_weights: Vec<(String, f64)>,
+    encode_booleans: bool,
+    near_dupe_threshold: Option<f64>,
+    collapse_near_dupes: bool,
+    save_norm: Option<PathBuf>,
+    load_norm: Option<PathBuf>,
+    dump_result: Option<PathBuf>,
+    existing_output: ExistingOutput,
+}
+
+/// What to do with output files a previous analyze run left in the output directory
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ExistingOutput {
+    /// Overwrite files this run writes; leave the rest in place
+    Overwrite,
+    /// Delete all previous output files once the new ones are ready
+    Clean,
+    /// Refuse to run
+    Fail,
+}
+
+/// Context size used when neither the CLI nor the model config sets one
+const DEFAULT_CONTEXT_SIZE: u32 = 12000;
+
+/// GPU layers offloaded when neither the CLI nor the model config sets them
+const DEFAULT_GPU_LAYERS: u32 = 99;
+
+/// Default `--max-xml-bytes`
+const DEFAULT_MAX_XML_BYTES: u64 = 256_000_000;
+
+/// Loaded XML size above which a memory warning is printed
+const LARGE_XML_BYTES: usize = 32_000_000;
+
+/// llama-server settings for the modify phase, after applying the model config
+#[derive(serde::Serialize)]
+struct ServerArgs {
+    context_size: u32,
+    gpu_layers: u32,
+    extra_args: Vec<String>,
+    /// Limit on each chat completion request
+    #[serde(rename = "request_timeout_secs", serialize_with = "serialize_secs")]
+    request_timeout: Duration,
+    /// Leave the server running when z exits
+    keep_server: bool,
+}
+
+/// Output options for the modify phase
+#[derive(serde::Serialize)]
+struct ModifyOutput {
+    dry_run: bool,
+    empty_element_style: Option<EmptyElementStyle>,
+    reformat: bool,
+    /// Where to write the run's XML edits for `z replay`
+    record_script: Option<PathBuf>,
+    show_model_summary: bool,
+}
+
 /// Run the ML analysis phase
 fn run_analyze(
     csv_path: &Path,
     output_dir: &Path,
     config: &ml::pipeline::AnalysisConfig,
-    tsv: bool,
+    io: &AnalyzeIo,
 ) -> Result<()> {
     // Validate input
     if !csv_path.exists() {
@@ -168,99 +928,737 @@ fn run_analyze(
 
     // Create output directory
     std::fs::create_dir_all(output_dir)?;
+    if io.existing_output == ExistingOutput::Fail {
+        let existing = ml::output::existing_outputs(output_dir);
+        if !existing.is_empty() {
+            return Err(ZError::Config(format!(
+                "Output directory {} already contains output files: {}",
+                output_dir.display(),
+                existing.join(", ")
+            )));
+        }
+    }
 
     eprintln!("Analyzing: {}", csv_path.display());
 
-    // Parse CSV
-    let csv_data = CsvData::from_file(csv_path, tsv)?;
-    eprintln!(
-        "Loaded {} rows x {} columns",
-        csv_data.row_count(),
-        csv_data.col_count()
-    );
+    let csv_data = load_analyze_csv(csv_path, io)?;
 
     // Extract and normalize features
     eprintln!("Extracting features...");
-    let features = FeatureMatrix::from_csv(&csv_data)?;
-    let normalized = features.normalize();
+    let features = FeatureMatrix::from_csv_with_options(&csv_data, io.encode_booleans)?;
+    let normalized = match &io.load_norm {
+        Some(path) => {
+            eprintln!("Using normalization parameters from {}", path.display());
+            features.normalize_with_params(path)?
+        }
+        None => features.normalize(),
+    };
+    if let Some(path) = &io.save_norm {
+        normalized.save_params(path)?;
+        eprintln!("Saved normalization parameters to {}", path.display());
+    }
+    // Near-duplicates are measured before weighting, in plain normalized space
+    let (features, mut normalized, near_duplicates) =
+        find_near_duplicates(features, normalized, io);
+    if !io.feature_weights.is_empty() {
+        normalized.apply_weights(&io.feature_weights)?;
+    }
+
+    let stats_columns = io
+        .stats_columns
+        .iter()
+        .map(|name| Ok((name.clone(), csv_data.numeric_column_values(name)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let inferred_id = inferred_label_column(&csv_data, io);
+    let labels = io
+        .label_column
+        .as_deref()
+        .or(inferred_id.as_deref())
+        .map(|name| {
+            csv_data
+                .labels_for_rows(name, &normalized.row_indices)
+                .map(|values| (name, values))
+        })
+        .transpose()?;
+    let truth = io
+        .truth_column
+        .as_deref()
+        .map(|name| csv_data.labels_for_rows(name, &normalized.row_indices))
+        .transpose()?;
 
     // Run pipeline
     eprintln!("Running analysis pipeline...");
-    let result = ml::pipeline::run_pipeline(&features, &normalized, config)?;
+    let mut result = ml::pipeline::run_pipeline(&features, &normalized, config)?;
+    result.near_duplicates = near_duplicates;
+    // The inferred id only labels pca_projection.csv, so report it only when that's written
+    if let (Some(name), Some(_)) = (&inferred_id, &result.pca) {
+        eprintln!(
+            "Using inferred id column '{name}' to label pca_projection.csv \
+             (override with --label-column)"
+        );
+        result.id_column = Some(name.clone());
+    }
+    if let (Some(name), Some(values)) = (&io.truth_column, &truth) {
+        result.truth = Some(ml::clustering::compare_to_truth(
+            name,
+            &result.cluster_result,
+            values,
+        ));
+    }
+    if !stats_columns.is_empty() {
+        result.column_stats = ml::pipeline::describe_columns(&stats_columns, config);
+    }
+    record_input_options(&mut result, &csv_data, io);
+    report_anomaly_count(&result, normalized.n_samples());
 
     // Write output files
     eprintln!("Writing output files...");
 
-    let summary = ml::output::build_summary(csv_path, &csv_data, &result);
-    ml::output::write_summary(output_dir, &summary)?;
-    ml::output::write_clusters(output_dir, &result.cluster_result, &normalized)?;
-    ml::output::write_anomalies(output_dir, &result.anomalies)?;
-
-    let stats_refs: Vec<_> = result.column_stats.iter().collect();
-    ml::output::write_stats_json(
-        output_dir,
-        &csv_data,
-        &stats_refs,
-        &result.cluster_result,
-        &result.anomalies,
-        result.dbscan_result.as_ref(),
-        result.correlation.as_ref(),
-        result.pca.as_ref(),
+    let labels = labels.as_ref().map(|(name, values)| (*name, values.as_slice()));
+    write_analyze_outputs(output_dir, csv_path, &csv_data, &normalized, &result, labels, io)?;
+
+    print_written_files(output_dir, &result);
+    if let Some(path) = &io.archive {
+        eprintln!("Archived output files to {}", path.display());
+    }
+    if let Some(path) = &io.dump_result {
+        ml::output::write_result_json(path, &result)?;
+        eprintln!("Wrote full analysis result to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Id column to label `pca_projection.csv` with when `--label-column` isn't given,
+/// if one can be inferred
+fn inferred_label_column(csv_data: &CsvData, io: &AnalyzeIo) -> Option<String> {
+    if io.label_column.is_some() {
+        return None;
+    }
+    Some(csv_data.headers[csv_reader::infer_id_column(csv_data)?].clone())
+}
+
+/// Report which columns would become features and how many rows the completeness
+/// filter of `FeatureMatrix::from_csv` keeps, without running the analysis
+fn run_show_features(csv_path: &Path, io: &AnalyzeIo) -> Result<()> {
+    if !csv_path.exists() {
+        return Err(ZError::Config(format!(
+            "CSV file not found: {}",
+            csv_path.display()
+        )));
+    }
+    let csv_data = load_analyze_csv(csv_path, io)?;
+
+    let columns = csv_data.feature_columns(io.encode_booleans);
+    let names: Vec<&str> = columns.iter().map(|&(i, _)| csv_data.headers[i].as_str()).collect();
+    let complete = (0..csv_data.row_count())
+        .filter(|&r| columns.iter().all(|(_, col)| col[r].is_some()))
+        .count();
+    let dropped = csv_data.row_count() - complete;
+
+    println!("Feature columns ({}): {}", names.len(), names.join(", "));
+    println!(
+        "Rows: {} total, {complete} complete, {dropped} dropped for an empty or non-numeric value",
+        csv_data.row_count()
+    );
+    if dropped > 0 {
+        println!("Empty or non-numeric cells per feature column:");
+        for ((_, col), name) in columns.iter().zip(&names) {
+            let missing = col.iter().filter(|v| v.is_none()).count();
+            if missing > 0 {
+                println!("- {name}: {missing}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse the analyze input with the delimiter and row options, reporting its shape
+/// and any ragged or truncated input
+fn load_analyze_csv(csv_path: &Path, io: &AnalyzeIo) -> Result<CsvData> {
+    let comment = match io.comment_char {
+        Some(c) if c.is_ascii() => Some(c as u8),
+        Some(c) => {
+            return Err(ZError::Config(format!(
+                "Comment character must be a single ASCII character: {c}"
+            )))
+        }
+        None => None,
+    };
+    let preamble = CsvPreamble {
+        skip_lines: io.skip_lines,
+        comment,
+    };
+    let delimiter = match (io.tsv, io.delimiter) {
+        (true, _) => b'\t',
+        (false, Some(d)) if d.is_ascii() => d as u8,
+        (false, Some(d)) => {
+            return Err(ZError::Config(format!(
+                "Delimiter must be a single ASCII character: {d}"
+            )))
+        }
+        (false, None) => csv_reader::detect_delimiter_after(csv_path, preamble)?,
+    };
+    let csv_data = CsvData::from_file_with_options(
+        csv_path,
+        delimiter,
+        io.strict_columns,
+        io.max_rows,
+        preamble,
+        io.continue_on_parse_error,
     )?;
+    if csv_data.skipped_lines > 0 {
+        eprintln!("Skipped {} line(s) before the header", csv_data.skipped_lines);
+    }
+    eprintln!(
+        "Loaded {} rows x {} columns (delimiter: {})",
+        color::green(csv_data.row_count()),
+        color::green(csv_data.col_count()),
+        csv_data.delimiter_name()
+    );
+    if csv_data.ragged_rows > 0 {
+        eprintln!(
+            "{} {} rows had a field count different from the header; short rows were padded",
+            color::warning(),
+            csv_data.ragged_rows
+        );
+    }
+    if csv_data.skipped_records > 0 {
+        for error in &csv_data.parse_errors {
+            eprintln!("{} skipped unparseable record at {error}", color::warning());
+        }
+        let unlogged = csv_data.skipped_records - csv_data.parse_errors.len();
+        if unlogged > 0 {
+            eprintln!("  ... and {unlogged} more");
+        }
+        eprintln!(
+            "{} {} unparseable record(s) skipped (--continue-on-parse-error)",
+            color::warning(),
+            csv_data.skipped_records
+        );
+    }
+    for warning in &csv_data.quote_warnings {
+        eprintln!("{} kept record at {warning}", color::warning());
+    }
+    if csv_data.truncated {
+        eprintln!(
+            "{} input truncated to the first {} rows (--max-rows)",
+            color::warning(),
+            csv_data.row_count()
+        );
+    }
+    Ok(csv_data)
+}
+
+/// Share of analyzed rows flagged as anomalous above which the count is shown in red
+const ANOMALY_ALERT_FRACTION: f64 = 0.1;
+
+/// Fill in the parts of `result` describing how the input was read: feature
+/// weights, boolean features, non-numeric cells of numeric columns and the
+/// `--geojson` coordinate columns
+fn record_input_options(result: &mut structs::AnalysisResult, csv_data: &CsvData, io: &AnalyzeIo) {
+    result.feature_weights = io
+        .feature_weights
+        .iter()
+        .map(|(name, weight)| structs::FeatureWeight {
+            name: name.clone(),
+            weight: *weight,
+        })
+        .collect();
+    if io.encode_booleans {
+        result.boolean_features = csv_data
+            .boolean_column_indices()
+            .into_iter()
+            .map(|i| csv_data.headers[i].clone())
+            .collect();
+    }
+    result.coercions = csv_data.non_numeric_cells();
+    report_coercions(&result.coercions);
+    if io.geojson {
+        result.lat_lon_columns = csv_reader::infer_lat_lon_columns(csv_data).map(|(lat, lon)| {
+            (csv_data.headers[lat].clone(), csv_data.headers[lon].clone())
+        });
+        match &result.lat_lon_columns {
+            Some((lat, lon)) => eprintln!("Mapping rows by latitude '{lat}', longitude '{lon}'"),
+            None => eprintln!(
+                "{} --geojson: no latitude/longitude columns found; clusters.geojson not written",
+                color::warning()
+            ),
+        }
+    }
+}
 
-    if let Some(corr) = &result.correlation {
-        ml::output::write_correlation(output_dir, corr)?;
+/// Warn that rows with non-numeric values in numeric columns were left out
+fn report_coercions(coercions: &[structs::ColumnCoercion]) {
+    if coercions.is_empty() {
+        return;
     }
+    let count: usize = coercions.iter().map(|c| c.count).sum();
+    eprintln!(
+        "{} {count} non-numeric value(s) in numeric columns; their rows are left out \
+         (see coercion_report.txt)",
+        color::warning()
+    );
+}
+
+/// Print how many rows were flagged, highlighting an unusually high share
+#[allow(clippy::cast_precision_loss)]
+fn report_anomaly_count(result: &structs::AnalysisResult, n_rows: usize) {
+    let flagged = result.anomalies.len();
+    let count = if flagged as f64 > ANOMALY_ALERT_FRACTION * n_rows as f64 {
+        color::red(flagged)
+    } else {
+        color::green(flagged)
+    };
+    eprintln!("Anomalies detected: {count} of {n_rows} rows");
+}
+
+/// Detect near-duplicate rows if `--near-dupe-threshold` is set, dropping the
+/// later row of each pair from the features with `--collapse-near-dupes`
+fn find_near_duplicates(
+    features: FeatureMatrix,
+    normalized: structs::NormalizedFeatures,
+    io: &AnalyzeIo,
+) -> (FeatureMatrix, structs::NormalizedFeatures, Option<structs::NearDuplicates>) {
+    let Some(threshold) = io.near_dupe_threshold else {
+        return (features, normalized, None);
+    };
 
-    eprintln!("Output written to {}", output_dir.display());
+    let pairs = ml::duplicates::near_duplicate_pairs(&normalized, threshold);
+    eprintln!("Found {} near-duplicate pairs within distance {threshold}", pairs.len());
+
+    let (features, normalized, collapsed_rows) = if io.collapse_near_dupes {
+        let (features, normalized, collapsed) =
+            ml::duplicates::collapse_near_duplicates(&features, &normalized, &pairs);
+        eprintln!("Collapsed {collapsed} near-duplicate rows");
+        (features, normalized, collapsed)
+    } else {
+        (features, normalized, 0)
+    };
+
+    let near_duplicates = structs::NearDuplicates {
+        threshold,
+        pairs,
+        collapsed_rows,
+    };
+    (features, normalized, Some(near_duplicates))
+}
+
+/// Write every analyze output file for `result` into `output_dir`
+fn write_analyze_outputs(
+    output_dir: &Path,
+    csv_path: &Path,
+    csv_data: &CsvData,
+    normalized: &structs::NormalizedFeatures,
+    result: &structs::AnalysisResult,
+    labels: Option<(&str, &[String])>,
+    io: &AnalyzeIo,
+) -> Result<()> {
+    // Stage every file first so a failed write leaves the previous outputs intact
+    ml::output::write_staged(output_dir, |dir| {
+        let precision = io.precision;
+        let summary = ml::output::build_summary(csv_path, csv_data, result, precision);
+        ml::output::write_summary(dir, &summary)?;
+        ml::output::write_clusters(
+            dir,
+            &result.cluster_result,
+            normalized,
+            io.distance_units,
+            precision,
+        )?;
+        ml::output::write_anomalies(
+            dir,
+            &result.anomalies,
+            io.anomalies_with_data.then_some(csv_data),
+            precision,
+        )?;
+        ml::output::write_anomalies_structured(dir, &result.anomalies, precision)?;
+        ml::output::write_anomaly_consensus(dir, &result.consensus, precision)?;
+        if let Some(near_duplicates) = &result.near_duplicates {
+            ml::output::write_near_duplicates(dir, &near_duplicates.pairs, precision)?;
+        }
+        if let Some(descriptors) = &result.cluster_descriptors {
+            ml::output::write_cluster_descriptions(dir, descriptors)?;
+        }
+        if let Some(truth) = &result.truth {
+            ml::output::write_cluster_vs_truth(dir, truth, &result.cluster_result)?;
+        }
+        if !result.coercions.is_empty() {
+            ml::output::write_coercion_report(dir, &result.coercions)?;
+        }
+        if let Some((lat, lon)) = &result.lat_lon_columns {
+            ml::output::write_clusters_geojson(
+                dir,
+                csv_data,
+                (lat, lon),
+                &result.cluster_result,
+                &normalized.row_indices,
+            )?;
+        }
+
+        let stats_refs: Vec<_> = result.column_stats.iter().collect();
+        ml::output::write_stats_json(
+            dir,
+            csv_data,
+            &stats_refs,
+            &result.cluster_result,
+            &result.anomalies,
+            result.dbscan_result.as_ref(),
+            result.correlation.as_ref(),
+            result.covariance.as_ref(),
+            result.pca.as_ref(),
+        )?;
+
+        if let Some(corr) = &result.correlation {
+            ml::output::write_correlation(dir, corr, precision)?;
+        }
+        if let Some(cov) = &result.covariance {
+            ml::output::write_covariance(dir, cov, precision)?;
+        }
+        if let Some(mi) = &result.mutual_info {
+            ml::output::write_mutual_info(dir, mi, precision)?;
+        }
+        if let Some(dbscan) = &result.dbscan_result {
+            ml::output::write_dbscan(dir, dbscan, normalized)?;
+        }
+        if let Some(pca) = &result.pca {
+            ml::output::write_pca_projection(dir, pca, normalized, labels, precision)?;
+        }
+        if let Some(path) = &io.archive {
+            ml::archive::write_dir_archive(dir, path)?;
+        }
+        // Only now that every new file is staged, so a failure keeps the old run
+        if io.existing_output == ExistingOutput::Clean {
+            ml::output::remove_outputs(output_dir)?;
+        }
+        Ok(())
+    })
+}
+
+/// List the files `run_analyze` wrote
+fn print_written_files(output_dir: &Path, result: &structs::AnalysisResult) {
+    eprintln!("Output written to {}", color::green(output_dir.display()));
     eprintln!("  - summary.txt");
     eprintln!("  - clusters.csv");
     eprintln!("  - anomalies.csv");
+    eprintln!("  - anomalies_structured.csv");
+    eprintln!("  - anomaly_consensus.csv");
+    if result.cluster_descriptors.is_some() {
+        eprintln!("  - cluster_descriptions.txt");
+    }
+    if result.truth.is_some() {
+        eprintln!("  - cluster_vs_truth.csv");
+    }
+    if !result.coercions.is_empty() {
+        eprintln!("  - coercion_report.txt");
+    }
+    if result.lat_lon_columns.is_some() {
+        eprintln!("  - clusters.geojson");
+    }
+    if result.near_duplicates.is_some() {
+        eprintln!("  - near_duplicates.csv");
+    }
     eprintln!("  - stats.json");
     if result.correlation.is_some() {
         eprintln!("  - correlation.csv");
     }
+    if result.covariance.is_some() {
+        eprintln!("  - covariance.csv");
+    }
+    if result.mutual_info.is_some() {
+        eprintln!("  - mi_matrix.csv");
+    }
+    if result.dbscan_result.is_some() {
+        eprintln!("  - dbscan.csv");
+    }
+    if result.pca.is_some() {
+        eprintln!("  - pca_projection.csv");
+    }
+}
+
+/// Poll interval for `--watch`
+const WATCH_POLL: Duration = Duration::from_millis(500);
+
+/// How long the input must stay unchanged before a `--watch` re-run starts
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Run the analysis, then re-run it on every change to the input until Ctrl+C
+fn run_analyze_watch(
+    csv_path: &Path,
+    output_dir: &Path,
+    config: &ml::pipeline::AnalysisConfig,
+    io: &AnalyzeIo,
+) -> Result<()> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_clone = shutdown.clone();
+    ctrlc::set_handler(move || {
+        eprintln!("\nReceived Ctrl+C, stopping watch...");
+        shutdown_clone.store(true, Ordering::SeqCst);
+    })
+    .map_err(|e| ZError::Config(format!("Failed to set Ctrl+C handler: {e}")))?;
+
+    let mut last_seen = file_fingerprint(csv_path);
+    loop {
+        eprintln!("[{}] Running analysis", utc_timestamp());
+        if let Err(e) = run_analyze(csv_path, output_dir, config, io) {
+            eprintln!("{} {e}", color::red("Error:"));
+        }
+        eprintln!("Watching {} for changes (Ctrl+C to stop)...", csv_path.display());
+
+        // Editors often save by writing a new file and renaming it over the old
+        // one, so the path is re-resolved on every poll rather than held open.
+        // A missing file (mid-rename) is never treated as a change.
+        loop {
+            if shutdown.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            std::thread::sleep(WATCH_POLL);
+
+            let current = file_fingerprint(csv_path);
+            if current.is_some() && current != last_seen {
+                // Debounce: wait until the file stops changing
+                let mut settled = current;
+                loop {
+                    std::thread::sleep(WATCH_DEBOUNCE);
+                    let next = file_fingerprint(csv_path);
+                    if next == settled {
+                        break;
+                    }
+                    settled = next;
+                }
+                last_seen = settled;
+                break;
+            }
+        }
+    }
+}
+
+/// Modification time and size of a file, or `None` if it can't be read
+fn file_fingerprint(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Current wall-clock time as `HH:MM:SS UTC`
+fn utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    format!(
+        "{:02}:{:02}:{:02} UTC",
+        (secs / 3600) % 24,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// Print an environment report: version, configured paths, GPU and disk space.
+/// Never spawns the LLM server.
+///
+/// # Errors
+/// Returns error if a configured path is missing, so scripts can act on the exit status
+fn run_doctor(
+    server: Option<&Path>,
+    model: Option<&Path>,
+    context_dir: Option<&Path>,
+    output_dir: &Path,
+) -> Result<()> {
+    let mut problems = 0;
+
+    println!("z {}", env!("CARGO_PKG_VERSION"));
+    println!(
+        "Platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    println!("\nPaths:");
+    for (label, path, want_dir) in [
+        ("Server", server, false),
+        ("Model", model, false),
+        ("Context dir", context_dir, true),
+    ] {
+        let Some(path) = path else {
+            println!("  {label}: (not set)");
+            continue;
+        };
+        let found = if want_dir { path.is_dir() } else { path.is_file() };
+        if !found {
+            problems += 1;
+        }
+        println!(
+            "  {label}: {} [{}]",
+            path.display(),
+            if found { "ok" } else { "NOT FOUND" }
+        );
+    }
+    println!("  Output dir: {}", output_dir.display());
 
+    println!("\nGPU:");
+    match detect_gpus() {
+        Some(gpus) => {
+            for gpu in gpus {
+                println!("  {gpu}");
+            }
+        }
+        None => println!("  none detected (nvidia-smi unavailable)"),
+    }
+
+    println!("\nDisk:");
+    match free_disk_bytes(output_dir) {
+        #[allow(clippy::cast_precision_loss)]
+        Some(bytes) => println!("  {:.1} GiB free", bytes as f64 / 1024f64.powi(3)),
+        None => println!("  unknown"),
+    }
+
+    if problems > 0 {
+        return Err(ZError::Config(format!("{problems} problem(s) found")));
+    }
+    println!("\nNo problems found");
     Ok(())
 }
 
-/// Run the LLM modification phase
-#[allow(clippy::too_many_arguments)]
-fn run_modify(
+/// GPU names reported by `nvidia-smi`, or `None` if it can't be run
+fn detect_gpus() -> Option<Vec<String>> {
+    let output = std::process::Command::new("nvidia-smi")
+        .args(["--query-gpu=name,memory.total", "--format=csv,noheader"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let gpus: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect();
+    (!gpus.is_empty()).then_some(gpus)
+}
+
+/// Free space on the filesystem holding `path` (or its nearest existing ancestor),
+/// via `df`. Best-effort: `None` if `df` is unavailable or its output is unexpected.
+fn free_disk_bytes(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let existing = if existing.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        existing
+    };
+
+    let output = std::process::Command::new("df")
+        .arg("-Pk")
+        .arg(existing)
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let available_kb: u64 = stdout.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available_kb * 1024)
+}
+
+/// Print the modify-phase system prompt without starting the LLM server
+fn run_dump_prompt(
     context_dir: &Path,
-    xml_path: &Path,
-    server_path: &Path,
-    model_path: &Path,
-    context_size: u32,
-    gpu_layers: u32,
-    max_turns: usize,
-    dry_run: bool,
+    append_system_context: bool,
+    instructions: Option<&str>,
+    tools: &[structs::ToolDefinition],
 ) -> Result<()> {
-    // Validate paths
     if !context_dir.exists() {
         return Err(ZError::Config(format!(
             "Context directory not found: {}",
             context_dir.display()
         )));
     }
-    if !xml_path.exists() {
-        return Err(ZError::Config(format!(
-            "XML file not found: {}",
-            xml_path.display()
-        )));
-    }
-    if !server_path.exists() {
-        return Err(ZError::Config(format!(
-            "Server executable not found: {}",
-            server_path.display()
-        )));
+
+    let context_manager = context::ContextManager::from_directory(context_dir)?;
+    println!(
+        "{}",
+        llm::build_modify_system_prompt(
+            &context_manager,
+            append_system_context,
+            instructions,
+            tools
+        )
+    );
+    Ok(())
+}
+
+/// Whether a path is `-`, meaning stdin for input and stdout for output
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Check that every input the modify phase needs exists
+fn validate_modify_paths(
+    context_dir: &Path,
+    xml_path: &Path,
+    server_path: &Path,
+    model_path: &Path,
+) -> Result<()> {
+    let checks = [
+        (context_dir, "Context directory"),
+        (xml_path, "XML file"),
+        (server_path, "Server executable"),
+        (model_path, "Model file"),
+    ];
+    for (path, what) in checks {
+        let from_stdin = what == "XML file" && is_stdio(path);
+        if !from_stdin && !path.exists() {
+            return Err(ZError::Config(format!("{what} not found: {}", path.display())));
+        }
     }
-    if !model_path.exists() {
-        return Err(ZError::Config(format!(
-            "Model file not found: {}",
-            model_path.display()
-        )));
+    Ok(())
+}
+
+/// Load the XML to modify from `xml_path`, or from stdin when it is `-`,
+/// refusing input over `max_bytes`
+fn load_modify_xml(xml_path: &Path, max_bytes: u64) -> Result<xml::XmlModifier> {
+    let xml_modifier = if is_stdio(xml_path) {
+        eprintln!("Loading XML from stdin");
+        let mut content = Vec::new();
+        let mut stdin = std::io::Read::take(std::io::stdin(), max_bytes.saturating_add(1));
+        std::io::Read::read_to_end(&mut stdin, &mut content)?;
+        if content.len() as u64 > max_bytes {
+            return Err(ZError::Config(format!(
+                "XML on stdin is over the limit of {max_bytes} bytes (--max-xml-bytes); \
+                 the whole document is held in memory"
+            )));
+        }
+        xml::XmlModifier::from_bytes(content)?
+    } else {
+        eprintln!("Loading XML: {}", xml_path.display());
+        xml::XmlModifier::from_file_with_limit(xml_path, max_bytes)?
+    };
+    if xml_modifier.len() > LARGE_XML_BYTES {
+        eprintln!(
+            "{} XML is {} MB; every edit copies the whole document, so expect high memory use",
+            color::warning(),
+            xml_modifier.len() / 1_000_000
+        );
     }
+    Ok(xml_modifier)
+}
+
+/// Run the LLM modification phase
+#[allow(clippy::too_many_arguments)]
+fn run_modify(
+    context_dir: &Path,
+    xml_path: &Path,
+    server_path: &Path,
+    model_path: &Path,
+    server_args: &ServerArgs,
+    max_turns: usize,
+    output: &ModifyOutput,
+    append_system_context: bool,
+    require_changes: bool,
+    tool_format: ToolFormat,
+    instructions: Option<&str>,
+    tools: Vec<structs::ToolDefinition>,
+    elements_per_query: usize,
+    max_xml_bytes: u64,
+) -> Result<()> {
+    validate_modify_paths(context_dir, xml_path, server_path, model_path)?;
 
     // Setup shutdown flag
     let shutdown = Arc::new(AtomicBool::new(false));
@@ -277,11 +1675,11 @@ fn run_modify(
     // Load context
     eprintln!("Loading context from: {}", context_dir.display());
     let context_manager = context::ContextManager::from_directory(context_dir)?;
-    eprintln!("Found {} context files", context_manager.file_count());
+    eprintln!("Found {} context files", color::green(context_manager.file_count()));
 
     // Load XML
-    eprintln!("Loading XML: {}", xml_path.display());
-    let xml_modifier = xml::XmlModifier::from_file(xml_path)?;
+    let xml_modifier = load_modify_xml(xml_path, max_xml_bytes)?
+        .with_empty_element_style(output.empty_element_style);
 
     // Check for shutdown before LLM startup
     if shutdown.load(Ordering::SeqCst) {
@@ -298,7 +1696,14 @@ fn run_modify(
         .to_str()
         .ok_or_else(|| ZError::Config("Model path contains invalid UTF-8".into()))?;
 
-    let server = llm::LlamaServer::spawn(server_str, model_str, context_size, gpu_layers)?;
+    let mut server = llm::LlamaServer::spawn(
+        server_str,
+        model_str,
+        server_args.context_size,
+        server_args.gpu_layers,
+        &server_args.extra_args,
+        server_args.keep_server,
+    )?;
 
     if shutdown.load(Ordering::SeqCst) {
         eprintln!("Shutdown requested, stopping server");
@@ -306,15 +1711,38 @@ fn run_modify(
     }
 
     // Build system prompt
-    let system_prompt = llm::build_modify_system_prompt(&context_manager);
+    let system_prompt = llm::build_modify_system_prompt(
+        &context_manager,
+        append_system_context,
+        instructions,
+        &tools,
+    );
 
     // Run conversation
-    let mut client = llm::LlmClient::new(&server, &system_prompt, max_turns);
-    client.add_user_message(
-        "Please read the context files to understand the ML analysis, then modify the XML file accordingly.",
-    );
+    let mut client = llm::LlmClient::new(&mut server, &system_prompt, max_turns)
+        .with_tool_format(tool_format)
+        .with_request_timeout(server_args.request_timeout);
+    if let Some(text) = instructions {
+        client.add_user_message(&format!(
+            "{}\n\nUse the context files as supporting information, then modify the XML file accordingly.",
+            text.trim()
+        ));
+    } else {
+        client.add_user_message(
+            "Please read the context files to understand the ML analysis, then modify the XML file accordingly.",
+        );
+    }
 
-    let modifications = client.run_modify_conversation(&context_manager, &xml_modifier)?;
+    let mut handler = llm::tools::ModifyToolHandler::new(&context_manager, &xml_modifier)
+        .with_require_modifications(require_changes)
+        .with_tools(tools)
+        .with_elements_per_query(elements_per_query);
+    let modifications = client.run_modify_conversation(&mut handler)?;
+    if let Some(path) = &output.record_script {
+        let script = handler.script();
+        script.write(path)?;
+        eprintln!("Recorded {} edit(s) to {}", script.steps.len(), path.display());
+    }
 
     // Report usage
     let usage = client.total_usage();
@@ -322,15 +1750,51 @@ fn run_modify(
         "Token usage: {} prompt + {} completion = {} total",
         usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
     );
+    if output.show_model_summary {
+        print_model_summary(handler.finish_summary(), client.final_message());
+    }
 
+    if output.reformat {
+        xml_modifier.reformat("  ")?;
+    }
     if modifications.is_empty() {
-        eprintln!("No modifications were made");
-        return Ok(());
+        if handler.no_changes_confirmed() {
+            eprintln!("No modifications were made (model confirmed none were needed)");
+        } else {
+            eprintln!("{}", color::yellow("No modifications were made"));
+        }
+        return write_unmodified_xml(&xml_modifier, xml_path, output);
     }
 
-    eprintln!("Applied {} modifications", modifications.len());
+    save_modified_xml(&xml_modifier, xml_path, &modifications, output)
+}
 
-    if dry_run {
+/// Print what the model said about its run: the `finish` summary and its final
+/// message, skipping the message when it only repeats the summary
+fn print_model_summary(finish_summary: Option<&str>, final_message: Option<&str>) {
+    if finish_summary.is_none() && final_message.is_none() {
+        eprintln!("The model gave no summary");
+        return;
+    }
+    if let Some(summary) = finish_summary {
+        eprintln!("Model summary: {summary}");
+    }
+    if let Some(message) = final_message.filter(|m| Some(*m) != finish_summary) {
+        eprintln!("Model's final message:\n{message}");
+    }
+}
+
+/// Report the applied modifications and write the modified XML to `xml_path`
+/// (stdout for `-`), or just list them for a dry run
+fn save_modified_xml(
+    xml_modifier: &xml::XmlModifier,
+    xml_path: &Path,
+    modifications: &[String],
+    output: &ModifyOutput,
+) -> Result<()> {
+    eprintln!("Applied {} modifications", color::green(modifications.len()));
+
+    if output.dry_run {
         eprintln!("Dry run - not saving XML");
         for (i, m) in modifications.iter().enumerate() {
             eprintln!("  {}: {m}", i + 1);
@@ -338,10 +1802,70 @@ fn run_modify(
         return Ok(());
     }
 
-    // Get modified XML and write
-    let modified_xml = xml_modifier.get_content();
-    xml::XmlModifier::write_to_file(&modified_xml, xml_path)?;
-    eprintln!("XML updated: {}", xml_path.display());
+    write_xml(xml_modifier, xml_path)
+}
+
+/// Write the XML of a run that made no edits: the re-indented XML for
+/// `--reformat`, otherwise the input passed through unchanged when it came from
+/// stdin so a pipeline still gets its XML
+fn write_unmodified_xml(
+    xml_modifier: &xml::XmlModifier,
+    xml_path: &Path,
+    output: &ModifyOutput,
+) -> Result<()> {
+    if output.dry_run {
+        return Ok(());
+    }
+    if output.reformat {
+        return write_xml(xml_modifier, xml_path);
+    }
+    if is_stdio(xml_path) {
+        let content = xml_modifier.encode(&xml_modifier.get_content());
+        std::io::Write::write_all(&mut std::io::stdout(), &content)?;
+    }
+    Ok(())
+}
 
+/// Write the current XML to `xml_path`, or stdout for `-`
+fn write_xml(xml_modifier: &xml::XmlModifier, xml_path: &Path) -> Result<()> {
+    let modified_xml = xml_modifier.encode(&xml_modifier.render()?);
+    if is_stdio(xml_path) {
+        std::io::Write::write_all(&mut std::io::stdout(), &modified_xml)?;
+        eprintln!("XML written to stdout");
+    } else {
+        xml::XmlModifier::write_to_file(&modified_xml, xml_path)?;
+        eprintln!("XML updated: {}", color::green(xml_path.display()));
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_unmodified_xml_reformat() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let path = dir.path().join("doc.xml");
+        std::fs::write(&path, "<root><a>1</a></root>").expect("write");
+        let output = |reformat| ModifyOutput {
+            dry_run: false,
+            empty_element_style: None,
+            reformat,
+            record_script: None,
+            show_model_summary: false,
+        };
+
+        // Without --reformat a run with no edits leaves the file alone
+        let xml_modifier = xml::XmlModifier::from_file(&path).expect("load");
+        write_unmodified_xml(&xml_modifier, &path, &output(false)).expect("write");
+        assert_eq!(std::fs::read_to_string(&path).expect("read"), "<root><a>1</a></root>");
+
+        xml_modifier.reformat("  ").expect("reformat");
+        write_unmodified_xml(&xml_modifier, &path, &output(true)).expect("write");
+        assert_eq!(
+            std::fs::read_to_string(&path).expect("read"),
+            "<root>\n  <a>1</a>\n</root>"
+        );
+    }
+}
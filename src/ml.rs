@@ -1,5 +1,9 @@
+pub mod archive;
 pub mod clustering;
 pub mod correlation;
+pub mod drift;
+pub mod duplicates;
+pub mod explain;
 pub mod features;
 pub mod output;
 pub mod pipeline;
@@ -0,0 +1,88 @@
+//! Packing analyze output files into one tar archive (`--archive`)
+
+use crate::structs::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Write every regular file in `dir` into a tar archive at `path`, named by file
+/// name and in name order. Gzip-compressed if `path` ends in `.gz` or `.tgz`.
+///
+/// # Errors
+/// Returns error if `dir` cannot be read or the archive cannot be written
+pub fn write_dir_archive(dir: &Path, path: &Path) -> Result<()> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .filter_map(std::result::Result::ok)
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+    names.sort();
+
+    let file = fs::File::create(path)?;
+    let gzip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz") || ext.eq_ignore_ascii_case("tgz"));
+    if gzip {
+        append_files(GzEncoder::new(file, Compression::default()), dir, &names)?.finish()?;
+    } else {
+        append_files(file, dir, &names)?;
+    }
+    Ok(())
+}
+
+/// Stream `names` from `dir` into a tar written to `writer`, returning the writer
+/// once the end-of-archive blocks are out
+fn append_files<W: Write>(writer: W, dir: &Path, names: &[String]) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for name in names {
+        builder.append_path_with_name(dir.join(name), name)?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    /// Name and content of every entry in a tar archive, in archive order
+    fn read_entries<R: Read>(reader: R) -> Vec<(String, String)> {
+        tar::Archive::new(reader)
+            .entries()
+            .expect("read entries")
+            .map(|entry| {
+                let mut entry = entry.expect("read entry");
+                let name = entry.path().expect("entry path").display().to_string();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).expect("read content");
+                (name, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_dir_archive() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let files = dir.path().join("files");
+        fs::create_dir(&files).expect("create files dir");
+        fs::write(files.join("summary.txt"), "hello\n").expect("write");
+        fs::write(files.join("clusters.csv"), "row_id,cluster\n").expect("write");
+        let expected = vec![
+            ("clusters.csv".to_string(), "row_id,cluster\n".to_string()),
+            ("summary.txt".to_string(), "hello\n".to_string()),
+        ];
+
+        let tar_path = dir.path().join("out.tar");
+        write_dir_archive(&files, &tar_path).expect("write tar");
+        let tar = fs::File::open(&tar_path).expect("open tar");
+        assert_eq!(read_entries(tar), expected);
+
+        let gz_path = dir.path().join("out.tar.gz");
+        write_dir_archive(&files, &gz_path).expect("write tar.gz");
+        let gz = fs::File::open(&gz_path).expect("open tar.gz");
+        assert_eq!(read_entries(GzDecoder::new(gz)), expected);
+    }
+}
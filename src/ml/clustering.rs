@@ -1,15 +1,100 @@
-use crate::structs::{ClusterResult, DbscanResult, NormalizedFeatures, Result, ZError};
+use crate::structs::{
+    ClusterDescriptor, ClusterMethod, ClusterResult, DbscanPointType, DbscanResult,
+    FeatureDeviation, NormalizedFeatures, Result, TruthComparison, ZError,
+};
 use linfa::traits::{Fit, Predict, Transformer};
 use linfa::ParamGuard;
 use linfa::DatasetBase;
-use linfa_clustering::{Dbscan, KMeans};
-use ndarray::Array2;
+use linfa_clustering::{Dbscan, GaussianMixtureModel, KMeans};
+use ndarray::{Array1, Array2};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-/// Perform K-means clustering on normalized features
+/// Perform K-means clustering on normalized features, seeding centroid initialization with `seed`
 ///
 /// # Errors
 /// Returns error if clustering fails
-pub fn kmeans(features: &NormalizedFeatures, k: usize) -> Result<ClusterResult> {
+pub fn kmeans(features: &NormalizedFeatures, k: usize, seed: u64) -> Result<ClusterResult> {
+    let dataset = clustering_dataset(features, k)?;
+
+    // Run K-means
+    let model = KMeans::params_with_rng(k, StdRng::seed_from_u64(seed))
+        .max_n_iterations(100)
+        .tolerance(1e-4)
+        .fit(&dataset)
+        .map_err(|e| ZError::Ml(format!("K-means failed: {e}")))?;
+
+    let predictions = model.predict(&dataset);
+    Ok(labeled_result(
+        ClusterMethod::Kmeans,
+        predictions.iter().copied().collect(),
+        k,
+    ))
+}
+
+/// Fit a Gaussian mixture model with `k` components on normalized features and
+/// assign each sample its most likely component, seeding initialization with `seed`
+///
+/// # Errors
+/// Returns error if there are fewer distinct rows than `k` or the fit fails
+pub fn gmm(features: &NormalizedFeatures, k: usize, seed: u64) -> Result<ClusterResult> {
+    let dataset = clustering_dataset(features, k)?;
+
+    let model = GaussianMixtureModel::params_with_rng(k, StdRng::seed_from_u64(seed))
+        .max_n_iterations(100)
+        .tolerance(1e-4)
+        .fit(&dataset)
+        .map_err(|e| ZError::Ml(format!("Gaussian mixture failed: {e}")))?;
+
+    let predictions = model.predict(dataset.records());
+    Ok(labeled_result(
+        ClusterMethod::Gmm,
+        predictions.iter().copied().collect(),
+        k,
+    ))
+}
+
+/// Use a DBSCAN result as the primary clustering. Noise points get the trailing
+/// label `n_clusters`, so every sample still has a group.
+#[must_use]
+pub fn dbscan_clusters(result: &DbscanResult) -> ClusterResult {
+    let k = result.n_clusters;
+    let labels = result.labels.iter().map(|label| label.unwrap_or(k)).collect();
+    let mut sizes = result.sizes.clone();
+    let noise_cluster = (result.n_noise > 0).then(|| {
+        sizes.push(result.n_noise);
+        k
+    });
+    ClusterResult {
+        method: ClusterMethod::Dbscan,
+        labels,
+        k,
+        sizes,
+        noise_cluster,
+    }
+}
+
+/// Count group sizes for labels in `0..k`
+fn labeled_result(method: ClusterMethod, labels: Vec<usize>, k: usize) -> ClusterResult {
+    let mut sizes: Vec<usize> = vec![0usize; k];
+    for &cluster_id in &labels {
+        sizes[cluster_id] += 1;
+    }
+
+    ClusterResult {
+        method,
+        labels,
+        k,
+        sizes,
+        noise_cluster: None,
+    }
+}
+
+/// Check that `k` clusters can be formed and build the dataset to fit them on
+fn clustering_dataset(
+    features: &NormalizedFeatures,
+    k: usize,
+) -> Result<DatasetBase<Array2<f64>, Array1<()>>> {
     let n_samples = features.n_samples();
 
     if n_samples < k {
@@ -22,36 +107,246 @@ pub fn kmeans(features: &NormalizedFeatures, k: usize) -> Result<ClusterResult>
         return Err(ZError::Ml("k must be at least 1".into()));
     }
 
+    // Duplicate rows share a point, so fewer distinct points than k leaves clusters empty
+    let distinct = distinct_rows(features);
+    if distinct < k {
+        return Err(ZError::Ml(format!(
+            "Cannot create {k} clusters from only {distinct} distinct rows ({n_samples} total); \
+             use -k {distinct} or lower"
+        )));
+    }
+
     // Convert to ndarray Array2
     let flat_data: Vec<f64> = features.to_flat();
     let array = Array2::from_shape_vec((n_samples, features.n_features()), flat_data)
         .map_err(|e| ZError::Ml(format!("Failed to create array: {e}")))?;
 
-    // Create dataset
-    let dataset = DatasetBase::from(array);
+    Ok(DatasetBase::from(array))
+}
 
-    // Run K-means
-    let model = KMeans::params(k)
-        .max_n_iterations(100)
-        .tolerance(1e-4)
-        .fit(&dataset)
-        .map_err(|e| ZError::Ml(format!("K-means failed: {e}")))?;
+/// Scale rows are rounded at before comparing, so float noise doesn't make duplicates distinct
+const DISTINCT_ROUNDING: f64 = 1e9;
+
+/// Number of distinct feature vectors, comparing values rounded to `1 / DISTINCT_ROUNDING`
+fn distinct_rows(features: &NormalizedFeatures) -> usize {
+    features
+        .data
+        .iter()
+        .map(|row| {
+            row.iter()
+                // Adding 0.0 folds -0.0 into 0.0 so both hash alike
+                .map(|v| ((v * DISTINCT_ROUNDING).round() + 0.0).to_bits())
+                .collect::<Vec<_>>()
+        })
+        .collect::<std::collections::HashSet<_>>()
+        .len()
+}
 
-    let predictions = model.predict(&dataset);
-    let labels: Vec<usize> = predictions.iter().copied().collect();
+/// Most distinctive features listed per cluster descriptor
+const DESCRIPTOR_FEATURES: usize = 3;
 
-    // Calculate cluster sizes
-    let mut sizes: Vec<usize> = vec![0usize; k];
+/// Smallest normalized centroid deviation worth describing as high or low
+const MIN_DESCRIPTOR_DEVIATION: f64 = 0.05;
 
-    for &cluster_id in &labels {
-        sizes[cluster_id] += 1;
+/// Describe each cluster by the features whose centroid deviates most from the
+/// overall mean in normalized space, up to `DESCRIPTOR_FEATURES` per cluster.
+/// The DBSCAN noise group isn't a cluster and gets no descriptor.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn cluster_descriptors(
+    clusters: &ClusterResult,
+    features: &NormalizedFeatures,
+) -> Vec<ClusterDescriptor> {
+    let n_features = features.n_features();
+    let mut overall = vec![0.0; n_features];
+    let mut centroids = vec![vec![0.0; n_features]; clusters.sizes.len()];
+    for (row, &cluster) in features.data.iter().zip(&clusters.labels) {
+        for (j, &value) in row.iter().enumerate() {
+            overall[j] += value;
+            centroids[cluster][j] += value;
+        }
+    }
+    let n_samples = features.n_samples().max(1) as f64;
+    for mean in &mut overall {
+        *mean /= n_samples;
     }
 
-    Ok(ClusterResult {
-        labels,
-        k,
-        sizes,
-    })
+    centroids
+        .into_iter()
+        .zip(&clusters.sizes)
+        .enumerate()
+        .filter(|&(cluster, _)| clusters.is_cluster(cluster))
+        .map(|(cluster, (centroid, &size))| {
+            let mut top_features: Vec<FeatureDeviation> = if size == 0 {
+                Vec::new()
+            } else {
+                centroid
+                    .iter()
+                    .zip(&overall)
+                    .zip(&features.names)
+                    .map(|((sum, mean), name)| FeatureDeviation {
+                        name: name.clone(),
+                        deviation: sum / size as f64 - mean,
+                    })
+                    .filter(|f| f.deviation.abs() >= MIN_DESCRIPTOR_DEVIATION)
+                    .collect()
+            };
+            top_features.sort_by(|a, b| b.deviation.abs().total_cmp(&a.deviation.abs()));
+            top_features.truncate(DESCRIPTOR_FEATURES);
+            ClusterDescriptor {
+                cluster,
+                size,
+                top_features,
+            }
+        })
+        .collect()
+}
+
+/// Adjusted Rand Index between two labelings of the same samples.
+/// 1.0 means identical partitions (up to label renaming), ~0.0 means chance agreement.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn adjusted_rand_index(labels_a: &[usize], labels_b: &[usize]) -> f64 {
+    let n = labels_a.len().min(labels_b.len());
+    if n < 2 {
+        return 1.0;
+    }
+
+    let pairs = |count: usize| (count * count.saturating_sub(1) / 2) as f64;
+
+    let mut contingency: std::collections::HashMap<(usize, usize), usize> =
+        std::collections::HashMap::new();
+    let mut row_sums: std::collections::HashMap<usize, usize> = std::collections::HashMap::new();
+    let mut col_sums: std::collections::HashMap<usize, usize> = std::collections::HashMap::new();
+    for (&a, &b) in labels_a.iter().zip(labels_b).take(n) {
+        *contingency.entry((a, b)).or_default() += 1;
+        *row_sums.entry(a).or_default() += 1;
+        *col_sums.entry(b).or_default() += 1;
+    }
+
+    let index: f64 = contingency.values().map(|&c| pairs(c)).sum();
+    let sum_a: f64 = row_sums.values().map(|&c| pairs(c)).sum();
+    let sum_b: f64 = col_sums.values().map(|&c| pairs(c)).sum();
+    let expected = sum_a * sum_b / pairs(n);
+    let max_index = f64::midpoint(sum_a, sum_b);
+
+    // Both labelings trivial (all one cluster or all singletons): they agree perfectly
+    if (max_index - expected).abs() < f64::EPSILON {
+        return 1.0;
+    }
+
+    (index - expected) / (max_index - expected)
+}
+
+/// Normalized Mutual Information between two labelings of the same samples: their
+/// mutual information over the arithmetic mean of their entropies. 1.0 means identical
+/// partitions (up to label renaming), 0.0 means independent ones.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn normalized_mutual_info(labels_a: &[usize], labels_b: &[usize]) -> f64 {
+    use std::collections::HashMap;
+
+    let n = labels_a.len().min(labels_b.len());
+    if n == 0 {
+        return 1.0;
+    }
+
+    let mut joint: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut counts_a: HashMap<usize, usize> = HashMap::new();
+    let mut counts_b: HashMap<usize, usize> = HashMap::new();
+    for (&a, &b) in labels_a.iter().zip(labels_b).take(n) {
+        *joint.entry((a, b)).or_default() += 1;
+        *counts_a.entry(a).or_default() += 1;
+        *counts_b.entry(b).or_default() += 1;
+    }
+
+    let total = n as f64;
+    let entropy = |counts: &HashMap<usize, usize>| -> f64 {
+        counts
+            .values()
+            .map(|&c| {
+                let p = c as f64 / total;
+                -p * p.ln()
+            })
+            .sum()
+    };
+    let (h_a, h_b) = (entropy(&counts_a), entropy(&counts_b));
+    // Both labelings trivial (a single group each): they agree perfectly
+    if h_a == 0.0 && h_b == 0.0 {
+        return 1.0;
+    }
+
+    let mutual_info: f64 = joint
+        .iter()
+        .map(|(&(a, b), &c)| {
+            let p = c as f64 / total;
+            let expected = counts_a[&a] as f64 * counts_b[&b] as f64 / (total * total);
+            p * (p / expected).ln()
+        })
+        .sum();
+    (mutual_info / f64::midpoint(h_a, h_b)).clamp(0.0, 1.0)
+}
+
+/// Compare cluster labels against `truth`, the value of a label column for each
+/// sample. Each distinct value is a class; an empty value is a class of its own.
+#[must_use]
+pub fn compare_to_truth(
+    column: &str,
+    clusters: &ClusterResult,
+    truth: &[String],
+) -> TruthComparison {
+    let classes: Vec<String> = truth
+        .iter()
+        .cloned()
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let class_labels: Vec<usize> = truth
+        .iter()
+        .map(|value| classes.binary_search(value).unwrap_or_default())
+        .collect();
+
+    let mut contingency = vec![vec![0usize; classes.len()]; clusters.sizes.len()];
+    for (&cluster, &class) in clusters.labels.iter().zip(&class_labels) {
+        contingency[cluster][class] += 1;
+    }
+
+    TruthComparison {
+        column: column.to_string(),
+        adjusted_rand_index: adjusted_rand_index(&clusters.labels, &class_labels),
+        normalized_mutual_info: normalized_mutual_info(&clusters.labels, &class_labels),
+        classes,
+        contingency,
+    }
+}
+
+/// Cluster stability: run K-means `runs` times with seeds drawn from `seed` and
+/// return the mean pairwise Adjusted Rand Index between the labelings
+///
+/// # Errors
+/// Returns error if any K-means run fails
+#[allow(clippy::cast_precision_loss)]
+pub fn kmeans_stability(
+    features: &NormalizedFeatures,
+    k: usize,
+    runs: usize,
+    seed: u64,
+) -> Result<f64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let labelings = (0..runs)
+        .map(|_| kmeans(features, k, rng.gen()).map(|r| r.labels))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut total = 0.0;
+    let mut count = 0usize;
+    for (i, a) in labelings.iter().enumerate() {
+        for b in &labelings[i + 1..] {
+            total += adjusted_rand_index(a, b);
+            count += 1;
+        }
+    }
+
+    Ok(if count == 0 { 1.0 } else { total / count as f64 })
 }
 
 /// Find optimal k using elbow method (simplified)
@@ -64,11 +359,11 @@ pub fn kmeans(features: &NormalizedFeatures, k: usize) -> Result<ClusterResult>
 )]
 pub fn suggest_k(features: &NormalizedFeatures, max_k: usize) -> usize {
     let n = features.n_samples();
-    let max_k = max_k.min(n).max(1);
+    let max_k = max_k.min(distinct_rows(features)).max(1);
 
     // Simple heuristic: sqrt of sample count, capped
     let suggested = (n as f64).sqrt().round() as usize;
-    suggested.clamp(2, max_k)
+    suggested.max(2).min(max_k)
 }
 
 /// Estimate a good epsilon for DBSCAN using k-distance heuristic
@@ -162,7 +457,7 @@ pub fn dbscan(
     // Count clusters and noise
     let mut n_clusters = 0usize;
     let mut n_noise = 0usize;
-    let mut cluster_sizes = std::collections::HashMap::new();
+    let mut cluster_sizes = std::collections::BTreeMap::new();
 
     for label in &labels {
         match label {
@@ -180,16 +475,57 @@ pub fn dbscan(
         .map(|c| cluster_sizes.get(&c).copied().unwrap_or(0))
         .collect();
 
+    let point_types = classify_points(features, &labels, epsilon, min_points);
+
     Ok(DbscanResult {
         labels,
+        point_types,
         n_clusters,
         n_noise,
         sizes,
         epsilon,
+        epsilon_estimated: false,
         min_points,
     })
 }
 
+/// Classify each point as core, border, or noise.
+/// A point is core if at least `min_points` points, itself included, lie within `epsilon`.
+fn classify_points(
+    features: &NormalizedFeatures,
+    labels: &[Option<usize>],
+    epsilon: f64,
+    min_points: usize,
+) -> Vec<DbscanPointType> {
+    labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| {
+            if label.is_none() {
+                return DbscanPointType::Noise;
+            }
+            let neighbors = features
+                .data
+                .iter()
+                .filter(|other| {
+                    features.data[i]
+                        .iter()
+                        .zip(other.iter())
+                        .map(|(a, b)| (a - b).powi(2))
+                        .sum::<f64>()
+                        .sqrt()
+                        <= epsilon
+                })
+                .count();
+            if neighbors >= min_points {
+                DbscanPointType::Core
+            } else {
+                DbscanPointType::Border
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,7 +555,7 @@ mod tests {
         let features = FeatureMatrix::from_csv(&csv).expect("extract features");
         let normalized = features.normalize();
 
-        let result = kmeans(&normalized, 2).expect("run kmeans");
+        let result = kmeans(&normalized, 2, 42).expect("run kmeans");
 
         assert_eq!(result.k, 2);
         assert_eq!(result.labels.len(), 8);
@@ -227,6 +563,98 @@ mod tests {
         assert!(result.sizes.iter().all(|&s| s == 4));
     }
 
+    #[test]
+    fn test_kmeans_too_few_distinct_rows() {
+        let features = NormalizedFeatures {
+            names: vec!["x".to_string(), "y".to_string()],
+            data: (0..10).map(|i| vec![f64::from(i % 2), 0.5]).collect(),
+            row_indices: (0..10).collect(),
+            mins: vec![0.0, 0.0],
+            maxs: vec![1.0, 1.0],
+        };
+
+        let err = kmeans(&features, 5, 42).expect_err("k above distinct rows");
+        let message = err.to_string();
+        assert!(message.contains("only 2 distinct rows"), "{message}");
+        assert!(message.contains("-k 2 or lower"), "{message}");
+
+        assert_eq!(suggest_k(&features, 10), 2);
+        assert!(kmeans(&features, 2, 42).is_ok());
+    }
+
+    #[test]
+    fn test_gmm_and_dbscan_clusters() {
+        let csv = create_clusterable_csv();
+        let normalized = FeatureMatrix::from_csv(&csv).expect("extract features").normalize();
+
+        let result = gmm(&normalized, 2, 42).expect("run gmm");
+        assert_eq!(result.method, ClusterMethod::Gmm);
+        assert_eq!(result.sizes, vec![4, 4]);
+        assert_eq!(result.labels[..4], [result.labels[0]; 4]);
+        assert!(gmm(&normalized, 9, 42).is_err());
+
+        let db = DbscanResult {
+            labels: vec![Some(0), Some(0), None, Some(1), Some(1), None],
+            point_types: Vec::new(),
+            n_clusters: 2,
+            n_noise: 2,
+            sizes: vec![2, 2],
+            epsilon: 0.1,
+            epsilon_estimated: false,
+            min_points: 2,
+        };
+        let clusters = dbscan_clusters(&db);
+        assert_eq!(clusters.k, 2);
+        assert_eq!(clusters.labels, vec![0, 0, 2, 1, 1, 2]);
+        assert_eq!(clusters.sizes, vec![2, 2, 2]);
+        assert_eq!(clusters.noise_cluster, Some(2));
+        assert!(!clusters.is_cluster(2));
+    }
+
+    #[test]
+    fn test_adjusted_rand_index() {
+        // Same partition with renamed labels
+        let a = [0, 0, 1, 1, 2, 2];
+        let b = [1, 1, 2, 2, 0, 0];
+        assert!((adjusted_rand_index(&a, &b) - 1.0).abs() < 1e-9);
+
+        // Known value: sklearn gives 0.242424... for this pair
+        let c = [0, 0, 0, 1, 1, 1];
+        let d = [0, 0, 1, 1, 2, 2];
+        assert!((adjusted_rand_index(&c, &d) - 0.242_424_242).abs() < 1e-6);
+
+        let csv = create_clusterable_csv();
+        let normalized = FeatureMatrix::from_csv(&csv).expect("extract features").normalize();
+        let stability = kmeans_stability(&normalized, 2, 5, 1).expect("stability");
+        assert!((stability - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_compare_to_truth() {
+        let a = [0, 0, 1, 1, 2, 2];
+        let b = [1, 1, 2, 2, 0, 0];
+        assert!((normalized_mutual_info(&a, &b) - 1.0).abs() < 1e-9);
+        // MI = (2/3) ln 2 over the mean of entropies ln 2 and ln 3
+        let c = [0, 0, 0, 1, 1, 1];
+        let d = [0, 0, 1, 1, 2, 2];
+        assert!((normalized_mutual_info(&c, &d) - 0.515_803_7).abs() < 1e-6);
+        assert!(normalized_mutual_info(&[0, 0, 0, 0], &[0, 1, 0, 1]).abs() < 1e-9);
+
+        let clusters = ClusterResult {
+            method: ClusterMethod::Kmeans,
+            labels: vec![0, 0, 1, 1],
+            k: 2,
+            sizes: vec![2, 2],
+            noise_cluster: None,
+        };
+        let truth: Vec<String> = ["dog", "dog", "cat", "dog"].map(String::from).to_vec();
+        let comparison = compare_to_truth("species", &clusters, &truth);
+        assert_eq!(comparison.classes, vec!["cat", "dog"]);
+        assert_eq!(comparison.contingency, vec![vec![0, 2], vec![1, 1]]);
+        assert!(comparison.adjusted_rand_index < 1.0);
+        assert!(comparison.normalized_mutual_info > 0.0);
+    }
+
     #[test]
     fn test_suggest_k() {
         let csv = create_clusterable_csv();
@@ -234,7 +662,7 @@ mod tests {
         let normalized = features.normalize();
 
         let k = suggest_k(&normalized, 10);
-        assert!(k >= 2 && k <= 10);
+        assert!((2..=10).contains(&k));
     }
 
     #[test]
@@ -249,6 +677,12 @@ mod tests {
         let result = dbscan(&normalized, eps, 3).expect("dbscan");
         assert_eq!(result.labels.len(), 8);
         assert!(result.n_clusters > 0);
+
+        assert_eq!(result.point_types.len(), 8);
+        for (label, point_type) in result.labels.iter().zip(&result.point_types) {
+            assert_eq!(label.is_none(), *point_type == DbscanPointType::Noise);
+        }
+        assert!(result.point_types.contains(&DbscanPointType::Core));
     }
 
     #[test]
@@ -261,4 +695,40 @@ mod tests {
         assert!(eps > 0.0);
         assert!(eps < 10.0);
     }
+
+    #[test]
+    fn test_cluster_descriptors() {
+        let csv = create_clusterable_csv();
+        let features = FeatureMatrix::from_csv(&csv).expect("extract features");
+        let normalized = features.normalize();
+        // Rows 0-3 sit low on x and y, rows 4-7 high
+        let clusters = ClusterResult {
+            method: ClusterMethod::Kmeans,
+            labels: vec![0, 0, 0, 0, 1, 1, 1, 1],
+            k: 2,
+            sizes: vec![4, 4],
+            noise_cluster: None,
+        };
+
+        let descriptors = cluster_descriptors(&clusters, &normalized);
+        assert_eq!(descriptors.len(), 2);
+        let low = &descriptors[0];
+        assert_eq!(low.size, 4);
+        assert!(low.top_features.iter().all(|f| f.deviation < 0.0));
+        assert_eq!(low.top_features[0].name, "x");
+        assert!(low.top_features[0].deviation < -0.4);
+        assert!(descriptors[1].describe().starts_with("Cluster 1 (4 rows): high "));
+
+        // A cluster matching the overall mean has nothing to describe
+        let single = ClusterResult {
+            method: ClusterMethod::Kmeans,
+            labels: vec![0; 8],
+            k: 1,
+            sizes: vec![8],
+            noise_cluster: None,
+        };
+        let descriptors = cluster_descriptors(&single, &normalized);
+        assert!(descriptors[0].top_features.is_empty());
+        assert!(descriptors[0].describe().contains("close to the overall mean"));
+    }
 }
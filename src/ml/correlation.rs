@@ -1,24 +1,52 @@
-//! Correlation matrix computation
+//! Correlation, covariance and mutual information matrix computation
 
-use crate::structs::{CorrelationMatrix, FeatureMatrix, Result};
-use crate::ml::stats::correlation;
+use crate::structs::{
+    CorrelationMatrix, CorrelationMethod, CovarianceMatrix, FeatureMatrix, MutualInfoMatrix,
+    Result, ZError,
+};
+use crate::ml::stats::{
+    covariance_from_deviations, deviations, kendall_tau, mutual_information,
+    pearson_from_deviations,
+};
+
+/// Each feature column's deviations from its mean, shared by every pair
+///
+/// # Errors
+/// Returns error if there are fewer than 2 samples
+fn column_deviations(features: &FeatureMatrix) -> Result<Vec<Vec<f64>>> {
+    if features.data.len() < 2 {
+        return Err(ZError::Ml("Need at least 2 values for correlation".into()));
+    }
+
+    Ok((0..features.n_features())
+        .filter_map(|i| features.column(i))
+        .map(|col| deviations(&col))
+        .collect())
+}
 
 /// Compute the `NxN` correlation matrix between all numeric features
 ///
 /// # Errors
 /// Returns error if feature extraction or correlation calculation fails
-pub fn correlation_matrix(features: &FeatureMatrix) -> Result<CorrelationMatrix> {
+pub fn correlation_matrix(
+    features: &FeatureMatrix,
+    method: CorrelationMethod,
+) -> Result<CorrelationMatrix> {
     let n = features.n_features();
     let mut matrix = vec![vec![0.0; n]; n];
 
-    let columns: Vec<Vec<f64>> = (0..n)
-        .filter_map(|i| features.column(i))
-        .collect();
+    let columns: Vec<Vec<f64>> = match method {
+        CorrelationMethod::Pearson => column_deviations(features)?,
+        CorrelationMethod::Kendall => (0..n).filter_map(|i| features.column(i)).collect(),
+    };
 
     for i in 0..n {
         matrix[i][i] = 1.0;
         for j in (i + 1)..n {
-            let r = correlation(&columns[i], &columns[j])?;
+            let r = match method {
+                CorrelationMethod::Pearson => pearson_from_deviations(&columns[i], &columns[j]),
+                CorrelationMethod::Kendall => kendall_tau(&columns[i], &columns[j])?,
+            };
             matrix[i][j] = r;
             matrix[j][i] = r;
         }
@@ -30,6 +58,75 @@ pub fn correlation_matrix(features: &FeatureMatrix) -> Result<CorrelationMatrix>
     })
 }
 
+/// Compute the `NxN` sample covariance matrix between all numeric features
+///
+/// # Errors
+/// Returns error if there are fewer than 2 samples
+pub fn covariance_matrix(features: &FeatureMatrix) -> Result<CovarianceMatrix> {
+    let columns = column_deviations(features)?;
+    let n = columns.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+
+    for i in 0..n {
+        for j in i..n {
+            let cov = covariance_from_deviations(&columns[i], &columns[j])?;
+            matrix[i][j] = cov;
+            matrix[j][i] = cov;
+        }
+    }
+
+    Ok(CovarianceMatrix {
+        names: features.names.clone(),
+        matrix,
+    })
+}
+
+/// Histogram bins for mutual information: Sturges' rule, `ceil(log2 n) + 1`
+///
+/// Grows slowly with the sample count so the joint histogram (bins squared
+/// cells) stays populated enough to keep the small-sample upward bias low.
+#[must_use]
+pub const fn mutual_info_bins(n_samples: usize) -> usize {
+    if n_samples < 2 {
+        return 1;
+    }
+    (n_samples - 1).ilog2() as usize + 2
+}
+
+/// Compute the `NxN` mutual information matrix between all numeric features
+///
+/// The diagonal holds each feature's binned entropy.
+///
+/// # Errors
+/// Returns error if there are fewer than 2 samples
+pub fn mutual_info_matrix(features: &FeatureMatrix) -> Result<MutualInfoMatrix> {
+    let n_samples = features.data.len();
+    if n_samples < 2 {
+        return Err(ZError::Ml("Need at least 2 values for mutual information".into()));
+    }
+
+    let bins = mutual_info_bins(n_samples);
+    let columns: Vec<Vec<f64>> = (0..features.n_features())
+        .filter_map(|i| features.column(i))
+        .collect();
+    let n = columns.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+
+    for i in 0..n {
+        for j in i..n {
+            let mi = mutual_information(&columns[i], &columns[j], bins);
+            matrix[i][j] = mi;
+            matrix[j][i] = mi;
+        }
+    }
+
+    Ok(MutualInfoMatrix {
+        names: features.names.clone(),
+        bins,
+        matrix,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -45,7 +142,7 @@ mod tests {
 
         let csv = CsvData::from_file(file.path(), false).expect("parse");
         let features = FeatureMatrix::from_csv(&csv).expect("extract");
-        let corr = correlation_matrix(&features).expect("correlate");
+        let corr = correlation_matrix(&features, CorrelationMethod::Pearson).expect("correlate");
 
         assert_eq!(corr.names.len(), 3);
         assert_eq!(corr.matrix.len(), 3);
@@ -54,4 +151,81 @@ mod tests {
         // a and b are perfectly correlated
         assert!((corr.matrix[0][1] - 1.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_pearson_matches_pairwise() {
+        let content = "a,b,c\n1.0,7.0,3.0\n2.0,1.0,3.0\n4.0,5.0,3.0\n8.0,2.0,3.0";
+        let mut file = NamedTempFile::new().expect("create");
+        file.write_all(content.as_bytes()).expect("write");
+
+        let csv = CsvData::from_file(file.path(), false).expect("parse");
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let corr = correlation_matrix(&features, CorrelationMethod::Pearson).expect("correlate");
+
+        // Pearson r computed directly from the raw columns
+        let (a, b) = ([1.0, 2.0, 4.0, 8.0], [7.0, 1.0, 5.0, 2.0]);
+        let (mean_a, mean_b) = (3.75, 3.75);
+        let cov: f64 = a.iter().zip(&b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum();
+        let var_a: f64 = a.iter().map(|x| (x - mean_a).powi(2)).sum();
+        let var_b: f64 = b.iter().map(|y| (y - mean_b).powi(2)).sum();
+        assert!((corr.matrix[0][1] - cov / (var_a * var_b).sqrt()).abs() < 1e-12);
+        // Constant column has no correlation
+        assert!(corr.matrix[0][2].abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_covariance_matrix() {
+        let content = "a,b\n1.0,2.0\n2.0,4.0\n3.0,6.0\n4.0,8.0";
+        let mut file = NamedTempFile::new().expect("create");
+        file.write_all(content.as_bytes()).expect("write");
+
+        let csv = CsvData::from_file(file.path(), false).expect("parse");
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let cov = covariance_matrix(&features).expect("covariance");
+
+        // var(a) = 5/3, cov(a, b) = 2 var(a), var(b) = 4 var(a)
+        assert!((cov.matrix[0][0] - 5.0 / 3.0).abs() < 1e-12);
+        assert!((cov.matrix[0][1] - 10.0 / 3.0).abs() < 1e-12);
+        assert!((cov.matrix[1][0] - cov.matrix[0][1]).abs() < f64::EPSILON);
+        assert!((cov.matrix[1][1] - 20.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mutual_info_bins() {
+        assert_eq!(mutual_info_bins(2), 2);
+        assert_eq!(mutual_info_bins(8), 4);
+        assert_eq!(mutual_info_bins(9), 5);
+        assert_eq!(mutual_info_bins(1000), 11);
+    }
+
+    #[test]
+    fn test_mutual_info_matrix() {
+        let content = "a,b,c\n-2.0,4.0,1.0\n-1.0,1.0,5.0\n0.0,0.0,2.0\n1.0,1.0,4.0\n2.0,4.0,3.0";
+        let mut file = NamedTempFile::new().expect("create");
+        file.write_all(content.as_bytes()).expect("write");
+
+        let csv = CsvData::from_file(file.path(), false).expect("parse");
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let mi = mutual_info_matrix(&features).expect("mutual information");
+
+        assert_eq!(mi.bins, 4);
+        assert_eq!(mi.matrix.len(), 3);
+        assert!((mi.matrix[0][1] - mi.matrix[1][0]).abs() < f64::EPSILON);
+        // Diagonal is the entropy, an upper bound for any pair
+        assert!(mi.matrix[0][1] <= mi.matrix[0][0] + 1e-12);
+        assert!(mi.matrix[0][1] > 0.0);
+    }
+
+    #[test]
+    fn test_kendall_correlation_matrix() {
+        let content = "a,b\n1.0,1.0\n2.0,8.0\n3.0,27.0\n4.0,64.0";
+        let mut file = NamedTempFile::new().expect("create");
+        file.write_all(content.as_bytes()).expect("write");
+
+        let csv = CsvData::from_file(file.path(), false).expect("parse");
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let corr = correlation_matrix(&features, CorrelationMethod::Kendall).expect("correlate");
+
+        assert!((corr.matrix[0][1] - 1.0).abs() < 1e-9);
+    }
 }
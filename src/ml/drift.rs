@@ -0,0 +1,120 @@
+//! Drift report between two analysis runs' `stats.json` files
+
+use crate::structs::{ColumnDrift, Result, StatsDiff};
+use std::path::Path;
+
+/// Compare two `stats.json` files, flagging columns whose mean moved by more
+/// than `threshold_sd` of the old standard deviation
+///
+/// # Errors
+/// Returns error if either file cannot be read or parsed
+pub fn diff_stats(old_path: &Path, new_path: &Path, threshold_sd: f64) -> Result<StatsDiff> {
+    let old = super::output::read_stats_json(old_path)?;
+    let new = super::output::read_stats_json(new_path)?;
+
+    let columns = new
+        .statistics
+        .iter()
+        .filter_map(|n| {
+            let o = old.statistics.iter().find(|o| o.name == n.name)?;
+            let mean_delta = n.mean - o.mean;
+            let mean_shift_sd = (o.std_dev > 0.0).then(|| mean_delta / o.std_dev);
+            let drifted = mean_shift_sd.map_or(mean_delta != 0.0, |s| s.abs() > threshold_sd);
+            Some(ColumnDrift {
+                name: n.name.clone(),
+                mean_delta,
+                std_dev_delta: n.std_dev - o.std_dev,
+                q1_delta: n.q1 - o.q1,
+                median_delta: n.median - o.median,
+                q3_delta: n.q3 - o.q3,
+                mean_shift_sd,
+                drifted,
+            })
+        })
+        .collect();
+
+    let names = |stats: &[super::output::StatsEntry]| -> Vec<String> {
+        stats.iter().map(|s| s.name.clone()).collect()
+    };
+    let old_names = names(&old.statistics);
+    let new_names = names(&new.statistics);
+
+    Ok(StatsDiff {
+        threshold_sd,
+        old_rows: old.row_count,
+        new_rows: new.row_count,
+        columns,
+        added_columns: new_names
+            .iter()
+            .filter(|n| !old_names.contains(n))
+            .cloned()
+            .collect(),
+        removed_columns: old_names
+            .iter()
+            .filter(|n| !new_names.contains(n))
+            .cloned()
+            .collect(),
+        old_k: old.clustering.k,
+        new_k: new.clustering.k,
+        old_dbscan_clusters: old.dbscan.as_ref().map(|d| d.n_clusters),
+        new_dbscan_clusters: new.dbscan.as_ref().map(|d| d.n_clusters),
+        old_anomalies: old.anomalies_summary.total,
+        new_anomalies: new.anomalies_summary.total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn stats_entry(name: &str, mean: f64, std_dev: f64) -> String {
+        format!(
+            r#"{{"name":"{name}","count":10,"mean":{mean},"std_dev":{std_dev},"min":0.0,
+            "max":10.0,"q1":2.0,"median":5.0,"q3":8.0,"iqr":6.0}}"#
+        )
+    }
+
+    fn stats_json(mean_a: f64, with_b: bool, k: usize, anomalies: usize) -> String {
+        let mut statistics = vec![stats_entry("a", mean_a, 2.0)];
+        if with_b {
+            statistics.push(stats_entry("b", 1.0, 1.0));
+        }
+        format!(
+            r#"{{"row_count":10,"column_count":2,"columns":["a","b"],
+            "statistics":[{}],
+            "clustering":{{"k":{k},"clusters":[]}},
+            "anomalies_summary":{{"total":{anomalies},"by_type":[]}}}}"#,
+            statistics.join(",")
+        )
+    }
+
+    #[test]
+    fn test_diff_stats() {
+        let dir = TempDir::new().expect("create temp dir");
+        let old_path = dir.path().join("old.json");
+        let new_path = dir.path().join("new.json");
+        fs::write(&old_path, stats_json(5.0, true, 3, 4)).expect("write");
+        fs::write(&new_path, stats_json(10.0, false, 4, 7)).expect("write");
+
+        let diff = diff_stats(&old_path, &new_path, 2.0).expect("diff");
+
+        assert_eq!(diff.columns.len(), 1);
+        let a = &diff.columns[0];
+        assert!((a.mean_delta - 5.0).abs() < 1e-9);
+        assert_eq!(a.mean_shift_sd, Some(2.5));
+        assert!(a.drifted);
+        assert_eq!(diff.removed_columns, vec!["b"]);
+        assert!(diff.added_columns.is_empty());
+        assert_eq!((diff.old_k, diff.new_k), (3, 4));
+        assert_eq!((diff.old_anomalies, diff.new_anomalies), (4, 7));
+
+        let summary = diff.summary();
+        assert!(summary.contains("Columns drifted (mean shift > 2 std): 1 of 1"));
+        assert!(summary.contains("! a: mean +5.0000 (+2.50 std)"));
+
+        let within = diff_stats(&old_path, &new_path, 3.0).expect("diff");
+        assert!(!within.columns[0].drifted);
+    }
+}
@@ -0,0 +1,210 @@
+//! Near-duplicate row detection over the normalized feature matrix
+
+use crate::structs::{FeatureMatrix, NearDuplicatePair, NormalizedFeatures};
+use std::collections::HashMap;
+
+/// Leading features used to bucket rows into grid cells. Each row is compared
+/// against the 3^`GRID_DIMS` cells around its own, so more dimensions prune
+/// better but cost more lookups.
+const GRID_DIMS: usize = 3;
+
+/// Pairs of rows within Euclidean `threshold` of each other, ordered by row.
+///
+/// Rows are bucketed on a grid of `threshold`-sized cells over the first
+/// `GRID_DIMS` features. Rows within `threshold` differ by at most that much on
+/// every feature, so only rows in neighbouring cells need a full distance check.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn near_duplicate_pairs(
+    features: &NormalizedFeatures,
+    threshold: f64,
+) -> Vec<NearDuplicatePair> {
+    let dims = features.n_features().min(GRID_DIMS);
+    let cell_of = |row: &[f64]| -> Vec<i64> {
+        row[..dims].iter().map(|v| (v / threshold).floor() as i64).collect()
+    };
+
+    let mut grid: HashMap<Vec<i64>, Vec<usize>> = HashMap::new();
+    for (i, row) in features.data.iter().enumerate() {
+        grid.entry(cell_of(row)).or_default().push(i);
+    }
+
+    // Every combination of -1/0/+1 cell steps across the grid dimensions
+    let offsets: Vec<Vec<i64>> = (0..3usize.pow(dims as u32))
+        .map(|mut n| {
+            (0..dims)
+                .map(|_| {
+                    let step = [-1, 0, 1][n % 3];
+                    n /= 3;
+                    step
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for (i, row) in features.data.iter().enumerate() {
+        let cell = cell_of(row);
+        for offset in &offsets {
+            let neighbour: Vec<i64> = cell.iter().zip(offset).map(|(c, o)| c + o).collect();
+            let Some(candidates) = grid.get(&neighbour) else {
+                continue;
+            };
+            for &j in candidates.iter().filter(|&&j| j > i) {
+                let distance = row
+                    .iter()
+                    .zip(&features.data[j])
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                if distance <= threshold {
+                    pairs.push(NearDuplicatePair {
+                        row_a: features.row_indices[i],
+                        row_b: features.row_indices[j],
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+
+    pairs.sort_by_key(|p| (p.row_a, p.row_b));
+    pairs
+}
+
+/// Drop rows that are near-duplicates of an earlier kept row, returning the
+/// reduced feature matrices and the number of rows dropped.
+///
+/// Pairs are taken in row order and the later row of each is dropped unless
+/// the earlier one was already dropped, so every dropped row is within the
+/// threshold of a row that is kept.
+#[must_use]
+pub fn collapse_near_duplicates(
+    features: &FeatureMatrix,
+    normalized: &NormalizedFeatures,
+    pairs: &[NearDuplicatePair],
+) -> (FeatureMatrix, NormalizedFeatures, usize) {
+    // Source row id -> sample index (row_indices is ascending)
+    let sample_of = |row: usize| normalized.row_indices.binary_search(&row).ok();
+
+    let mut dropped = vec![false; normalized.n_samples()];
+    for pair in pairs {
+        if let (Some(a), Some(b)) = (sample_of(pair.row_a), sample_of(pair.row_b)) {
+            if !dropped[a] {
+                dropped[b] = true;
+            }
+        }
+    }
+
+    let keep = |data: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        data.iter()
+            .zip(&dropped)
+            .filter(|(_, &d)| !d)
+            .map(|(row, _)| row.clone())
+            .collect()
+    };
+    let row_indices: Vec<usize> = normalized
+        .row_indices
+        .iter()
+        .zip(&dropped)
+        .filter(|(_, &d)| !d)
+        .map(|(&r, _)| r)
+        .collect();
+
+    let collapsed = dropped.iter().filter(|&&d| d).count();
+    (
+        FeatureMatrix {
+            names: features.names.clone(),
+            data: keep(&features.data),
+            row_indices: row_indices.clone(),
+        },
+        NormalizedFeatures {
+            names: normalized.names.clone(),
+            data: keep(&normalized.data),
+            row_indices,
+            mins: normalized.mins.clone(),
+            maxs: normalized.maxs.clone(),
+        },
+        collapsed,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::CsvData;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn normalized_features(content: &str) -> (FeatureMatrix, NormalizedFeatures) {
+        let mut file = NamedTempFile::new().expect("create temp file");
+        file.write_all(content.as_bytes()).expect("write content");
+        let csv = CsvData::from_file(file.path(), false).expect("parse csv");
+        let features = FeatureMatrix::from_csv(&csv).expect("extract features");
+        let normalized = features.normalize();
+        (features, normalized)
+    }
+
+    /// Pairs found by comparing every row with every other
+    fn brute_force_pairs(features: &NormalizedFeatures, threshold: f64) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..features.n_samples() {
+            for j in (i + 1)..features.n_samples() {
+                let distance = features.data[i]
+                    .iter()
+                    .zip(&features.data[j])
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                if distance <= threshold {
+                    pairs.push((features.row_indices[i], features.row_indices[j]));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn test_near_duplicate_pairs() {
+        let (_, normalized) =
+            normalized_features("x,y\n0,0\n10,10\n0.1,0\n5,5\n10,9.9\n0,0.2\n");
+
+        let pairs = near_duplicate_pairs(&normalized, 0.02);
+        let found: Vec<_> = pairs.iter().map(|p| (p.row_a, p.row_b)).collect();
+        // Rows 2 and 5 are each close to row 0 but ~0.022 apart
+        assert_eq!(found, vec![(0, 2), (0, 5), (1, 4)]);
+        assert!(pairs.iter().all(|p| p.distance <= 0.02));
+    }
+
+    #[test]
+    fn test_near_duplicate_pairs_match_brute_force() {
+        let rows = (0..200)
+            .map(|i| format!("{},{},{},{}", i % 17, (i * 7) % 13, (i * 3) % 11, i % 5))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (_, normalized) = normalized_features(&format!("a,b,c,d\n{rows}"));
+
+        for threshold in [0.05, 0.2, 0.5] {
+            let found: Vec<_> = near_duplicate_pairs(&normalized, threshold)
+                .iter()
+                .map(|p| (p.row_a, p.row_b))
+                .collect();
+            assert_eq!(found, brute_force_pairs(&normalized, threshold));
+        }
+    }
+
+    #[test]
+    fn test_collapse_near_duplicates() {
+        let (features, normalized) =
+            normalized_features("x,y\n0,0\n10,10\n0.1,0\n5,5\n10,9.9\n0,0.2\n");
+        let pairs = near_duplicate_pairs(&normalized, 0.02);
+
+        let (kept, kept_normalized, collapsed) =
+            collapse_near_duplicates(&features, &normalized, &pairs);
+        assert_eq!(collapsed, 3);
+        assert_eq!(kept.row_indices, vec![0, 1, 3]);
+        assert_eq!(kept_normalized.row_indices, vec![0, 1, 3]);
+        assert_eq!(kept.data[2], vec![5.0, 5.0]);
+        assert_eq!(kept_normalized.data.len(), 3);
+    }
+}
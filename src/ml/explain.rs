@@ -0,0 +1,162 @@
+//! Per-row anomaly report built from an analyze output directory (`z explain-anomaly`)
+
+use crate::structs::{AnomalyExplanation, FlaggedAnomaly, OutlierColumn, Result, ZError};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Suffix of anomaly types measured against the row's cluster (`cluster_iqr`)
+const CLUSTER_OUTLIER_SUFFIX: &str = "_cluster_outlier";
+
+#[derive(Deserialize)]
+struct StructuredRecord {
+    row_id: usize,
+    #[serde(rename = "type")]
+    anomaly_type: String,
+    column: Option<String>,
+    value: Option<f64>,
+    z_score: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ClusterRecord {
+    row_id: usize,
+    cluster: Option<usize>,
+    distance_to_centroid: Option<f64>,
+}
+
+/// Collect what `clusters.csv`, `anomalies.csv`, `anomalies_structured.csv` and
+/// `stats.json` in `output_dir` say about source row `row_id`. Outlier columns
+/// need `anomalies_structured.csv` and are left empty without it.
+///
+/// # Errors
+/// Returns error if a required file cannot be read or parsed, or the row appears
+/// in neither `clusters.csv` nor `anomalies.csv`
+pub fn explain_anomaly(output_dir: &Path, row_id: usize) -> Result<AnomalyExplanation> {
+    let stats = super::output::read_stats_json(&output_dir.join("stats.json"))?;
+
+    let mut clustered = None;
+    for record in csv::Reader::from_path(output_dir.join("clusters.csv"))?.deserialize() {
+        let record: ClusterRecord = record?;
+        if record.row_id == row_id {
+            clustered = Some(record);
+            break;
+        }
+    }
+
+    // Read by position: `--anomalies-with-data` appends source columns whose
+    // names may repeat the leading `row_id,anomaly_type,score,cluster,details`
+    let mut anomalies = Vec::new();
+    for record in csv::Reader::from_path(output_dir.join("anomalies.csv"))?.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        if field(0).parse::<usize>().ok() != Some(row_id) {
+            continue;
+        }
+        anomalies.push(FlaggedAnomaly {
+            anomaly_type: field(1).to_string(),
+            score: field(2).parse().map_err(|_| {
+                ZError::Config(format!("Invalid score in anomalies.csv: {:?}", field(2)))
+            })?,
+            details: field(4).to_string(),
+        });
+    }
+
+    if clustered.is_none() && anomalies.is_empty() {
+        return Err(ZError::Config(format!(
+            "Row {row_id} is in neither clusters.csv nor anomalies.csv in {}",
+            output_dir.display()
+        )));
+    }
+
+    let structured_path = output_dir.join("anomalies_structured.csv");
+    let mut outlier_columns = Vec::new();
+    if structured_path.exists() {
+        for record in csv::Reader::from_path(structured_path)?.deserialize() {
+            let record: StructuredRecord = record?;
+            let (Some(column), Some(value), Some(z_score)) =
+                (record.column, record.value, record.z_score)
+            else {
+                continue;
+            };
+            if record.row_id != row_id {
+                continue;
+            }
+            let described = stats.statistics.iter().find(|s| s.name == column);
+            outlier_columns.push(OutlierColumn {
+                within_cluster: record.anomaly_type.ends_with(CLUSTER_OUTLIER_SUFFIX),
+                mean: described.map(|s| s.mean),
+                std_dev: described.map(|s| s.std_dev),
+                column,
+                value,
+                z_score,
+            });
+        }
+    }
+
+    Ok(AnomalyExplanation {
+        row_id,
+        row_count: stats.row_count,
+        clustered: clustered.is_some(),
+        cluster: clustered.as_ref().and_then(|c| c.cluster),
+        distance_to_centroid: clustered.as_ref().and_then(|c| c.distance_to_centroid),
+        outlier_columns,
+        anomalies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_explain_anomaly() {
+        let dir = TempDir::new().expect("create temp dir");
+        fs::write(
+            dir.path().join("stats.json"),
+            r#"{"row_count":4,"column_count":1,"columns":["price"],
+            "statistics":[{"name":"price","count":4,"mean":10.0,"std_dev":2.0,"min":8.0,
+            "max":18.0,"q1":8.5,"median":9.0,"q3":11.0,"iqr":2.5}],
+            "clustering":{"k":2,"clusters":[]},
+            "anomalies_summary":{"total":2,"by_type":[]}}"#,
+        )
+        .expect("write stats");
+        fs::write(
+            dir.path().join("clusters.csv"),
+            "row_id,cluster,distance_to_centroid\n0,0,0.1000\n1,1,0.7500\n2,,\n",
+        )
+        .expect("write clusters");
+        fs::write(
+            dir.path().join("anomalies.csv"),
+            "row_id,anomaly_type,score,cluster,details\n\
+             1,price_outlier,1.0000,1,\"price=18.00 is 4.0 std from mean\"\n\
+             2,dbscan_noise,0.8000,,\"Row 2 classified as noise by DBSCAN (eps=0.5000)\"\n",
+        )
+        .expect("write anomalies");
+        fs::write(
+            dir.path().join("anomalies_structured.csv"),
+            "row_id,type,column,value,z_score,score,cluster\n\
+             1,price_outlier,price,18.0000,4.0000,1.0000,1\n\
+             2,dbscan_noise,,,,0.8000,\n",
+        )
+        .expect("write structured");
+
+        let explanation = explain_anomaly(dir.path(), 1).expect("explain");
+        assert_eq!(explanation.cluster, Some(1));
+        assert_eq!(explanation.outlier_columns.len(), 1);
+        assert_eq!(explanation.outlier_columns[0].mean, Some(10.0));
+        let summary = explanation.summary();
+        assert!(summary.contains("Cluster: 1 (distance to centroid 0.7500)"));
+        assert!(summary.contains("- price = 18.0000: +4.00 std from the mean (mean 10.0000"));
+        assert!(summary.contains("- price_outlier (score 1.0000): price=18.00 is 4.0 std"));
+
+        let noise = explain_anomaly(dir.path(), 2).expect("explain");
+        assert!(noise.outlier_columns.is_empty());
+        assert!(noise.summary().contains("Cluster: none (DBSCAN noise)"));
+
+        let quiet = explain_anomaly(dir.path(), 0).expect("explain");
+        assert!(quiet.summary().contains("Anomalies: none"));
+        assert!(explain_anomaly(dir.path(), 9).is_err());
+    }
+}
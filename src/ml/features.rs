@@ -1,44 +1,44 @@
-use crate::structs::{CsvData, FeatureMatrix, NormalizedFeatures, Result, ZError};
+use crate::structs::{CsvData, FeatureMatrix, NormParams, NormalizedFeatures, Result, ZError};
+use std::fs;
+use std::path::Path;
 
 impl FeatureMatrix {
-    /// Extract numeric features from CSV data
+    /// Extract numeric features from CSV data. Rows with an empty or
+    /// non-numeric value in any numeric column (including fields padded onto
+    /// short rows) are skipped as incomplete.
     ///
     /// # Errors
     /// Returns error if no numeric columns found
+    #[cfg(test)]
     pub fn from_csv(csv: &CsvData) -> Result<Self> {
-        let numeric_cols = csv.numeric_column_indices();
+        Self::from_csv_with_options(csv, false)
+    }
+
+    /// Like `from_csv`, optionally also coding boolean columns (`true`/`yes`/`y` and
+    /// `false`/`no`/`n`) as 1.0/0.0 features; an unrecognized cell makes the row incomplete
+    ///
+    /// # Errors
+    /// Returns error if no feature columns found
+    pub fn from_csv_with_options(csv: &CsvData, encode_booleans: bool) -> Result<Self> {
+        let feature_cols = csv.feature_columns(encode_booleans);
 
-        if numeric_cols.is_empty() {
+        if feature_cols.is_empty() {
             return Err(ZError::Ml("No numeric columns found".into()));
         }
 
-        let names: Vec<String> = numeric_cols
+        let names: Vec<String> = feature_cols
             .iter()
-            .filter_map(|&i| csv.headers.get(i).cloned())
+            .filter_map(|&(i, _)| csv.headers.get(i).cloned())
             .collect();
 
+        let columns: Vec<&[Option<f64>]> = feature_cols.iter().map(|(_, col)| &**col).collect();
         let mut data = Vec::new();
         let mut row_indices = Vec::new();
 
-        for (row_idx, row) in csv.rows.iter().enumerate() {
-            let mut features = Vec::new();
-            let mut valid = true;
-
-            for &col_idx in &numeric_cols {
-                if let Some(val) = row.get(col_idx) {
-                    if let Ok(num) = val.parse::<f64>() {
-                        features.push(num);
-                    } else {
-                        valid = false;
-                        break;
-                    }
-                } else {
-                    valid = false;
-                    break;
-                }
-            }
+        for row_idx in 0..csv.row_count() {
+            let features: Option<Vec<f64>> = columns.iter().map(|col| col[row_idx]).collect();
 
-            if valid && features.len() == numeric_cols.len() {
+            if let Some(features) = features {
                 data.push(features);
                 row_indices.push(row_idx);
             }
@@ -96,6 +96,120 @@ impl FeatureMatrix {
             maxs,
         }
     }
+
+    /// Normalize using min/max parameters saved from another dataset.
+    ///
+    /// Features are matched by name. Values outside the saved range are
+    /// clamped to [0, 1]; constant saved columns map to 0.5 as in `normalize`.
+    ///
+    /// # Errors
+    /// Returns error if the file can't be read, its `names`/`mins`/`maxs` differ in
+    /// length, or it lacks one of our features
+    pub fn normalize_with_params(&self, path: &Path) -> Result<NormalizedFeatures> {
+        let params: NormParams = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if params.mins.len() != params.names.len() || params.maxs.len() != params.names.len() {
+            return Err(ZError::Config(format!(
+                "Normalization file {} has {} names but {} mins and {} maxs",
+                path.display(),
+                params.names.len(),
+                params.mins.len(),
+                params.maxs.len()
+            )));
+        }
+
+        let mut mins = Vec::with_capacity(self.n_features());
+        let mut maxs = Vec::with_capacity(self.n_features());
+        for name in &self.names {
+            let idx = params.names.iter().position(|n| n == name).ok_or_else(|| {
+                ZError::Config(format!(
+                    "Normalization file {} has no parameters for feature '{name}'",
+                    path.display()
+                ))
+            })?;
+            mins.push(params.mins[idx]);
+            maxs.push(params.maxs[idx]);
+        }
+
+        let normalized_data: Vec<Vec<f64>> = self
+            .data
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, &val)| {
+                        let range = maxs[i] - mins[i];
+                        if range == 0.0 {
+                            0.5
+                        } else {
+                            ((val - mins[i]) / range).clamp(0.0, 1.0)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(NormalizedFeatures {
+            names: self.names.clone(),
+            data: normalized_data,
+            row_indices: self.row_indices.clone(),
+            mins,
+            maxs,
+        })
+    }
+}
+
+impl NormalizedFeatures {
+    /// Save the min/max scaling parameters as JSON for `normalize_with_params`
+    ///
+    /// # Errors
+    /// Returns error if the file cannot be written
+    pub fn save_params(&self, path: &Path) -> Result<()> {
+        let params = NormParams {
+            names: self.names.clone(),
+            mins: self.mins.clone(),
+            maxs: self.maxs.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&params)?)?;
+        Ok(())
+    }
+
+    /// Multiply each named feature column by its weight; unlisted features keep
+    /// weight 1.0 and a weight of 0 effectively drops the feature. The saved ranges
+    /// are scaled to match, so `denormalize` still returns original units.
+    ///
+    /// # Errors
+    /// Returns error if a weight names a feature that doesn't exist or is given twice
+    pub fn apply_weights(&mut self, weights: &[(String, f64)]) -> Result<()> {
+        let mut factors: Vec<Option<f64>> = vec![None; self.n_features()];
+        for (name, weight) in weights {
+            let idx = self.names.iter().position(|n| n == name).ok_or_else(|| {
+                ZError::Config(format!("Feature weight for unknown numeric column: {name}"))
+            })?;
+            if factors[idx].replace(*weight).is_some() {
+                return Err(ZError::Config(format!("Feature weight given twice: {name}")));
+            }
+        }
+        let factors: Vec<f64> = factors.into_iter().map(|f| f.unwrap_or(1.0)).collect();
+        for row in &mut self.data {
+            for (val, factor) in row.iter_mut().zip(&factors) {
+                *val *= factor;
+            }
+        }
+        for ((min, max), &factor) in self.mins.iter().zip(&mut self.maxs).zip(&factors) {
+            // A dropped feature denormalizes to its minimum for every row
+            *max = if factor == 0.0 { *min } else { *min + (*max - *min) / factor };
+        }
+        Ok(())
+    }
+
+    /// Map a row of normalized values back to original feature units
+    #[must_use]
+    pub fn denormalize(&self, row: &[f64]) -> Vec<f64> {
+        row.iter()
+            .zip(self.mins.iter().zip(&self.maxs))
+            .map(|(&val, (&min, &max))| val.mul_add(max - min, min))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -121,6 +235,23 @@ mod tests {
         assert_eq!(features.names, vec!["x", "y"]);
     }
 
+    #[test]
+    fn test_boolean_features() {
+        let content = "name,x,active,note\na,1,yes,y\nb,2,No,n\nc,3,TRUE,maybe\nd,4,,n\n";
+        let mut file = NamedTempFile::new().expect("create temp file");
+        file.write_all(content.as_bytes()).expect("write content");
+        let csv = CsvData::from_file(file.path(), false).expect("parse csv");
+        assert_eq!(csv.boolean_column_indices(), vec![2]);
+
+        let plain = FeatureMatrix::from_csv(&csv).expect("extract features");
+        assert_eq!(plain.names, vec!["x"]);
+
+        let features = FeatureMatrix::from_csv_with_options(&csv, true).expect("extract features");
+        assert_eq!(features.names, vec!["x", "active"]);
+        assert_eq!(features.data, vec![vec![1.0, 1.0], vec![2.0, 0.0], vec![3.0, 1.0]]);
+        assert_eq!(features.row_indices, vec![0, 1, 2]);
+    }
+
     #[test]
     fn test_normalization() {
         let csv = create_test_csv();
@@ -131,4 +262,67 @@ mod tests {
         assert!((normalized.data[0][0] - 0.0).abs() < 0.01);
         assert!((normalized.data[2][0] - 1.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_apply_weights() {
+        let csv = create_test_csv();
+        let mut normalized = FeatureMatrix::from_csv(&csv).expect("extract features").normalize();
+
+        let weights = [("x".to_string(), 2.0), ("y".to_string(), 0.0)];
+        normalized.apply_weights(&weights).expect("apply weights");
+        assert_eq!(normalized.data[1], vec![1.0, 0.0]);
+        assert_eq!(normalized.data[2], vec![2.0, 0.0]);
+        // Weighted values still map back to original units
+        assert_eq!(normalized.denormalize(&normalized.data[2]), vec![3.0, 10.0]);
+
+        assert!(normalized.apply_weights(&[("z".to_string(), 1.5)]).is_err());
+        let twice = [("x".to_string(), 2.0), ("x".to_string(), 3.0)];
+        let err = normalized.apply_weights(&twice).expect_err("duplicate weight");
+        assert!(err.to_string().contains("given twice: x"));
+    }
+
+    #[test]
+    fn test_denormalize() {
+        let csv = create_test_csv();
+        let normalized = FeatureMatrix::from_csv(&csv).expect("extract features").normalize();
+
+        assert_eq!(normalized.denormalize(&normalized.data[1]), vec![2.0, 20.0]);
+        assert_eq!(normalized.denormalize(&[0.25, 0.5]), vec![1.5, 20.0]);
+    }
+
+    #[test]
+    fn test_normalization_params_roundtrip() {
+        let train = create_test_csv();
+        let train_features = FeatureMatrix::from_csv(&train).expect("extract features");
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let params_path = dir.path().join("norm.json");
+        train_features
+            .normalize()
+            .save_params(&params_path)
+            .expect("save params");
+
+        let content = "name,y,x\nd,20.0,2.0\ne,40.0,5.0";
+        let mut file = NamedTempFile::new().expect("create temp file");
+        file.write_all(content.as_bytes()).expect("write content");
+        let test = CsvData::from_file(file.path(), false).expect("parse csv");
+        let test_features = FeatureMatrix::from_csv(&test).expect("extract features");
+
+        let normalized = test_features
+            .normalize_with_params(&params_path)
+            .expect("normalize with params");
+
+        // Columns are matched by name: y=20 in [10, 30] -> 0.5, x=2 in [1, 3] -> 0.5
+        assert!((normalized.data[0][0] - 0.5).abs() < 0.01);
+        assert!((normalized.data[0][1] - 0.5).abs() < 0.01);
+        // Out-of-range values clamp
+        assert!((normalized.data[1][0] - 1.0).abs() < 0.01);
+        assert!((normalized.data[1][1] - 1.0).abs() < 0.01);
+
+        fs::write(&params_path, r#"{"names": ["y", "x"], "mins": [10.0], "maxs": [30.0, 3.0]}"#)
+            .expect("write params");
+        let err = test_features
+            .normalize_with_params(&params_path)
+            .expect_err("mismatched lengths");
+        assert!(err.to_string().contains("2 names but 1 mins and 2 maxs"));
+    }
 }
@@ -1,23 +1,90 @@
 //! ML output file writers for the analyze phase
 
 use crate::structs::{
-    AnalysisResult, Anomaly, ClusterResult, ColumnStats, CorrelationMatrix, CsvData,
-    DbscanResult, NormalizedFeatures, PcaResult, Result,
+    AnalysisResult, Anomaly, ClusterDescriptor, ClusterResult, ColumnCoercion, ColumnStats,
+    ConsensusAnomaly, CorrelationMatrix, CovarianceMatrix, CsvData, DbscanPointType, DbscanResult,
+    DistanceUnits, InferredType, MutualInfoMatrix, NearDuplicatePair, NormalizedFeatures,
+    PcaResult, Result, TruthComparison, ZError,
 };
-use serde::Serialize;
+use crate::context::resolve_within;
+use crate::csv_reader::{FALSE_TOKENS, TRUE_TOKENS};
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::io::{BufWriter, Write as _};
 use std::path::Path;
 
-/// Build the summary text from analysis results
+/// Every file the analyze phase can write, for detecting and clearing a previous run
+pub const OUTPUT_FILES: &[&str] = &[
+    "summary.txt",
+    "clusters.csv",
+    "anomalies.csv",
+    "anomalies_structured.csv",
+    "anomaly_consensus.csv",
+    "cluster_descriptions.txt",
+    "cluster_vs_truth.csv",
+    "clusters.geojson",
+    "coercion_report.txt",
+    "near_duplicates.csv",
+    "stats.json",
+    "correlation.csv",
+    "covariance.csv",
+    "mi_matrix.csv",
+    "dbscan.csv",
+    "pca_projection.csv",
+];
+
+/// Analyze output files already present in `output_dir`
 #[must_use]
-#[allow(clippy::cast_precision_loss)]
+pub fn existing_outputs(output_dir: &Path) -> Vec<&'static str> {
+    OUTPUT_FILES
+        .iter()
+        .copied()
+        .filter(|name| output_dir.join(name).is_file())
+        .collect()
+}
+
+/// Delete analyze output files from `output_dir`, leaving any other files alone
+///
+/// # Errors
+/// Returns error if an existing output file cannot be removed
+pub fn remove_outputs(output_dir: &Path) -> Result<()> {
+    for name in existing_outputs(output_dir) {
+        fs::remove_file(output_dir.join(name))?;
+    }
+    Ok(())
+}
+
+/// Feature pairs listed in the summary's mutual information section
+const MI_SUMMARY_PAIRS: usize = 5;
+
+/// Load a `stats.json` written by `write_stats_json`
+///
+/// # Errors
+/// Returns error if the file cannot be read or is not a stats file
+pub(super) fn read_stats_json(path: &Path) -> Result<StatsOutput> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Mean ARI below which the summary flags the clustering as unstable
+const WEAK_STABILITY: f64 = 0.5;
+
+/// Build the summary text from analysis results. `precision` overrides the
+/// decimal places of statistics, scores and coefficients (percentages are unaffected).
+#[must_use]
+#[allow(clippy::cast_precision_loss, clippy::too_many_lines)]
 pub fn build_summary(
     csv_path: &Path,
     csv_data: &CsvData,
     result: &AnalysisResult,
+    precision: Option<usize>,
 ) -> String {
     use std::fmt::Write as _;
 
+    let p2 = precision.unwrap_or(2);
+    let p3 = precision.unwrap_or(3);
+    let p4 = precision.unwrap_or(4);
+
     let mut summary = String::new();
     let _ = writeln!(
         summary,
@@ -29,35 +96,153 @@ pub fn build_summary(
     let _ = writeln!(summary, "================================");
     let _ = writeln!(
         summary,
-        "Rows: {}\nColumns: {} ({} numeric)",
+        "Rows: {}\nColumns: {} ({} numeric)\nDelimiter: {}",
         csv_data.row_count(),
         csv_data.col_count(),
-        result.column_stats.len()
+        csv_data.numeric_column_indices().len(),
+        csv_data.delimiter_name()
     );
+    if let Some(id) = &result.id_column {
+        let _ = writeln!(
+            summary,
+            "Id column: {id} (inferred to label pca_projection.csv; choose another with \
+             --label-column)"
+        );
+    }
+    if csv_data.truncated {
+        let _ = writeln!(
+            summary,
+            "Input truncated: only the first {} rows were read (--max-rows)",
+            csv_data.row_count()
+        );
+    }
+    if !result.coercions.is_empty() {
+        let columns: Vec<String> = result
+            .coercions
+            .iter()
+            .map(|c| format!("{} ({})", c.column, c.count))
+            .collect();
+        let _ = writeln!(
+            summary,
+            "Non-numeric values in numeric columns: {}; their rows were left out \
+             (see coercion_report.txt)",
+            columns.join(", ")
+        );
+    }
+    let _ = writeln!(summary);
+    let _ = writeln!(summary, "Column Types:");
+    for (name, inferred) in csv_data.headers.iter().zip(csv_data.column_types()) {
+        let _ = writeln!(summary, "- {name}: {}", inferred.as_str());
+    }
     let _ = writeln!(summary);
     let _ = writeln!(summary, "Key Statistics:");
     for stats in &result.column_stats {
-        let _ = writeln!(summary, "- {}", stats.summary());
+        let _ = writeln!(summary, "- {}", stats.summary_with_precision(p2));
+    }
+    if !result.boolean_features.is_empty() {
+        let _ = writeln!(summary);
+        let _ = writeln!(
+            summary,
+            "Boolean-Coded Features ({} = 1, {} = 0):",
+            TRUE_TOKENS.join("/"),
+            FALSE_TOKENS.join("/")
+        );
+        for name in &result.boolean_features {
+            let _ = writeln!(summary, "- {name}");
+        }
+    }
+    if !result.feature_weights.is_empty() {
+        let _ = writeln!(summary);
+        let _ = writeln!(summary, "Feature Weights (applied before clustering, DBSCAN and PCA):");
+        for fw in &result.feature_weights {
+            let _ = writeln!(summary, "- {}: {}", fw.name, fw.weight);
+        }
     }
     let _ = writeln!(summary);
-    let _ = writeln!(summary, "Clustering (k={}):", result.cluster_result.k);
-    for (i, size) in result.cluster_result.sizes.iter().enumerate() {
+    let clusters = &result.cluster_result;
+    let _ = writeln!(summary, "Clustering ({}, k={}):", clusters.method.as_str(), clusters.k);
+    for (i, size) in clusters.sizes.iter().enumerate() {
         let pct = (*size as f64 / csv_data.row_count() as f64) * 100.0;
-        let _ = writeln!(summary, "- Cluster {i} ({pct:.0}%): {size} samples");
+        if clusters.is_cluster(i) {
+            let _ = writeln!(summary, "- Cluster {i} ({pct:.0}%): {size} samples");
+        } else {
+            let _ = writeln!(summary, "- Noise ({pct:.0}%): {size} samples");
+        }
+    }
+    if let Some(stability) = result.cluster_stability {
+        let verdict = if stability < WEAK_STABILITY {
+            " - weak cluster structure, treat clusters with caution"
+        } else {
+            ""
+        };
+        let _ = writeln!(summary, "- Stability (mean ARI across runs): {stability:.p2$}{verdict}");
+    }
+    if let Some(truth) = &result.truth {
+        let _ = writeln!(summary);
+        let _ = writeln!(summary, "Clusters vs Truth Column '{}':", truth.column);
+        let _ = writeln!(summary, "- Adjusted Rand Index: {:.p2$}", truth.adjusted_rand_index);
+        let _ = writeln!(
+            summary,
+            "- Normalized Mutual Information: {:.p2$}",
+            truth.normalized_mutual_info
+        );
     }
     let _ = writeln!(summary);
-    let _ = writeln!(summary, "Anomalies Detected: {} rows", result.anomalies.len());
+    if result.suppressed_anomalies > 0 {
+        let _ = writeln!(
+            summary,
+            "Anomalies Detected: {} rows ({} anomalies suppressed by type filter)",
+            result.anomalies.len(),
+            result.suppressed_anomalies
+        );
+    } else {
+        let _ = writeln!(summary, "Anomalies Detected: {} rows", result.anomalies.len());
+    }
+    let agreed = result.consensus.iter().filter(|c| c.detectors.len() > 1).count();
+    if agreed > 0 {
+        let _ = writeln!(summary, "- Flagged by more than one detector: {agreed} rows");
+    }
+
+    if let Some(near) = &result.near_duplicates {
+        let _ = writeln!(summary);
+        let _ = writeln!(
+            summary,
+            "Near-Duplicates: {} pairs within distance {}",
+            near.pairs.len(),
+            near.threshold
+        );
+        if near.collapsed_rows > 0 {
+            let _ = writeln!(
+                summary,
+                "- {} rows collapsed into an earlier near-identical row before analysis",
+                near.collapsed_rows
+            );
+        }
+    }
 
     // DBSCAN section
     if let Some(dbscan) = &result.dbscan_result {
         let _ = writeln!(summary);
         let _ = writeln!(
             summary,
-            "DBSCAN Results (eps={:.4}, min_points={}):",
-            dbscan.epsilon, dbscan.min_points
+            "DBSCAN Results (eps={:.p4$}{}, min_points={}):",
+            dbscan.epsilon,
+            if dbscan.epsilon_estimated { " auto" } else { "" },
+            dbscan.min_points
         );
         let _ = writeln!(summary, "- Clusters found: {}", dbscan.n_clusters);
         let _ = writeln!(summary, "- Noise points: {}", dbscan.n_noise);
+        let n_core = dbscan
+            .point_types
+            .iter()
+            .filter(|&&t| t == DbscanPointType::Core)
+            .count();
+        let n_border = dbscan
+            .point_types
+            .iter()
+            .filter(|&&t| t == DbscanPointType::Border)
+            .count();
+        let _ = writeln!(summary, "- Core points: {n_core}, border points: {n_border}");
         for (i, size) in dbscan.sizes.iter().enumerate() {
             let _ = writeln!(summary, "- DBSCAN Cluster {i}: {size} samples");
         }
@@ -80,7 +265,7 @@ pub fn build_summary(
                     let direction = if r > 0.0 { "positive" } else { "negative" };
                     let _ = writeln!(
                         summary,
-                        "- {} vs {}: {:.3} ({} {})",
+                        "- {} vs {}: {:.p3$} ({} {})",
                         corr.names[i], corr.names[j], r, strength, direction
                     );
                 }
@@ -88,6 +273,27 @@ pub fn build_summary(
         }
     }
 
+    if let Some(mi) = &result.mutual_info {
+        let n = mi.names.len();
+        let mut pairs: Vec<(usize, usize)> =
+            (0..n).flat_map(|i| ((i + 1)..n).map(move |j| (i, j))).collect();
+        pairs.sort_by(|&(a, b), &(c, d)| {
+            mi.matrix[c][d]
+                .partial_cmp(&mi.matrix[a][b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let _ = writeln!(summary);
+        let _ = writeln!(summary, "Mutual Information (top pairs, {} bins, nats):", mi.bins);
+        for &(i, j) in pairs.iter().take(MI_SUMMARY_PAIRS) {
+            let _ = writeln!(
+                summary,
+                "- {} vs {}: {:.p3$}",
+                mi.names[i], mi.names[j], mi.matrix[i][j]
+            );
+        }
+    }
+
     // PCA section
     if let Some(pca) = &result.pca {
         let _ = writeln!(summary);
@@ -107,17 +313,53 @@ pub fn build_summary(
     summary
 }
 
+/// Run `write` against a staging directory inside `output_dir`, then move every
+/// file it wrote into `output_dir`
+///
+/// Like `XmlModifier::write_to_file`, files only replace the previous outputs
+/// by rename, and only once all of them were written. If `write` fails the
+/// staging directory is removed and `output_dir` is left as it was.
+///
+/// # Errors
+/// Returns error if `write` fails or staged files cannot be moved into place
+pub fn write_staged<F>(output_dir: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    // Same directory keeps the renames on one filesystem; dot-prefixed so a
+    // leftover is skipped by the context loader
+    let staging = output_dir.join(format!(".z-staging-{}", std::process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let result = write(&staging).and_then(|()| {
+        for entry in fs::read_dir(&staging)? {
+            let entry = entry?;
+            fs::rename(entry.path(), output_dir.join(entry.file_name()))?;
+        }
+        Ok(())
+    });
+
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        eprintln!("Warning: Could not remove {}: {e}", staging.display());
+    }
+    result
+}
+
 /// Write `summary.txt` - human/LLM readable overview
 ///
 /// # Errors
 /// Returns error if file cannot be written
 pub fn write_summary(output_dir: &Path, content: &str) -> Result<()> {
-    let path = output_dir.join("summary.txt");
+    let path = resolve_within(output_dir, "summary.txt")?;
     fs::write(path, content)?;
     Ok(())
 }
 
-/// Write `clusters.csv` - cluster assignments for each row
+/// Write `clusters.csv` - cluster assignments for each row, with each row's distance
+/// to its centroid measured in `units`. DBSCAN noise rows leave both empty.
 ///
 /// # Errors
 /// Returns error if file cannot be written
@@ -126,15 +368,16 @@ pub fn write_clusters(
     output_dir: &Path,
     clusters: &ClusterResult,
     features: &NormalizedFeatures,
+    units: DistanceUnits,
+    precision: Option<usize>,
 ) -> Result<()> {
-    use std::fmt::Write as _;
-
-    let path = output_dir.join("clusters.csv");
-    let mut content = String::from("row_id,cluster,distance_to_centroid\n");
+    let p = precision.unwrap_or(4);
+    let path = resolve_within(output_dir, "clusters.csv")?;
 
     // Calculate centroids
-    let mut centroids: Vec<Vec<f64>> = vec![vec![0.0; features.n_features()]; clusters.k];
-    let mut counts = vec![0usize; clusters.k];
+    let groups = clusters.sizes.len();
+    let mut centroids: Vec<Vec<f64>> = vec![vec![0.0; features.n_features()]; groups];
+    let mut counts = vec![0usize; groups];
 
     for (sample_idx, &cluster_id) in clusters.labels.iter().enumerate() {
         counts[cluster_id] += 1;
@@ -152,35 +395,235 @@ pub fn write_clusters(
         }
     }
 
-    // Write rows with distances
+    // Stream rows with distances
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "row_id,cluster,distance_to_centroid")?;
     for (sample_idx, &cluster_id) in clusters.labels.iter().enumerate() {
         let original_row = features.row_indices[sample_idx];
-        let distance = euclidean_distance(&features.data[sample_idx], &centroids[cluster_id]);
-        let _ = writeln!(content, "{original_row},{cluster_id},{distance:.4}");
+        // DBSCAN noise has no cluster, so no centroid to measure against
+        if !clusters.is_cluster(cluster_id) {
+            writeln!(writer, "{original_row},,")?;
+            continue;
+        }
+        let point = &features.data[sample_idx];
+        let centroid = &centroids[cluster_id];
+        let distance = match units {
+            DistanceUnits::Normalized => euclidean_distance(point, centroid),
+            DistanceUnits::Original => euclidean_distance(
+                &features.denormalize(point),
+                &features.denormalize(centroid),
+            ),
+        };
+        writeln!(writer, "{original_row},{cluster_id},{distance:.p$}")?;
     }
 
-    fs::write(path, content)?;
+    writer.flush()?;
     Ok(())
 }
 
-/// Write `anomalies.csv` - detected anomalies
+/// Write `anomalies.csv` - detected anomalies. With `source`, each record is
+/// followed by the columns of the original row it refers to (`row_id` indexes `source.rows()`).
 ///
 /// # Errors
 /// Returns error if file cannot be written
-pub fn write_anomalies(output_dir: &Path, anomalies: &[Anomaly]) -> Result<()> {
-    use std::fmt::Write as _;
-
-    let path = output_dir.join("anomalies.csv");
-    let mut content = String::from("row_id,anomaly_type,score,details\n");
+pub fn write_anomalies(
+    output_dir: &Path,
+    anomalies: &[Anomaly],
+    source: Option<&CsvData>,
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    let path = resolve_within(output_dir, "anomalies.csv")?;
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    write!(writer, "row_id,anomaly_type,score,cluster,details")?;
+    if let Some(csv) = source {
+        for header in &csv.headers {
+            write!(writer, ",{}", csv_field(header))?;
+        }
+    }
+    writeln!(writer)?;
 
     for anomaly in anomalies {
         // Escape details for CSV
         let escaped_details = anomaly.details.replace('"', "\"\"");
-        let _ = writeln!(
-            content,
-            "{},{},{:.4},\"{escaped_details}\"",
+        let cluster = anomaly.cluster.map(|c| c.to_string()).unwrap_or_default();
+        write!(
+            writer,
+            "{},{},{:.p$},{cluster},\"{escaped_details}\"",
             anomaly.row_id, anomaly.anomaly_type, anomaly.score
-        );
+        )?;
+        if let Some(csv) = source {
+            let row = csv.rows().get(anomaly.row_id);
+            for i in 0..csv.headers.len() {
+                let value = row.and_then(|r| r.get(i)).map_or("", String::as_str);
+                write!(writer, ",{}", csv_field(value))?;
+            }
+        }
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `anomalies_structured.csv` - the anomalies of `anomalies.csv` with the
+/// flagged column, value and z-score in their own columns instead of `details`.
+/// Fields that don't apply to a detector (e.g. `column` for DBSCAN noise) are empty.
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_anomalies_structured(
+    output_dir: &Path,
+    anomalies: &[Anomaly],
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    let path = resolve_within(output_dir, "anomalies_structured.csv")?;
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "row_id,type,column,value,z_score,score,cluster")?;
+
+    let number = |v: Option<f64>| v.map(|v| format!("{v:.p$}")).unwrap_or_default();
+    for anomaly in anomalies {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{:.p$},{}",
+            anomaly.row_id,
+            csv_field(&anomaly.anomaly_type),
+            csv_field(anomaly.column.as_deref().unwrap_or_default()),
+            number(anomaly.value),
+            number(anomaly.z_score),
+            anomaly.score,
+            anomaly.cluster.map(|c| c.to_string()).unwrap_or_default()
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `anomaly_consensus.csv` - flagged rows with the detectors that agree on them
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_anomaly_consensus(
+    output_dir: &Path,
+    consensus: &[ConsensusAnomaly],
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    let path = resolve_within(output_dir, "anomaly_consensus.csv")?;
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "row_id,num_detectors,detector_list,combined_score")?;
+
+    for row in consensus {
+        writeln!(
+            writer,
+            "{},{},{},{:.p$}",
+            row.row_id,
+            row.detectors.len(),
+            row.detectors.join(";"),
+            row.combined_score
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `near_duplicates.csv` - pairs of rows close together in normalized feature space
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_near_duplicates(
+    output_dir: &Path,
+    pairs: &[NearDuplicatePair],
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    let path = resolve_within(output_dir, "near_duplicates.csv")?;
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "row_id_a,row_id_b,distance")?;
+
+    for pair in pairs {
+        writeln!(writer, "{},{},{:.p$}", pair.row_a, pair.row_b, pair.distance)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `pca_projection.csv` - each row's coordinates on the principal components,
+/// optionally with a label column (`labels` holds the column name and one value per sample)
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_pca_projection(
+    output_dir: &Path,
+    pca: &PcaResult,
+    features: &NormalizedFeatures,
+    labels: Option<(&str, &[String])>,
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    let path = resolve_within(output_dir, "pca_projection.csv")?;
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+
+    write!(writer, "row_id")?;
+    if let Some((name, _)) = labels {
+        write!(writer, ",{}", csv_field(name))?;
+    }
+    // The fit may keep fewer components than requested, so size the header from the data
+    let width = pca.projection.first().map_or(pca.n_components, Vec::len);
+    for i in 1..=width {
+        write!(writer, ",pc{i}")?;
+    }
+    writeln!(writer)?;
+
+    for (sample_idx, coords) in pca.projection.iter().enumerate() {
+        write!(writer, "{}", features.row_indices[sample_idx])?;
+        if let Some((_, values)) = labels {
+            let value = values.get(sample_idx).map_or("", String::as_str);
+            write!(writer, ",{}", csv_field(value))?;
+        }
+        for c in coords {
+            write!(writer, ",{c:.p$}")?;
+        }
+        writeln!(writer)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a delimiter, quote, or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Write `dbscan.csv` - DBSCAN cluster and core/border/noise role for each row
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_dbscan(
+    output_dir: &Path,
+    dbscan: &DbscanResult,
+    features: &NormalizedFeatures,
+) -> Result<()> {
+    use std::fmt::Write as _;
+
+    let path = resolve_within(output_dir, "dbscan.csv")?;
+    let mut content = String::from("row_id,cluster,point_type\n");
+
+    for (sample_idx, (label, point_type)) in
+        dbscan.labels.iter().zip(&dbscan.point_types).enumerate()
+    {
+        let original_row = features.row_indices[sample_idx];
+        let cluster = label.map(|c| c.to_string()).unwrap_or_default();
+        let _ = writeln!(content, "{original_row},{cluster},{}", point_type.as_str());
     }
 
     fs::write(path, content)?;
@@ -191,24 +634,196 @@ pub fn write_anomalies(output_dir: &Path, anomalies: &[Anomaly]) -> Result<()> {
 ///
 /// # Errors
 /// Returns error if file cannot be written
-pub fn write_correlation(output_dir: &Path, corr: &CorrelationMatrix) -> Result<()> {
+pub fn write_correlation(
+    output_dir: &Path,
+    corr: &CorrelationMatrix,
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    write_matrix(&resolve_within(output_dir, "correlation.csv")?, &corr.names, &corr.matrix, p)
+}
+
+/// Write `covariance.csv` - `NxN` sample covariance matrix
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_covariance(
+    output_dir: &Path,
+    cov: &CovarianceMatrix,
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(6);
+    write_matrix(&resolve_within(output_dir, "covariance.csv")?, &cov.names, &cov.matrix, p)
+}
+
+/// Write `cluster_descriptions.txt` - one plain-language line per cluster
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_cluster_descriptions(
+    output_dir: &Path,
+    descriptors: &[ClusterDescriptor],
+) -> Result<()> {
+    use std::fmt::Write as _;
+
+    let mut content = String::from(
+        "# Features whose cluster mean differs most from the overall mean \
+         (normalized 0-1 scale)\n",
+    );
+    for descriptor in descriptors {
+        let _ = writeln!(content, "{}", descriptor.describe());
+    }
+
+    fs::write(resolve_within(output_dir, "cluster_descriptions.txt")?, content)?;
+    Ok(())
+}
+
+/// Write `clusters.geojson` - a `FeatureCollection` with one Point per source row that
+/// has both coordinates, carrying its `row_id` and `cluster` (`null` if the row wasn't
+/// clustered or is DBSCAN noise). `row_indices` are the source rows of `clusters.labels`.
+///
+/// # Errors
+/// Returns error if a coordinate column is missing or the file cannot be written
+pub fn write_clusters_geojson(
+    output_dir: &Path,
+    csv: &CsvData,
+    (lat_column, lon_column): (&str, &str),
+    clusters: &ClusterResult,
+    row_indices: &[usize],
+) -> Result<()> {
+    let index = |name: &str| {
+        csv.headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| ZError::Config(format!("Coordinate column '{name}' not found")))
+    };
+    let parsed = csv.numeric_columns();
+    let (lats, lons) = (&parsed[index(lat_column)?], &parsed[index(lon_column)?]);
+
+    let mut cluster_of = vec![None; csv.row_count()];
+    for (&row, &label) in row_indices.iter().zip(&clusters.labels) {
+        cluster_of[row] = clusters.is_cluster(label).then_some(label);
+    }
+
+    let features: Vec<serde_json::Value> = (0..csv.row_count())
+        .filter_map(|row| {
+            let (lat, lon) = (lats[row]?, lons[row]?);
+            Some(serde_json::json!({
+                "type": "Feature",
+                // GeoJSON orders positions longitude first
+                "geometry": {"type": "Point", "coordinates": [lon, lat]},
+                "properties": {"row_id": row, "cluster": cluster_of[row]},
+            }))
+        })
+        .collect();
+    let collection = serde_json::json!({"type": "FeatureCollection", "features": features});
+
+    fs::write(
+        resolve_within(output_dir, "clusters.geojson")?,
+        serde_json::to_string(&collection)?,
+    )?;
+    Ok(())
+}
+
+/// Write `coercion_report.txt` - numeric columns with cells that aren't numbers,
+/// whose rows were left out of the analysis
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_coercion_report(output_dir: &Path, coercions: &[ColumnCoercion]) -> Result<()> {
+    use std::fmt::Write as _;
+
+    let mut content = String::from(
+        "# Non-numeric values in numeric columns. Rows holding one are left out of\n\
+         # the analysis; fix or blank them to keep those rows.\n",
+    );
+    for c in coercions {
+        let quoted: Vec<String> = c.samples.iter().map(|s| format!("{s:?}")).collect();
+        let rows: Vec<String> = c.first_rows.iter().map(ToString::to_string).collect();
+        let more = if c.count > c.first_rows.len() { ", ..." } else { "" };
+        let _ = writeln!(
+            content,
+            "{}: {} value(s), e.g. {}; rows {}{more}",
+            c.column,
+            c.count,
+            quoted.join(", "),
+            rows.join(", ")
+        );
+    }
+
+    fs::write(resolve_within(output_dir, "coercion_report.txt")?, content)?;
+    Ok(())
+}
+
+/// Write `cluster_vs_truth.csv` - rows per cluster (one line each) and truth class
+/// (one column each). The DBSCAN noise group is the `noise` line.
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_cluster_vs_truth(
+    output_dir: &Path,
+    truth: &TruthComparison,
+    clusters: &ClusterResult,
+) -> Result<()> {
+    use std::fmt::Write as _;
+
+    let mut content = String::from("cluster");
+    for class in &truth.classes {
+        let _ = write!(content, ",{}", csv_field(class));
+    }
+    content.push('\n');
+    for (cluster, counts) in truth.contingency.iter().enumerate() {
+        if clusters.is_cluster(cluster) {
+            let _ = write!(content, "{cluster}");
+        } else {
+            content.push_str("noise");
+        }
+        for count in counts {
+            let _ = write!(content, ",{count}");
+        }
+        content.push('\n');
+    }
+
+    fs::write(resolve_within(output_dir, "cluster_vs_truth.csv")?, content)?;
+    Ok(())
+}
+
+/// Write `mi_matrix.csv` - `NxN` mutual information matrix (nats)
+///
+/// # Errors
+/// Returns error if file cannot be written
+pub fn write_mutual_info(
+    output_dir: &Path,
+    mi: &MutualInfoMatrix,
+    precision: Option<usize>,
+) -> Result<()> {
+    let p = precision.unwrap_or(4);
+    write_matrix(&resolve_within(output_dir, "mi_matrix.csv")?, &mi.names, &mi.matrix, p)
+}
+
+/// Write a square feature-by-feature matrix with a `feature` header column
+fn write_matrix(
+    path: &Path,
+    names: &[String],
+    matrix: &[Vec<f64>],
+    precision: usize,
+) -> Result<()> {
     use std::fmt::Write as _;
 
-    let path = output_dir.join("correlation.csv");
     let mut content = String::new();
 
     // Header
     content.push_str("feature");
-    for name in &corr.names {
+    for name in names {
         let _ = write!(content, ",{name}");
     }
     content.push('\n');
 
     // Rows
-    for (i, name) in corr.names.iter().enumerate() {
+    for (name, row) in names.iter().zip(matrix) {
         content.push_str(name);
-        for j in 0..corr.names.len() {
-            let _ = write!(content, ",{:.4}", corr.matrix[i][j]);
+        for value in row {
+            let _ = write!(content, ",{value:.precision$}");
         }
         content.push('\n');
     }
@@ -230,9 +845,10 @@ pub fn write_stats_json(
     anomalies: &[Anomaly],
     dbscan: Option<&DbscanResult>,
     correlations: Option<&CorrelationMatrix>,
+    covariance: Option<&CovarianceMatrix>,
     pca: Option<&PcaResult>,
 ) -> Result<()> {
-    let path = output_dir.join("stats.json");
+    let path = resolve_within(output_dir, "stats.json")?;
 
     let stats_json: Vec<_> = stats
         .iter()
@@ -247,6 +863,7 @@ pub fn write_stats_json(
             median: s.median,
             q3: s.q3,
             iqr: s.iqr,
+            mean_ci_95: s.mean_ci.map(<[f64; 2]>::from),
         })
         .collect();
 
@@ -254,6 +871,7 @@ pub fn write_stats_json(
         .sizes
         .iter()
         .enumerate()
+        .filter(|&(i, _)| clusters.is_cluster(i))
         .map(|(i, &size)| ClusterEntry {
             id: i,
             size,
@@ -263,6 +881,7 @@ pub fn write_stats_json(
 
     let dbscan_json = dbscan.map(|d| DbscanEntry {
         epsilon: d.epsilon,
+        epsilon_estimated: d.epsilon_estimated,
         min_points: d.min_points,
         n_clusters: d.n_clusters,
         n_noise: d.n_noise,
@@ -274,6 +893,11 @@ pub fn write_stats_json(
         matrix: c.matrix.clone(),
     });
 
+    let covariance_json = covariance.map(|c| CorrelationEntry {
+        names: c.names.clone(),
+        matrix: c.matrix.clone(),
+    });
+
     let pca_json = pca.map(|p| PcaEntry {
         n_components: p.n_components,
         explained_variance_ratio: p.explained_variance_ratio.clone(),
@@ -292,6 +916,15 @@ pub fn write_stats_json(
         row_count: csv_data.row_count(),
         column_count: csv_data.col_count(),
         columns: csv_data.headers.clone(),
+        column_types: csv_data
+            .headers
+            .iter()
+            .zip(csv_data.column_types())
+            .map(|(name, inferred_type)| ColumnTypeEntry {
+                name: name.clone(),
+                inferred_type,
+            })
+            .collect(),
         statistics: stats_json,
         clustering: ClusteringSummary {
             k: clusters.k,
@@ -303,6 +936,7 @@ pub fn write_stats_json(
         },
         dbscan: dbscan_json,
         correlations: correlations_json,
+        covariance: covariance_json,
         pca: pca_json,
     };
 
@@ -311,6 +945,16 @@ pub fn write_stats_json(
     Ok(())
 }
 
+/// Write the complete `AnalysisResult` as pretty-printed JSON to `path`
+///
+/// # Errors
+/// Returns error if the file cannot be written
+pub fn write_result_json(path: &Path, result: &AnalysisResult) -> Result<()> {
+    let json = serde_json::to_string_pretty(result)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
 /// Calculate Euclidean distance between two points
 fn euclidean_distance(a: &[f64], b: &[f64]) -> f64 {
     a.iter()
@@ -322,9 +966,10 @@ fn euclidean_distance(a: &[f64], b: &[f64]) -> f64 {
 
 /// Count anomalies by type
 fn count_by_type(anomalies: &[Anomaly]) -> Vec<AnomalyTypeCount> {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
 
-    let mut counts: HashMap<&str, usize> = HashMap::new();
+    // Ordered so stats.json is identical across runs
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
     for a in anomalies {
         *counts.entry(&a.anomaly_type).or_insert(0) += 1;
     }
@@ -338,88 +983,102 @@ fn count_by_type(anomalies: &[Anomaly]) -> Vec<AnomalyTypeCount> {
         .collect()
 }
 
-// JSON output structures (private)
+// JSON output structures (read back by `drift`)
 
-#[derive(Serialize)]
-struct StatsOutput {
-    row_count: usize,
+#[derive(Serialize, Deserialize)]
+pub(super) struct StatsOutput {
+    pub(super) row_count: usize,
     column_count: usize,
     columns: Vec<String>,
-    statistics: Vec<StatsEntry>,
-    clustering: ClusteringSummary,
-    anomalies_summary: AnomaliesSummary,
+    #[serde(default)]
+    column_types: Vec<ColumnTypeEntry>,
+    pub(super) statistics: Vec<StatsEntry>,
+    pub(super) clustering: ClusteringSummary,
+    pub(super) anomalies_summary: AnomaliesSummary,
     #[serde(skip_serializing_if = "Option::is_none")]
-    dbscan: Option<DbscanEntry>,
+    pub(super) dbscan: Option<DbscanEntry>,
     #[serde(skip_serializing_if = "Option::is_none")]
     correlations: Option<CorrelationEntry>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    covariance: Option<CorrelationEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pca: Option<PcaEntry>,
 }
 
-#[derive(Serialize)]
-struct StatsEntry {
+#[derive(Serialize, Deserialize)]
+pub(super) struct ColumnTypeEntry {
     name: String,
+    inferred_type: InferredType,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct StatsEntry {
+    pub(super) name: String,
     count: usize,
-    mean: f64,
-    std_dev: f64,
+    pub(super) mean: f64,
+    pub(super) std_dev: f64,
     min: f64,
     max: f64,
-    q1: f64,
-    median: f64,
-    q3: f64,
+    pub(super) q1: f64,
+    pub(super) median: f64,
+    pub(super) q3: f64,
     iqr: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mean_ci_95: Option<[f64; 2]>,
 }
 
-#[derive(Serialize)]
-struct ClusteringSummary {
-    k: usize,
+#[derive(Serialize, Deserialize)]
+pub(super) struct ClusteringSummary {
+    pub(super) k: usize,
     clusters: Vec<ClusterEntry>,
 }
 
-#[derive(Serialize)]
-struct ClusterEntry {
+#[derive(Serialize, Deserialize)]
+pub(super) struct ClusterEntry {
     id: usize,
     size: usize,
     percentage: f64,
 }
 
-#[derive(Serialize)]
-struct AnomaliesSummary {
-    total: usize,
+#[derive(Serialize, Deserialize)]
+pub(super) struct AnomaliesSummary {
+    pub(super) total: usize,
     by_type: Vec<AnomalyTypeCount>,
 }
 
-#[derive(Serialize)]
-struct AnomalyTypeCount {
+#[derive(Serialize, Deserialize)]
+pub(super) struct AnomalyTypeCount {
     anomaly_type: String,
     count: usize,
 }
 
-#[derive(Serialize)]
-struct DbscanEntry {
+#[derive(Serialize, Deserialize)]
+pub(super) struct DbscanEntry {
     epsilon: f64,
+    #[serde(default)]
+    epsilon_estimated: bool,
     min_points: usize,
-    n_clusters: usize,
+    pub(super) n_clusters: usize,
     n_noise: usize,
     sizes: Vec<usize>,
 }
 
-#[derive(Serialize)]
-struct CorrelationEntry {
+#[derive(Serialize, Deserialize)]
+pub(super) struct CorrelationEntry {
     names: Vec<String>,
     matrix: Vec<Vec<f64>>,
 }
 
-#[derive(Serialize)]
-struct PcaEntry {
+#[derive(Serialize, Deserialize)]
+pub(super) struct PcaEntry {
     n_components: usize,
     explained_variance_ratio: Vec<f64>,
     cumulative_variance: Vec<f64>,
     feature_importance: Vec<FeatureImportance>,
 }
 
-#[derive(Serialize)]
-struct FeatureImportance {
+#[derive(Serialize, Deserialize)]
+pub(super) struct FeatureImportance {
     name: String,
     importance: f64,
 }
@@ -427,6 +1086,7 @@ struct FeatureImportance {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::structs::{ClusterMethod, FeatureDeviation};
     use tempfile::TempDir;
 
     #[test]
@@ -445,23 +1105,347 @@ mod tests {
             Anomaly {
                 row_id: 1,
                 anomaly_type: "price_outlier".to_string(),
+                detector: "iqr",
                 score: 0.95,
                 details: "price=999 is 4.2 std above mean".to_string(),
+                column: Some("price".to_string()),
+                value: Some(999.0),
+                z_score: Some(4.2),
+                cluster: Some(2),
             },
             Anomaly {
                 row_id: 5,
                 anomaly_type: "rating_outlier".to_string(),
+                detector: "iqr",
                 score: 0.87,
                 details: "rating=1.0 with price=150+".to_string(),
+                column: None,
+                value: None,
+                z_score: None,
+                cluster: None,
             },
         ];
 
-        write_anomalies(dir.path(), &anomalies).expect("write anomalies");
+        write_anomalies(dir.path(), &anomalies, None, None).expect("write anomalies");
+
+        let content = fs::read_to_string(dir.path().join("anomalies.csv")).expect("read");
+        assert!(content.contains("row_id,anomaly_type,score,cluster,details"));
+        assert!(content.contains("1,price_outlier,0.9500,2,"));
+        assert!(content.contains("5,rating_outlier,0.8700,,"));
+
+        let source = CsvData::new(
+            vec!["name".to_string(), "price".to_string()],
+            (0..6).map(|i| vec![format!("item {i}, ltd"), format!("{i}")]).collect(),
+            b',',
+        );
+        write_anomalies(dir.path(), &anomalies, Some(&source), None).expect("write anomalies");
 
         let content = fs::read_to_string(dir.path().join("anomalies.csv")).expect("read");
-        assert!(content.contains("row_id,anomaly_type,score,details"));
-        assert!(content.contains("1,price_outlier,0.9500"));
-        assert!(content.contains("5,rating_outlier,0.8700"));
+        assert!(content.starts_with("row_id,anomaly_type,score,cluster,details,name,price\n"));
+        assert!(content.contains("std above mean\",\"item 1, ltd\",1\n"));
+        assert!(content.contains("price=150+\",\"item 5, ltd\",5\n"));
+
+        write_anomalies_structured(dir.path(), &anomalies, Some(2)).expect("write structured");
+        let content =
+            fs::read_to_string(dir.path().join("anomalies_structured.csv")).expect("read");
+        assert_eq!(
+            content,
+            "row_id,type,column,value,z_score,score,cluster\n\
+             1,price_outlier,price,999.00,4.20,0.95,2\n\
+             5,rating_outlier,,,,0.87,\n"
+        );
+    }
+
+    #[test]
+    fn test_write_anomaly_consensus() {
+        let dir = TempDir::new().expect("create temp dir");
+        let consensus = vec![
+            ConsensusAnomaly {
+                row_id: 7,
+                detectors: vec!["dbscan", "iqr"],
+                combined_score: 0.85,
+            },
+            ConsensusAnomaly {
+                row_id: 2,
+                detectors: vec!["iqr"],
+                combined_score: 0.9,
+            },
+        ];
+
+        write_anomaly_consensus(dir.path(), &consensus, None).expect("write consensus");
+
+        let content = fs::read_to_string(dir.path().join("anomaly_consensus.csv")).expect("read");
+        assert_eq!(
+            content,
+            "row_id,num_detectors,detector_list,combined_score\n\
+             7,2,dbscan;iqr,0.8500\n\
+             2,1,iqr,0.9000\n"
+        );
+
+        write_anomaly_consensus(dir.path(), &consensus, Some(1)).expect("write consensus");
+        let content = fs::read_to_string(dir.path().join("anomaly_consensus.csv")).expect("read");
+        assert!(content.ends_with("7,2,dbscan;iqr,0.8\n2,1,iqr,0.9\n"));
+    }
+
+    #[test]
+    fn test_write_clusters_distance_units() {
+        let dir = TempDir::new().expect("create temp dir");
+        let clusters = ClusterResult {
+            method: ClusterMethod::Kmeans,
+            labels: vec![0, 0],
+            k: 1,
+            sizes: vec![2],
+            noise_cluster: None,
+        };
+        let features = NormalizedFeatures {
+            names: vec!["x".into()],
+            data: vec![vec![0.0], vec![0.2]],
+            row_indices: vec![4, 7],
+            mins: vec![0.0],
+            maxs: vec![10.0],
+        };
+        let written = |units| {
+            write_clusters(dir.path(), &clusters, &features, units, Some(2)).expect("write");
+            fs::read_to_string(dir.path().join("clusters.csv")).expect("read")
+        };
+
+        assert!(written(DistanceUnits::Normalized).ends_with("4,0,0.10\n7,0,0.10\n"));
+        assert!(written(DistanceUnits::Original).ends_with("4,0,1.00\n7,0,1.00\n"));
+
+        let clusters = ClusterResult {
+            method: ClusterMethod::Dbscan,
+            labels: vec![0, 1],
+            k: 1,
+            sizes: vec![1, 1],
+            noise_cluster: Some(1),
+        };
+        write_clusters(dir.path(), &clusters, &features, DistanceUnits::Normalized, Some(2))
+            .expect("write");
+        let content = fs::read_to_string(dir.path().join("clusters.csv")).expect("read");
+        assert!(content.ends_with("4,0,0.00\n7,,\n"));
+    }
+
+    #[test]
+    fn test_write_cluster_vs_truth() {
+        let dir = TempDir::new().expect("create temp dir");
+        let clusters = ClusterResult {
+            method: ClusterMethod::Dbscan,
+            labels: vec![0, 0, 1],
+            k: 1,
+            sizes: vec![2, 1],
+            noise_cluster: Some(1),
+        };
+        let truth = TruthComparison {
+            column: "kind".into(),
+            adjusted_rand_index: 1.0,
+            normalized_mutual_info: 1.0,
+            classes: vec!["a, b".into(), "c".into()],
+            contingency: vec![vec![2, 0], vec![0, 1]],
+        };
+
+        write_cluster_vs_truth(dir.path(), &truth, &clusters).expect("write");
+        let content = fs::read_to_string(dir.path().join("cluster_vs_truth.csv")).expect("read");
+        assert_eq!(content, "cluster,\"a, b\",c\n0,2,0\nnoise,0,1\n");
+    }
+
+    #[test]
+    fn test_write_near_duplicates() {
+        let dir = TempDir::new().expect("create temp dir");
+        let pairs = vec![NearDuplicatePair {
+            row_a: 3,
+            row_b: 9,
+            distance: 0.012_34,
+        }];
+
+        write_near_duplicates(dir.path(), &pairs, None).expect("write near duplicates");
+
+        let content = fs::read_to_string(dir.path().join("near_duplicates.csv")).expect("read");
+        assert_eq!(content, "row_id_a,row_id_b,distance\n3,9,0.0123\n");
+    }
+
+    #[test]
+    fn test_write_anomalies_large() {
+        let dir = TempDir::new().expect("create temp dir");
+        let anomalies: Vec<Anomaly> = (0..200_000)
+            .map(|i| Anomaly {
+                row_id: i,
+                anomaly_type: "dbscan_noise".to_string(),
+                detector: "dbscan",
+                score: 0.5,
+                details: format!("row \"{i}\", isolated"),
+                column: None,
+                value: None,
+                z_score: None,
+                cluster: None,
+            })
+            .collect();
+
+        write_anomalies(dir.path(), &anomalies, None, None).expect("write anomalies");
+
+        let mut reader =
+            csv::Reader::from_path(dir.path().join("anomalies.csv")).expect("open anomalies");
+        let mut count = 0;
+        for (i, record) in reader.records().enumerate() {
+            let record = record.expect("parse row");
+            assert_eq!(record[0].parse::<usize>().expect("row id"), i);
+            assert_eq!(&record[1], "dbscan_noise");
+            assert_eq!(&record[2], "0.5000");
+            assert_eq!(&record[3], "");
+            assert_eq!(record[4], format!("row \"{i}\", isolated"));
+            count += 1;
+        }
+        assert_eq!(count, anomalies.len());
+    }
+
+    #[test]
+    fn test_write_dbscan() {
+        let dir = TempDir::new().expect("create temp dir");
+        let dbscan = DbscanResult {
+            labels: vec![Some(0), Some(0), None],
+            point_types: vec![
+                DbscanPointType::Core,
+                DbscanPointType::Border,
+                DbscanPointType::Noise,
+            ],
+            n_clusters: 1,
+            n_noise: 1,
+            sizes: vec![2],
+            epsilon: 0.5,
+            epsilon_estimated: false,
+            min_points: 2,
+        };
+        let features = NormalizedFeatures {
+            names: vec!["x".to_string()],
+            data: vec![vec![0.0], vec![0.1], vec![1.0]],
+            row_indices: vec![0, 2, 5],
+            mins: vec![0.0],
+            maxs: vec![1.0],
+        };
+
+        write_dbscan(dir.path(), &dbscan, &features).expect("write dbscan");
+
+        let content = fs::read_to_string(dir.path().join("dbscan.csv")).expect("read");
+        assert!(content.contains("row_id,cluster,point_type"));
+        assert!(content.contains("0,0,core"));
+        assert!(content.contains("2,0,border"));
+        assert!(content.contains("5,,noise"));
+    }
+
+    #[test]
+    fn test_write_pca_projection() {
+        let dir = TempDir::new().expect("create temp dir");
+        let pca = PcaResult {
+            n_components: 2,
+            explained_variance_ratio: vec![0.9, 0.1],
+            cumulative_variance: vec![0.9, 1.0],
+            feature_importance: Vec::new(),
+            projection: vec![vec![0.5, -0.25], vec![-0.5, 0.25]],
+        };
+        let features = NormalizedFeatures {
+            names: vec!["x".to_string(), "y".to_string()],
+            data: vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+            row_indices: vec![0, 3],
+            mins: vec![0.0, 0.0],
+            maxs: vec![1.0, 1.0],
+        };
+
+        write_pca_projection(dir.path(), &pca, &features, None, None).expect("write projection");
+        let content = fs::read_to_string(dir.path().join("pca_projection.csv")).expect("read");
+        assert_eq!(content, "row_id,pc1,pc2\n0,0.5000,-0.2500\n3,-0.5000,0.2500\n");
+
+        let labels = vec!["alpha".to_string(), "beta, inc".to_string()];
+        write_pca_projection(dir.path(), &pca, &features, Some(("name", &labels)), None)
+            .expect("write projection");
+        let content = fs::read_to_string(dir.path().join("pca_projection.csv")).expect("read");
+        assert!(content.starts_with("row_id,name,pc1,pc2\n0,alpha,0.5000"));
+        assert!(content.contains("3,\"beta, inc\",-0.5000"));
+    }
+
+    #[test]
+    fn test_write_clusters_geojson() {
+        let dir = TempDir::new().expect("create temp dir");
+        let csv_path = dir.path().join("in.csv");
+        fs::write(&csv_path, "lat,lon\n52.5,13.4\n,2.0\n-33.9,151.2\n48.8,2.3\n")
+            .expect("write csv");
+        let csv = CsvData::from_file(&csv_path, false).expect("parse");
+        let clusters = ClusterResult {
+            method: ClusterMethod::Dbscan,
+            labels: vec![0, 1],
+            k: 1,
+            sizes: vec![1, 1],
+            noise_cluster: Some(1),
+        };
+
+        write_clusters_geojson(dir.path(), &csv, ("lat", "lon"), &clusters, &[0, 2])
+            .expect("write geojson");
+        let json: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(dir.path().join("clusters.geojson")).expect("read"),
+        )
+        .expect("parse");
+        let features = json["features"].as_array().expect("features");
+        assert_eq!(features.len(), 3);
+        assert_eq!(
+            features[0]["geometry"]["coordinates"],
+            serde_json::json!([13.4, 52.5])
+        );
+        assert_eq!(features[0]["properties"]["cluster"], 0);
+        assert_eq!(features[1]["properties"]["row_id"], 2);
+        assert!(features[1]["properties"]["cluster"].is_null());
+        assert!(features[2]["properties"]["cluster"].is_null());
+    }
+
+    #[test]
+    fn test_write_coercion_report() {
+        let dir = TempDir::new().expect("create temp dir");
+        let coercions = vec![ColumnCoercion {
+            column: "price".to_string(),
+            count: 7,
+            samples: vec!["n/a".to_string(), "12,5".to_string()],
+            first_rows: vec![3, 8, 9, 10, 14],
+        }];
+
+        write_coercion_report(dir.path(), &coercions).expect("write report");
+        let content = fs::read_to_string(dir.path().join("coercion_report.txt")).expect("read");
+        assert!(content.starts_with("# Non-numeric values in numeric columns"));
+        assert!(content
+            .contains("price: 7 value(s), e.g. \"n/a\", \"12,5\"; rows 3, 8, 9, 10, 14, ...\n"));
+    }
+
+    #[test]
+    fn test_write_cluster_descriptions() {
+        let dir = TempDir::new().expect("create temp dir");
+        let descriptors = vec![
+            ClusterDescriptor {
+                cluster: 0,
+                size: 12,
+                top_features: vec![
+                    FeatureDeviation {
+                        name: "price".to_string(),
+                        deviation: 0.314,
+                    },
+                    FeatureDeviation {
+                        name: "rating".to_string(),
+                        deviation: -0.2,
+                    },
+                ],
+            },
+            ClusterDescriptor {
+                cluster: 1,
+                size: 3,
+                top_features: Vec::new(),
+            },
+        ];
+
+        write_cluster_descriptions(dir.path(), &descriptors).expect("write descriptions");
+
+        let content =
+            fs::read_to_string(dir.path().join("cluster_descriptions.txt")).expect("read");
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[1], "Cluster 0 (12 rows): high price (+0.31), low rating (-0.20)");
+        assert_eq!(
+            lines[2],
+            "Cluster 1 (3 rows): close to the overall mean on every feature"
+        );
     }
 
     #[test]
@@ -472,13 +1456,128 @@ mod tests {
             matrix: vec![vec![1.0, 0.95], vec![0.95, 1.0]],
         };
 
-        write_correlation(dir.path(), &corr).expect("write correlation");
+        write_correlation(dir.path(), &corr, None).expect("write correlation");
 
         let content = fs::read_to_string(dir.path().join("correlation.csv")).expect("read");
         assert!(content.contains("feature,a,b"));
         assert!(content.contains("a,1.0000,0.9500"));
     }
 
+    #[test]
+    fn test_write_covariance() {
+        let dir = TempDir::new().expect("create temp dir");
+        let cov = CovarianceMatrix {
+            names: vec!["a".to_string(), "b".to_string()],
+            matrix: vec![vec![2.5, -1.25], vec![-1.25, 12.0]],
+        };
+
+        write_covariance(dir.path(), &cov, None).expect("write covariance");
+
+        let content = fs::read_to_string(dir.path().join("covariance.csv")).expect("read");
+        assert_eq!(content, "feature,a,b\na,2.500000,-1.250000\nb,-1.250000,12.000000\n");
+    }
+
+    #[test]
+    fn test_write_result_json() {
+        let dir = TempDir::new().expect("create temp dir");
+        let path = dir.path().join("result.json");
+        let result = AnalysisResult {
+            column_stats: Vec::new(),
+            cluster_result: ClusterResult {
+                method: ClusterMethod::Kmeans,
+                labels: vec![0, 1, 0],
+                k: 2,
+                sizes: vec![2, 1],
+                noise_cluster: None,
+            },
+            cluster_stability: None,
+            cluster_descriptors: None,
+            dbscan_result: Some(DbscanResult {
+                labels: vec![Some(0), Some(0), None],
+                point_types: vec![
+                    DbscanPointType::Core,
+                    DbscanPointType::Border,
+                    DbscanPointType::Noise,
+                ],
+                n_clusters: 1,
+                n_noise: 1,
+                sizes: vec![2],
+                epsilon: 0.25,
+                epsilon_estimated: true,
+                min_points: 2,
+            }),
+            anomalies: Vec::new(),
+            consensus: Vec::new(),
+            suppressed_anomalies: 0,
+            correlation: Some(CorrelationMatrix {
+                names: vec!["a".to_string(), "b".to_string()],
+                matrix: vec![vec![1.0, f64::NAN], vec![f64::NAN, 1.0]],
+            }),
+            covariance: None,
+            mutual_info: None,
+            pca: None,
+            near_duplicates: None,
+            feature_weights: Vec::new(),
+            boolean_features: vec!["active".to_string()],
+            truth: None,
+            id_column: Some("sku".to_string()),
+            coercions: Vec::new(),
+            lat_lon_columns: None,
+        };
+
+        write_result_json(&path, &result).expect("write result");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).expect("read")).expect("parse");
+        assert_eq!(json["cluster_result"]["sizes"], serde_json::json!([2, 1]));
+        assert_eq!(json["dbscan_result"]["labels"], serde_json::json!([0, 0, null]));
+        assert_eq!(json["dbscan_result"]["point_types"][2], "noise");
+        assert!(json["correlation"]["matrix"][0][1].is_null());
+        assert!(json["pca"].is_null());
+        assert_eq!(json["id_column"], "sku");
+    }
+
+    #[test]
+    fn test_write_staged() {
+        let dir = TempDir::new().expect("create temp dir");
+        fs::write(dir.path().join("summary.txt"), "old summary").expect("write");
+        fs::write(dir.path().join("stats.json"), "old stats").expect("write");
+
+        // A failure partway leaves the previous outputs untouched
+        let result = write_staged(dir.path(), |staging| {
+            write_summary(staging, "new summary")?;
+            Err(crate::structs::ZError::Io(std::io::Error::other("disk full")))
+        });
+        assert!(result.is_err());
+        let summary = fs::read_to_string(dir.path().join("summary.txt")).expect("read");
+        assert_eq!(summary, "old summary");
+        let entries = fs::read_dir(dir.path()).expect("read dir").count();
+        assert_eq!(entries, 2, "staging directory should be cleaned up");
+
+        write_staged(dir.path(), |staging| write_summary(staging, "new summary"))
+            .expect("write staged");
+        let summary = fs::read_to_string(dir.path().join("summary.txt")).expect("read");
+        assert_eq!(summary, "new summary");
+        let stats = fs::read_to_string(dir.path().join("stats.json")).expect("read");
+        assert_eq!(stats, "old stats");
+        assert_eq!(fs::read_dir(dir.path()).expect("read dir").count(), 2);
+    }
+
+    #[test]
+    fn test_existing_and_remove_outputs() {
+        let dir = TempDir::new().expect("create temp dir");
+        assert!(existing_outputs(dir.path()).is_empty());
+
+        fs::write(dir.path().join("summary.txt"), "old").expect("write");
+        fs::write(dir.path().join("covariance.csv"), "old").expect("write");
+        fs::write(dir.path().join("notes.md"), "mine").expect("write");
+        assert_eq!(existing_outputs(dir.path()), vec!["summary.txt", "covariance.csv"]);
+
+        remove_outputs(dir.path()).expect("remove outputs");
+        assert!(existing_outputs(dir.path()).is_empty());
+        assert!(dir.path().join("notes.md").exists());
+    }
+
     #[test]
     fn test_euclidean_distance() {
         let a = vec![0.0, 0.0];
@@ -1,49 +1,253 @@
 //! Analysis pipeline that orchestrates all ML computations
 
+use crate::color;
 use crate::structs::{
-    AnalysisResult, Anomaly, ColumnStats, FeatureMatrix, NormalizedFeatures, Result,
+    AnalysisResult, Anomaly, ClusterMethod, ClusterResult, ColumnStats, ConsensusAnomaly,
+    CorrelationMatrix, CorrelationMethod, CovarianceMatrix, DbscanResult, FeatureMatrix,
+    MutualInfoMatrix, NormalizedFeatures, Result, ZError,
 };
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+/// Number of resamples used for bootstrap confidence intervals
+const BOOTSTRAP_SAMPLES: usize = 1000;
+
+/// Number of K-means runs compared for the cluster stability score
+const STABILITY_RUNS: usize = 10;
+
+/// Smallest cluster whose quartiles are used for cluster-relative anomalies
+const MIN_CLUSTER_STATS_SIZE: usize = 4;
 
 /// Configuration for the analysis pipeline
+#[derive(Serialize)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct AnalysisConfig {
+    /// Algorithm for the primary clustering
+    pub cluster_method: ClusterMethod,
+    /// Number of K-means clusters or GMM components (0 = suggest one); unused by DBSCAN
     pub clusters: usize,
     pub dbscan_eps: f64,
     pub dbscan_min_points: usize,
     pub pca_components: usize,
+    pub correlation_method: CorrelationMethod,
+    /// Also compute the sample covariance matrix
+    pub covariance: bool,
+    /// Also compute the pairwise mutual information matrix
+    pub mutual_info: bool,
+    pub bootstrap: bool,
+    /// Score cluster stability by comparing several K-means runs (multiplies clustering time)
+    pub stability: bool,
+    /// Describe each cluster by its most distinctive features
+    pub describe_clusters: bool,
+    /// Flag IQR outliers against each row's own cluster instead of the whole dataset
+    pub cluster_relative_anomalies: bool,
+    /// Anomaly types to drop (exact match on `anomaly_type`)
+    pub exclude_anomaly_types: Vec<String>,
+    /// If non-empty, keep only these anomaly types
+    pub only_anomaly_types: Vec<String>,
+    /// Seed for the master RNG every randomized step derives its seed from.
+    /// `None` seeds from entropy, so repeated runs may differ.
+    pub seed: Option<u64>,
 }
 
 /// Run the full analysis pipeline
 ///
 /// # Errors
 /// Returns error if feature extraction or clustering fails.
-/// DBSCAN, PCA, and correlation failures are non-fatal (logged and set to `None`).
+/// DBSCAN, PCA, and correlation failures are non-fatal (logged and set to `None`),
+/// unless DBSCAN is the primary clustering method.
 #[allow(clippy::cast_precision_loss)]
 pub fn run_pipeline(
     features: &FeatureMatrix,
     normalized: &NormalizedFeatures,
     config: &AnalysisConfig,
 ) -> Result<AnalysisResult> {
+    let (kmeans_seed, bootstrap_seed, stability_seed) = component_seeds(config);
+
     // Column statistics
     let mut column_stats_with_data = Vec::new();
     for (i, name) in features.names.iter().enumerate() {
         if let Some(col) = features.column(i) {
-            if let Ok(stats) = ColumnStats::calculate(name, &col) {
+            if let Some(stats) = column_stats(name, &col, config, bootstrap_seed) {
                 column_stats_with_data.push((stats, col));
             }
         }
     }
 
-    // K-means clustering
+    // DBSCAN (non-fatal unless it is the primary clustering)
+    let mut dbscan_noise = Vec::new();
+    let dbscan_result = run_dbscan_safe(normalized, config, &mut dbscan_noise);
+
+    // Primary clustering
+    let (cluster_result, cluster_stability) = primary_clustering(
+        normalized,
+        config,
+        dbscan_result.as_ref(),
+        kmeans_seed,
+        stability_seed,
+    )?;
+    let cluster_descriptors = config
+        .describe_clusters
+        .then(|| super::clustering::cluster_descriptors(&cluster_result, normalized));
+
+    // Anomaly detection (IQR outliers, globally or within each cluster)
+    let mut anomalies = if config.cluster_relative_anomalies {
+        cluster_iqr_anomalies(features, &cluster_result)
+    } else {
+        iqr_anomalies(features, &column_stats_with_data)
+    };
+
+    anomalies.extend(dbscan_noise);
+
+    // Apply anomaly type filters before deduping so a suppressed type
+    // doesn't hide another anomaly on the same row
+    let before_filter = anomalies.len();
+    anomalies.retain(|a| {
+        !config.exclude_anomaly_types.contains(&a.anomaly_type)
+            && (config.only_anomaly_types.is_empty()
+                || config.only_anomaly_types.contains(&a.anomaly_type))
+    });
+    let suppressed_anomalies = before_filter - anomalies.len();
+
+    // Agreement across detectors, before deduping keeps one anomaly per row
+    let consensus = anomaly_consensus(&anomalies);
+
+    // Sort and dedupe anomalies
+    anomalies.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut seen_rows = std::collections::HashSet::new();
+    anomalies.retain(|a| seen_rows.insert(a.row_id));
+    assign_anomaly_clusters(&mut anomalies, normalized, &cluster_result);
+
+    // Correlation, covariance and mutual information (non-fatal)
+    let (correlation, covariance, mutual_info) = run_correlation_safe(features, config);
+
+    // PCA (non-fatal)
+    let pca = if features.n_features() >= 2 {
+        match super::reduction::run_pca(normalized, config.pca_components) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                eprintln!("Warning: PCA failed: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let column_stats = column_stats_with_data
+        .into_iter()
+        .map(|(s, _)| s)
+        .collect();
+
+    Ok(AnalysisResult {
+        column_stats,
+        cluster_result,
+        cluster_stability,
+        cluster_descriptors,
+        dbscan_result,
+        anomalies,
+        consensus,
+        suppressed_anomalies,
+        correlation,
+        covariance,
+        mutual_info,
+        pca,
+        near_duplicates: None,
+        feature_weights: Vec::new(),
+        boolean_features: Vec::new(),
+        truth: None,
+        id_column: None,
+        coercions: Vec::new(),
+        lat_lon_columns: None,
+    })
+}
+
+/// Statistics for the given columns, which need not be features (`--stats-columns`).
+/// Columns too short to describe are left out.
+#[must_use]
+pub fn describe_columns(
+    columns: &[(String, Vec<f64>)],
+    config: &AnalysisConfig,
+) -> Vec<ColumnStats> {
+    let (_, bootstrap_seed, _) = component_seeds(config);
+    columns
+        .iter()
+        .filter_map(|(name, values)| column_stats(name, values, config, bootstrap_seed))
+        .collect()
+}
+
+/// K-means, bootstrap and stability seeds, derived from `config.seed` in a fixed
+/// order so one seed reproduces the whole run
+fn component_seeds(config: &AnalysisConfig) -> (u64, u64, u64) {
+    let mut master_rng = config
+        .seed
+        .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+    (master_rng.gen(), master_rng.gen(), master_rng.gen())
+}
+
+/// Statistics of one column, with a bootstrap CI of the mean if requested
+fn column_stats(
+    name: &str,
+    values: &[f64],
+    config: &AnalysisConfig,
+    bootstrap_seed: u64,
+) -> Option<ColumnStats> {
+    let mut stats = ColumnStats::calculate(name, values).ok()?;
+    if config.bootstrap {
+        stats.mean_ci = Some(super::stats::mean_ci(
+            values,
+            0.95,
+            BOOTSTRAP_SAMPLES,
+            bootstrap_seed,
+        ));
+    }
+    Some(stats)
+}
+
+/// Cluster with `config.cluster_method`, plus the K-means stability score if requested
+fn primary_clustering(
+    normalized: &NormalizedFeatures,
+    config: &AnalysisConfig,
+    dbscan_result: Option<&DbscanResult>,
+    kmeans_seed: u64,
+    stability_seed: u64,
+) -> Result<(ClusterResult, Option<f64>)> {
     let k = if config.clusters == 0 {
         super::clustering::suggest_k(normalized, 10)
     } else {
         config.clusters
     };
-    let cluster_result = super::clustering::kmeans(normalized, k)?;
+    let cluster_result = match config.cluster_method {
+        ClusterMethod::Kmeans => super::clustering::kmeans(normalized, k, kmeans_seed)?,
+        ClusterMethod::Gmm => super::clustering::gmm(normalized, k, kmeans_seed)?,
+        ClusterMethod::Dbscan => dbscan_result
+            .map(super::clustering::dbscan_clusters)
+            .ok_or_else(|| ZError::Ml("DBSCAN failed and it is the clustering method".into()))?,
+    };
+
+    let stability = config.stability && config.cluster_method == ClusterMethod::Kmeans;
+    if config.stability && !stability {
+        eprintln!(
+            "{} --stability only applies to kmeans, ignored for --method {}",
+            color::warning(),
+            config.cluster_method.as_str()
+        );
+    }
+    let cluster_stability = stability
+        .then(|| super::clustering::kmeans_stability(normalized, k, STABILITY_RUNS, stability_seed))
+        .transpose()?;
+    Ok((cluster_result, cluster_stability))
+}
 
-    // Anomaly detection (IQR outliers)
+/// IQR outliers of every column, scored by their z-score
+fn iqr_anomalies(features: &FeatureMatrix, columns: &[(ColumnStats, Vec<f64>)]) -> Vec<Anomaly> {
     let mut anomalies = Vec::new();
-    for (stats, col) in &column_stats_with_data {
+    for (stats, col) in columns {
         let outlier_indices = stats.outlier_indices(col);
         for idx in outlier_indices {
             let value = col.get(idx).copied().unwrap_or(0.0);
@@ -53,31 +257,142 @@ pub fn run_pipeline(
                 0.0
             };
             anomalies.push(Anomaly {
-                row_id: idx,
+                // Index into the source CSV, not the filtered sample list
+                row_id: features.row_indices[idx],
                 anomaly_type: format!("{}_outlier", stats.name),
+                detector: "iqr",
                 score: z_score.abs() / 4.0,
                 details: format!(
                     "{}={:.2} is {:.1} std from mean",
                     stats.name, value, z_score
                 ),
+                column: Some(stats.name.clone()),
+                value: Some(value),
+                z_score: Some(z_score),
+                cluster: None,
             });
         }
     }
+    anomalies
+}
 
-    // DBSCAN (non-fatal)
-    let dbscan_result = run_dbscan_safe(normalized, config, &mut anomalies);
+/// IQR outliers of every column measured against the row's own cluster, so a value
+/// normal globally but unusual for its cluster is flagged (and vice versa).
+/// Clusters smaller than `MIN_CLUSTER_STATS_SIZE` and the DBSCAN noise group are skipped.
+fn cluster_iqr_anomalies(features: &FeatureMatrix, clusters: &ClusterResult) -> Vec<Anomaly> {
+    let mut members = vec![Vec::new(); clusters.sizes.len()];
+    for (idx, &cluster) in clusters.labels.iter().enumerate() {
+        members[cluster].push(idx);
+    }
 
-    // Sort and dedupe anomalies
-    anomalies.sort_by(|a, b| {
-        b.score
-            .partial_cmp(&a.score)
-            .unwrap_or(std::cmp::Ordering::Equal)
+    let mut anomalies = Vec::new();
+    for (cluster, rows) in members.iter().enumerate() {
+        if rows.len() < MIN_CLUSTER_STATS_SIZE || !clusters.is_cluster(cluster) {
+            continue;
+        }
+        for (i, name) in features.names.iter().enumerate() {
+            let col: Vec<f64> = rows.iter().map(|&r| features.data[r][i]).collect();
+            let Ok(stats) = ColumnStats::calculate(name, &col) else {
+                continue;
+            };
+            for member in stats.outlier_indices(&col) {
+                let value = col[member];
+                let z_score = if stats.std_dev > 0.0 {
+                    (value - stats.mean) / stats.std_dev
+                } else {
+                    0.0
+                };
+                anomalies.push(Anomaly {
+                    row_id: features.row_indices[rows[member]],
+                    anomaly_type: format!("{name}_cluster_outlier"),
+                    detector: "cluster_iqr",
+                    score: z_score.abs() / 4.0,
+                    details: format!(
+                        "{name}={value:.2} is {z_score:.1} std from cluster {cluster} mean"
+                    ),
+                    column: Some(name.clone()),
+                    value: Some(value),
+                    z_score: Some(z_score),
+                    cluster: Some(cluster),
+                });
+            }
+        }
+    }
+    anomalies
+}
+
+/// Record each anomaly's cluster, looked up by its source row. Rows that aren't
+/// among the clustered samples or fall in the DBSCAN noise group keep `None`.
+fn assign_anomaly_clusters(
+    anomalies: &mut [Anomaly],
+    normalized: &NormalizedFeatures,
+    clusters: &ClusterResult,
+) {
+    let sample_of: std::collections::HashMap<usize, usize> = normalized
+        .row_indices
+        .iter()
+        .enumerate()
+        .map(|(sample, &row)| (row, sample))
+        .collect();
+    for anomaly in anomalies {
+        anomaly.cluster = sample_of
+            .get(&anomaly.row_id)
+            .and_then(|&sample| clusters.labels.get(sample).copied())
+            .filter(|&label| clusters.is_cluster(label));
+    }
+}
+
+/// Group anomalies by row, recording which detectors flagged each one
+///
+/// Sorted by detector count, then combined score (both descending), then row.
+fn anomaly_consensus(anomalies: &[Anomaly]) -> Vec<ConsensusAnomaly> {
+    use std::collections::BTreeMap;
+
+    // row -> detector -> best score from that detector
+    let mut by_row: BTreeMap<usize, BTreeMap<&'static str, f64>> = BTreeMap::new();
+    for a in anomalies {
+        let best = by_row
+            .entry(a.row_id)
+            .or_default()
+            .entry(a.detector)
+            .or_insert(a.score);
+        *best = best.max(a.score);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut consensus: Vec<ConsensusAnomaly> = by_row
+        .into_iter()
+        .map(|(row_id, scores)| ConsensusAnomaly {
+            row_id,
+            combined_score: scores.values().sum::<f64>() / scores.len() as f64,
+            detectors: scores.into_keys().collect(),
+        })
+        .collect();
+    consensus.sort_by(|a, b| {
+        b.detectors
+            .len()
+            .cmp(&a.detectors.len())
+            .then(
+                b.combined_score
+                    .partial_cmp(&a.combined_score)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+            .then(a.row_id.cmp(&b.row_id))
     });
-    let mut seen_rows = std::collections::HashSet::new();
-    anomalies.retain(|a| seen_rows.insert(a.row_id));
+    consensus
+}
 
-    // Correlation (non-fatal)
-    let correlation = match super::correlation::correlation_matrix(features) {
+/// Correlation matrix, plus the covariance and mutual information matrices if requested.
+/// Non-fatal.
+fn run_correlation_safe(
+    features: &FeatureMatrix,
+    config: &AnalysisConfig,
+) -> (
+    Option<CorrelationMatrix>,
+    Option<CovarianceMatrix>,
+    Option<MutualInfoMatrix>,
+) {
+    let correlation = match super::correlation::correlation_matrix(features, config.correlation_method) {
         Ok(corr) => Some(corr),
         Err(e) => {
             eprintln!("Warning: correlation failed: {e}");
@@ -85,12 +400,11 @@ pub fn run_pipeline(
         }
     };
 
-    // PCA (non-fatal)
-    let pca = if features.n_features() >= 2 {
-        match super::reduction::run_pca(normalized, config.pca_components) {
-            Ok(p) => Some(p),
+    let covariance = if config.covariance {
+        match super::correlation::covariance_matrix(features) {
+            Ok(cov) => Some(cov),
             Err(e) => {
-                eprintln!("Warning: PCA failed: {e}");
+                eprintln!("Warning: covariance failed: {e}");
                 None
             }
         }
@@ -98,19 +412,19 @@ pub fn run_pipeline(
         None
     };
 
-    let column_stats = column_stats_with_data
-        .into_iter()
-        .map(|(s, _)| s)
-        .collect();
+    let mutual_info = if config.mutual_info {
+        match super::correlation::mutual_info_matrix(features) {
+            Ok(mi) => Some(mi),
+            Err(e) => {
+                eprintln!("Warning: mutual information failed: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-    Ok(AnalysisResult {
-        column_stats,
-        cluster_result,
-        dbscan_result,
-        anomalies,
-        correlation,
-        pca,
-    })
+    (correlation, covariance, mutual_info)
 }
 
 /// Run DBSCAN, adding noise points as anomalies. Non-fatal.
@@ -118,27 +432,40 @@ fn run_dbscan_safe(
     normalized: &NormalizedFeatures,
     config: &AnalysisConfig,
     anomalies: &mut Vec<Anomaly>,
-) -> Option<crate::structs::DbscanResult> {
-    let eps = if config.dbscan_eps <= 0.0 {
+) -> Option<DbscanResult> {
+    let estimated = config.dbscan_eps <= 0.0;
+    let eps = if estimated {
         super::clustering::estimate_epsilon(normalized, config.dbscan_min_points)
     } else {
         config.dbscan_eps
     };
 
     match super::clustering::dbscan(normalized, eps, config.dbscan_min_points) {
-        Ok(result) => {
-            // Add noise points as anomalies
-            for (i, label) in result.labels.iter().enumerate() {
-                if label.is_none() {
+        Ok(mut result) => {
+            result.epsilon_estimated = estimated;
+            // Add noise points as anomalies, ordered by row so ties sort deterministically
+            let mut noise: Vec<Anomaly> = result
+                .labels
+                .iter()
+                .enumerate()
+                .filter(|(_, label)| label.is_none())
+                .map(|(i, _)| {
                     let row_id = normalized.row_indices[i];
-                    anomalies.push(Anomaly {
+                    Anomaly {
                         row_id,
                         anomaly_type: "dbscan_noise".to_string(),
+                        detector: "dbscan",
                         score: 0.8,
                         details: format!("Row {row_id} classified as noise by DBSCAN (eps={eps:.4})"),
-                    });
-                }
-            }
+                        column: None,
+                        value: None,
+                        z_score: None,
+                        cluster: None,
+                    }
+                })
+                .collect();
+            noise.sort_by_key(|a| a.row_id);
+            anomalies.extend(noise);
             Some(result)
         }
         Err(e) => {
@@ -169,10 +496,21 @@ mod tests {
         let normalized = features.normalize();
 
         let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
             clusters: 2,
             dbscan_eps: 0.0,
             dbscan_min_points: 2,
             pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: false,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: Vec::new(),
+            seed: None,
         };
 
         let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
@@ -183,6 +521,44 @@ mod tests {
         assert!(result.pca.is_some());
     }
 
+    #[test]
+    fn test_pipeline_dbscan_method() {
+        let csv = create_test_csv();
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let normalized = features.normalize();
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Dbscan,
+            clusters: 5,
+            dbscan_eps: 0.0,
+            dbscan_min_points: 2,
+            pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: true,
+            describe_clusters: true,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: Vec::new(),
+            seed: Some(1),
+        };
+
+        let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
+        let clusters = &result.cluster_result;
+        let dbscan = result.dbscan_result.as_ref().expect("dbscan");
+        assert_eq!(clusters.method, ClusterMethod::Dbscan);
+        assert_eq!(clusters.k, dbscan.n_clusters);
+        assert_eq!(clusters.noise_cluster.is_some(), dbscan.n_noise > 0);
+        assert!(result.cluster_stability.is_none());
+        let descriptors = result.cluster_descriptors.expect("descriptors");
+        assert_eq!(descriptors.len(), clusters.k);
+        for anomaly in result.anomalies.iter().filter(|a| a.detector == "dbscan") {
+            assert_eq!(anomaly.cluster, None);
+        }
+    }
+
     #[test]
     fn test_pipeline_defaults() {
         let csv = create_test_csv();
@@ -190,15 +566,308 @@ mod tests {
         let normalized = features.normalize();
 
         let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
             clusters: 0,
             dbscan_eps: 0.0,
             dbscan_min_points: 5,
             pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: false,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: Vec::new(),
+            seed: None,
         };
 
         let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
 
         assert!(!result.column_stats.is_empty());
         assert!(!result.anomalies.is_empty());
+        for a in &result.anomalies {
+            let sample = normalized.row_indices.iter().position(|&r| r == a.row_id);
+            assert_eq!(a.cluster, sample.map(|s| result.cluster_result.labels[s]));
+        }
+
+        // A row dropped before clustering has no cluster
+        let mut dropped = result.anomalies[..1].to_vec();
+        dropped[0].row_id = csv.row_count();
+        assign_anomaly_clusters(&mut dropped, &normalized, &result.cluster_result);
+        assert_eq!(dropped[0].cluster, None);
+    }
+
+    #[test]
+    fn test_dbscan_config_reaches_result() {
+        let csv = create_test_csv();
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let normalized = features.normalize();
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            clusters: 2,
+            dbscan_eps: 0.3,
+            dbscan_min_points: 3,
+            pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: false,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: Vec::new(),
+            seed: None,
+        };
+        let dbscan = run_dbscan_safe(&normalized, &config, &mut Vec::new()).expect("dbscan");
+        assert!((dbscan.epsilon - 0.3).abs() < f64::EPSILON);
+        assert_eq!(dbscan.min_points, 3);
+        assert!(!dbscan.epsilon_estimated);
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            dbscan_eps: 0.0,
+            ..config
+        };
+        let dbscan = run_dbscan_safe(&normalized, &config, &mut Vec::new()).expect("dbscan");
+        assert!(dbscan.epsilon > 0.0);
+        assert!(dbscan.epsilon_estimated);
+    }
+
+    #[test]
+    fn test_dbscan_noise_deterministic() {
+        let csv = create_test_csv();
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let normalized = features.normalize();
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            clusters: 2,
+            dbscan_eps: 0.05,
+            dbscan_min_points: 2,
+            pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: false,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: Vec::new(),
+            seed: None,
+        };
+
+        let mut first = Vec::new();
+        run_dbscan_safe(&normalized, &config, &mut first);
+        let mut second = Vec::new();
+        run_dbscan_safe(&normalized, &config, &mut second);
+
+        let first_rows: Vec<usize> = first.iter().map(|a| a.row_id).collect();
+        let second_rows: Vec<usize> = second.iter().map(|a| a.row_id).collect();
+        assert_eq!(first_rows, second_rows);
+        assert!(first_rows.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_outlier_row_ids_map_to_source_rows() {
+        let content = "name,x,y,z\na,1.0,10.0,100.0\nskipped,,,\nb,2.0,20.0,200.0\n\
+                       c,3.0,30.0,300.0\nd,4.0,40.0,400.0\ne,5.0,50.0,500.0\nf,100.0,1.0,1.0";
+        let mut file = NamedTempFile::new().expect("create");
+        file.write_all(content.as_bytes()).expect("write");
+        let csv = CsvData::from_file(file.path(), false).expect("parse");
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let normalized = features.normalize();
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            clusters: 2,
+            dbscan_eps: 0.0,
+            dbscan_min_points: 2,
+            pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: false,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: vec!["x_outlier".to_string()],
+            seed: Some(1),
+        };
+        let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
+
+        let rows: Vec<usize> = result.anomalies.iter().map(|a| a.row_id).collect();
+        assert_eq!(rows, vec![6]);
+        assert_eq!(csv.rows()[6][0], "f");
+    }
+
+    #[test]
+    fn test_anomaly_type_filters() {
+        let csv = create_test_csv();
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let normalized = features.normalize();
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            clusters: 2,
+            dbscan_eps: 0.0,
+            dbscan_min_points: 2,
+            pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: false,
+            stability: false,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: vec!["dbscan_noise".to_string()],
+            only_anomaly_types: Vec::new(),
+            seed: None,
+        };
+        let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
+        assert!(result.anomalies.iter().all(|a| a.anomaly_type != "dbscan_noise"));
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: vec!["x_outlier".to_string()],
+            ..config
+        };
+        let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
+        assert!(result.anomalies.iter().all(|a| a.anomaly_type == "x_outlier"));
+    }
+
+    #[test]
+    fn test_seeded_runs_identical_outputs() {
+        let csv = create_test_csv();
+        let features = FeatureMatrix::from_csv(&csv).expect("extract");
+        let normalized = features.normalize();
+
+        let config = AnalysisConfig {
+            cluster_method: ClusterMethod::Kmeans,
+            clusters: 2,
+            dbscan_eps: 0.0,
+            dbscan_min_points: 2,
+            pca_components: 0,
+            correlation_method: CorrelationMethod::Pearson,
+            covariance: false,
+            mutual_info: false,
+            bootstrap: true,
+            stability: true,
+            describe_clusters: false,
+            cluster_relative_anomalies: false,
+            exclude_anomaly_types: Vec::new(),
+            only_anomaly_types: Vec::new(),
+            seed: Some(7),
+        };
+
+        let write_run = || {
+            let dir = tempfile::TempDir::new().expect("create temp dir");
+            let result = run_pipeline(&features, &normalized, &config).expect("pipeline");
+            let summary = crate::ml::output::build_summary(
+                std::path::Path::new("t.csv"),
+                &csv,
+                &result,
+                None,
+            );
+            crate::ml::output::write_summary(dir.path(), &summary).expect("summary");
+            crate::ml::output::write_clusters(
+                dir.path(),
+                &result.cluster_result,
+                &normalized,
+                crate::structs::DistanceUnits::Normalized,
+                None,
+            )
+            .expect("clusters");
+            crate::ml::output::write_anomalies(dir.path(), &result.anomalies, None, None)
+                .expect("anomalies");
+            let stats_refs: Vec<_> = result.column_stats.iter().collect();
+            crate::ml::output::write_stats_json(
+                dir.path(),
+                &csv,
+                &stats_refs,
+                &result.cluster_result,
+                &result.anomalies,
+                result.dbscan_result.as_ref(),
+                result.correlation.as_ref(),
+                result.covariance.as_ref(),
+                result.pca.as_ref(),
+            )
+            .expect("stats");
+            dir
+        };
+
+        let first = write_run();
+        let second = write_run();
+        for name in ["summary.txt", "clusters.csv", "anomalies.csv", "stats.json"] {
+            let a = std::fs::read(first.path().join(name)).expect("read first");
+            let b = std::fs::read(second.path().join(name)).expect("read second");
+            assert_eq!(a, b, "{name} differs between seeded runs");
+        }
+    }
+
+    #[test]
+    fn test_cluster_iqr_anomalies() {
+        // 50 is unremarkable across all rows but far below the rest of cluster 1
+        let xs = [1.0, 2.0, 3.0, 4.0, 5.0, 100.0, 101.0, 102.0, 103.0, 50.0];
+        let features = FeatureMatrix {
+            names: vec!["price".to_string()],
+            data: xs.iter().map(|&x| vec![x]).collect(),
+            row_indices: (10..20).collect(),
+        };
+        let clusters = ClusterResult {
+            method: ClusterMethod::Kmeans,
+            labels: vec![0, 0, 0, 0, 0, 1, 1, 1, 1, 1],
+            k: 2,
+            sizes: vec![5, 5],
+            noise_cluster: None,
+        };
+
+        let col = features.column(0).expect("column");
+        let stats = ColumnStats::calculate("price", &col).expect("stats");
+        assert!(iqr_anomalies(&features, &[(stats, col)]).is_empty());
+
+        let anomalies = cluster_iqr_anomalies(&features, &clusters);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].row_id, 19);
+        assert_eq!(anomalies[0].anomaly_type, "price_cluster_outlier");
+        assert!(anomalies[0].details.contains("from cluster 1 mean"));
+    }
+
+    #[test]
+    fn test_anomaly_consensus() {
+        let anomaly = |row_id, detector, score| Anomaly {
+            row_id,
+            anomaly_type: format!("{detector}_test"),
+            detector,
+            score,
+            details: String::new(),
+            column: None,
+            value: None,
+            z_score: None,
+            cluster: None,
+        };
+        let anomalies = vec![
+            anomaly(3, "iqr", 0.9),
+            anomaly(1, "iqr", 0.4),
+            anomaly(1, "iqr", 0.6),
+            anomaly(1, "dbscan", 0.8),
+            anomaly(2, "dbscan", 0.8),
+            anomaly(2, "iqr", 0.2),
+        ];
+
+        let consensus = anomaly_consensus(&anomalies);
+        let rows: Vec<usize> = consensus.iter().map(|c| c.row_id).collect();
+        assert_eq!(rows, vec![1, 2, 3]);
+        assert_eq!(consensus[0].detectors, vec!["dbscan", "iqr"]);
+        // Best score per detector, then averaged: (0.8 + 0.6) / 2
+        assert!((consensus[0].combined_score - 0.7).abs() < 1e-12);
+        assert!((consensus[1].combined_score - 0.5).abs() < 1e-12);
+        assert_eq!(consensus[2].detectors, vec!["iqr"]);
     }
 }
@@ -65,9 +65,9 @@ pub fn run_pca(
         cumulative.push(running);
     }
 
-    // Feature importance: sum of absolute loadings per original feature
-    let transformed = pca.predict(&dataset);
-    let _ = transformed; // we only need the model's components for importance
+    // Project each sample onto the principal components
+    let transformed: Array2<f64> = pca.predict(dataset.records());
+    let projection: Vec<Vec<f64>> = transformed.outer_iter().map(|row| row.to_vec()).collect();
 
     // Use singular values as proxy for feature importance per component
     let feature_importance: Vec<(String, f64)> = features
@@ -91,6 +91,7 @@ pub fn run_pca(
         explained_variance_ratio,
         cumulative_variance: cumulative,
         feature_importance,
+        projection,
     })
 }
 
@@ -116,6 +117,8 @@ mod tests {
         assert!(result.n_components > 0);
         assert!(!result.explained_variance_ratio.is_empty());
         assert!(!result.cumulative_variance.is_empty());
+        assert_eq!(result.projection.len(), 5);
+        assert!(result.projection.iter().all(|p| p.len() == result.explained_variance_ratio.len()));
         // Cumulative should be monotonically increasing
         for i in 1..result.cumulative_variance.len() {
             assert!(result.cumulative_variance[i] >= result.cumulative_variance[i - 1]);
@@ -1,4 +1,6 @@
 use crate::structs::{ColumnStats, Result, ZError};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 impl ColumnStats {
     /// Calculate statistics for a vector of values
@@ -17,15 +19,20 @@ impl ColumnStats {
         let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / count as f64;
         let std_dev = variance.sqrt();
 
-        let mut sorted = values.to_vec();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-
-        let min = sorted[0];
-        let max = sorted[count - 1];
-
-        let q1 = percentile(&sorted, 25.0);
-        let median = percentile(&sorted, 50.0);
-        let q3 = percentile(&sorted, 75.0);
+        let (min, max, [q1, median, q3]) = if count >= QUICKSELECT_MIN_LEN {
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let mut scratch = values.to_vec();
+            (min, max, [25.0, 50.0, 75.0].map(|p| select_percentile(&mut scratch, p)))
+        } else {
+            let mut sorted = values.to_vec();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            (
+                sorted[0],
+                sorted[count - 1],
+                [25.0, 50.0, 75.0].map(|p| percentile(&sorted, p)),
+            )
+        };
         let iqr = q3 - q1;
 
         Ok(Self {
@@ -39,16 +46,67 @@ impl ColumnStats {
             median,
             q3,
             iqr,
+            mean_ci: None,
         })
     }
 }
 
-/// Calculate percentile using linear interpolation
+/// Bootstrap confidence interval for the mean of `values`
+///
+/// Resamples with replacement `n_bootstrap` times using an RNG seeded with
+/// `seed`, and returns the percentile interval at the given `confidence`
+/// (e.g. 0.95). Returns `(mean, mean)` for fewer than 2 values.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn mean_ci(values: &[f64], confidence: f64, n_bootstrap: usize, seed: u64) -> (f64, f64) {
+    let n = values.len();
+    if n < 2 || n_bootstrap == 0 {
+        let mean = values.iter().sum::<f64>() / n.max(1) as f64;
+        return (mean, mean);
+    }
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut means: Vec<f64> = (0..n_bootstrap)
+        .map(|_| (0..n).map(|_| values[rng.gen_range(0..n)]).sum::<f64>() / n as f64)
+        .collect();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let alpha = (1.0 - confidence.clamp(0.0, 1.0)) / 2.0;
+    (
+        percentile(&means, alpha * 100.0),
+        percentile(&means, (1.0 - alpha) * 100.0),
+    )
+}
+
+/// Column length from which quartiles are found by O(n) selection instead of an
+/// O(n log n) full sort; shorter columns sort cheaply enough that selection's extra
+/// passes don't pay off
+const QUICKSELECT_MIN_LEN: usize = 128;
+
+/// Fractional rank of percentile `p` in `len` sorted values, with its floor and ceiling
 #[allow(
     clippy::cast_precision_loss,
     clippy::cast_possible_truncation,
     clippy::cast_sign_loss
 )]
+fn percentile_rank(len: usize, p: f64) -> (f64, usize, usize) {
+    let k = (p / 100.0) * (len - 1) as f64;
+    (k, k.floor() as usize, k.ceil() as usize)
+}
+
+/// Linearly interpolate between the values at ranks `f` and `c` for rank `k`
+#[allow(clippy::cast_precision_loss)]
+fn interpolate(k: f64, f: usize, c: usize, at_f: f64, at_c: f64) -> f64 {
+    if f == c {
+        at_f
+    } else {
+        let d0 = at_f * (c as f64 - k);
+        let d1 = at_c * (k - f as f64);
+        d0 + d1
+    }
+}
+
+/// Calculate percentile using linear interpolation
 fn percentile(sorted: &[f64], p: f64) -> f64 {
     if sorted.is_empty() {
         return 0.0;
@@ -57,25 +115,156 @@ fn percentile(sorted: &[f64], p: f64) -> f64 {
         return sorted[0];
     }
 
-    let k = (p / 100.0) * (sorted.len() - 1) as f64;
-    let f = k.floor() as usize;
-    let c = k.ceil() as usize;
+    let (k, f, c) = percentile_rank(sorted.len(), p);
+    interpolate(k, f, c, sorted[f], sorted[c])
+}
 
-    if f == c {
-        sorted[f]
+/// Same result as [`percentile`] on the sorted values, without sorting
+///
+/// Uses `select_nth_unstable_by` (introselect with a median-of-medians
+/// fallback, so O(n) worst case) to place the floor rank, then takes the
+/// minimum of the upper partition as the ceiling rank. `values` is reordered.
+fn select_percentile(values: &mut [f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    if values.len() == 1 {
+        return values[0];
+    }
+
+    let (k, f, c) = percentile_rank(values.len(), p);
+    let (_, &mut at_f, upper) = values
+        .select_nth_unstable_by(f, |a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let at_c = if c == f {
+        at_f
     } else {
-        let d0 = sorted[f] * (c as f64 - k);
-        let d1 = sorted[c] * (k - f as f64);
-        d0 + d1
+        upper.iter().copied().fold(f64::INFINITY, f64::min)
+    };
+    interpolate(k, f, c, at_f, at_c)
+}
+
+/// Deviation of each value from the mean
+///
+/// Computing these once per column lets correlation and covariance matrices
+/// reuse them for every pair instead of recomputing the means.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn deviations(values: &[f64]) -> Vec<f64> {
+    let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+    values.iter().map(|x| x - mean).collect()
+}
+
+/// Sum of products of two deviation vectors (equal length)
+fn cross_sum(dx: &[f64], dy: &[f64]) -> f64 {
+    dx.iter().zip(dy).map(|(a, b)| a * b).sum()
+}
+
+/// Pearson correlation coefficient from two variables' [`deviations`]
+///
+/// Returns 0 when either variable is constant.
+#[must_use]
+pub fn pearson_from_deviations(dx: &[f64], dy: &[f64]) -> f64 {
+    let denom = (cross_sum(dx, dx) * cross_sum(dy, dy)).sqrt();
+    if denom == 0.0 {
+        return 0.0;
     }
+    cross_sum(dx, dy) / denom
 }
 
-/// Calculate correlation coefficient between two variables
+/// Sample covariance (n - 1 denominator) from two variables' [`deviations`]
 ///
 /// # Errors
 /// Returns error if vectors have different lengths or fewer than 2 values
 #[allow(clippy::cast_precision_loss)]
-pub fn correlation(x: &[f64], y: &[f64]) -> Result<f64> {
+pub fn covariance_from_deviations(dx: &[f64], dy: &[f64]) -> Result<f64> {
+    if dx.len() != dy.len() {
+        return Err(ZError::Ml("Vectors must have same length".into()));
+    }
+    if dx.len() < 2 {
+        return Err(ZError::Ml("Need at least 2 values for covariance".into()));
+    }
+    Ok(cross_sum(dx, dy) / (dx.len() - 1) as f64)
+}
+
+/// Equal-width histogram bin (0..bins) of each value over the values' range
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn bin_indices(values: &[f64], bins: usize) -> Vec<usize> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let width = (max - min) / bins as f64;
+
+    values
+        .iter()
+        .map(|v| {
+            if width > 0.0 {
+                (((v - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// Mutual information (in nats) between two variables
+///
+/// Both variables are discretized into `bins` equal-width bins over their own
+/// range, and MI is computed from the joint and marginal histograms. The
+/// estimate depends on the binning: more bins pick up finer structure but are
+/// biased upward on small samples (sparse joint cells look like dependence),
+/// fewer bins smooth real dependence away. Equal-width bins are also sensitive
+/// to outliers, which can squeeze most values into one bin. Compare scores
+/// only between pairs binned the same way. Returns 0 for fewer than 2 values.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn mutual_information(x: &[f64], y: &[f64], bins: usize) -> f64 {
+    let n = x.len().min(y.len());
+    if n < 2 || bins == 0 {
+        return 0.0;
+    }
+
+    let bx = bin_indices(&x[..n], bins);
+    let by = bin_indices(&y[..n], bins);
+
+    let mut joint = vec![0usize; bins * bins];
+    let mut marginal_x = vec![0usize; bins];
+    let mut marginal_y = vec![0usize; bins];
+    for (&i, &j) in bx.iter().zip(&by) {
+        joint[i * bins + j] += 1;
+        marginal_x[i] += 1;
+        marginal_y[j] += 1;
+    }
+
+    let n = n as f64;
+    let mut mi = 0.0;
+    for i in 0..bins {
+        for j in 0..bins {
+            let count = joint[i * bins + j];
+            if count == 0 {
+                continue;
+            }
+            let joint_p = count as f64 / n;
+            let independent_p = (marginal_x[i] as f64 / n) * (marginal_y[j] as f64 / n);
+            mi += joint_p * (joint_p / independent_p).ln();
+        }
+    }
+
+    mi.max(0.0)
+}
+
+/// Calculate Kendall's tau-b rank correlation between two variables
+///
+/// Counts concordant and discordant pairs with a correction for ties.
+/// This is O(n^2) in the number of values, so it is much slower than
+/// Pearson on large columns.
+///
+/// # Errors
+/// Returns error if vectors have different lengths or fewer than 2 values
+#[allow(clippy::cast_precision_loss, clippy::similar_names)]
+pub fn kendall_tau(x: &[f64], y: &[f64]) -> Result<f64> {
     if x.len() != y.len() {
         return Err(ZError::Ml("Vectors must have same length".into()));
     }
@@ -83,28 +272,38 @@ pub fn correlation(x: &[f64], y: &[f64]) -> Result<f64> {
         return Err(ZError::Ml("Need at least 2 values for correlation".into()));
     }
 
-    let n = x.len() as f64;
-    let mean_x = x.iter().sum::<f64>() / n;
-    let mean_y = y.iter().sum::<f64>() / n;
-
-    let mut cov = 0.0;
-    let mut var_x = 0.0;
-    let mut var_y = 0.0;
+    let mut concordant = 0u64;
+    let mut discordant = 0u64;
+    let mut ties_x_only = 0u64;
+    let mut ties_y_only = 0u64;
 
     for i in 0..x.len() {
-        let dx = x[i] - mean_x;
-        let dy = y[i] - mean_y;
-        cov += dx * dy;
-        var_x += dx * dx;
-        var_y += dy * dy;
+        for j in (i + 1)..x.len() {
+            let dx = x[i] - x[j];
+            let dy = y[i] - y[j];
+            if dx == 0.0 && dy == 0.0 {
+                continue;
+            }
+            if dx == 0.0 {
+                ties_x_only += 1;
+            } else if dy == 0.0 {
+                ties_y_only += 1;
+            } else if (dx > 0.0) == (dy > 0.0) {
+                concordant += 1;
+            } else {
+                discordant += 1;
+            }
+        }
     }
 
-    let denom = (var_x * var_y).sqrt();
+    let untied_x = (concordant + discordant + ties_y_only) as f64;
+    let untied_y = (concordant + discordant + ties_x_only) as f64;
+    let denom = (untied_x * untied_y).sqrt();
     if denom == 0.0 {
         return Ok(0.0);
     }
 
-    Ok(cov / denom)
+    Ok((concordant as f64 - discordant as f64) / denom)
 }
 
 #[cfg(test)]
@@ -123,6 +322,29 @@ mod tests {
         assert!((stats.median - 5.5).abs() < 0.01);
     }
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_quickselect_matches_sorted_percentiles() {
+        // Deterministic pseudo-random column with plenty of ties
+        let values: Vec<f64> = (0..QUICKSELECT_MIN_LEN * 3 + 7)
+            .map(|i| f64::from(u32::try_from((i * 7919) % 1009).expect("fits")) / 7.0)
+            .collect();
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut scratch = values.clone();
+        for p in [0.0, 10.0, 25.0, 33.3, 50.0, 75.0, 99.9, 100.0] {
+            assert_eq!(select_percentile(&mut scratch, p), percentile(&sorted, p), "p={p}");
+        }
+
+        let stats = ColumnStats::calculate("big", &values).expect("calculate stats");
+        assert_eq!(stats.q1, percentile(&sorted, 25.0));
+        assert_eq!(stats.median, percentile(&sorted, 50.0));
+        assert_eq!(stats.q3, percentile(&sorted, 75.0));
+        assert_eq!(stats.min, sorted[0]);
+        assert_eq!(stats.max, sorted[sorted.len() - 1]);
+    }
+
     #[test]
     fn test_outlier_detection() {
         let values = vec![1.0, 2.0, 3.0, 4.0, 5.0, 100.0]; // 100 is outlier
@@ -136,8 +358,68 @@ mod tests {
     fn test_correlation() {
         let x = vec![1.0, 2.0, 3.0, 4.0, 5.0];
         let y = vec![2.0, 4.0, 6.0, 8.0, 10.0];
-        let corr = correlation(&x, &y).expect("calculate correlation");
+        let corr = pearson_from_deviations(&deviations(&x), &deviations(&y));
 
         assert!((corr - 1.0).abs() < 0.01); // Perfect positive correlation
+
+        let constant = vec![3.0; 5];
+        assert!(pearson_from_deviations(&deviations(&x), &deviations(&constant)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_mutual_information() {
+        // y = x^2 on a symmetric range: zero correlation but strong dependence
+        let xs: Vec<f64> = (-50..=50).map(f64::from).collect();
+        let squares: Vec<f64> = xs.iter().map(|v| v * v).collect();
+        let corr = pearson_from_deviations(&deviations(&xs), &deviations(&squares));
+        assert!(corr.abs() < 1e-9);
+        assert!(mutual_information(&xs, &squares, 8) > 0.5);
+
+        // Identical variables: MI equals the entropy of the binned variable (ln 4 here)
+        let uniform: Vec<f64> = (0..400).map(|i| f64::from(i % 4)).collect();
+        let mi = mutual_information(&uniform, &uniform, 4);
+        assert!((mi - 4.0f64.ln()).abs() < 1e-9);
+
+        // Independent pattern: every (x, y) bin combination equally often
+        let a: Vec<f64> = (0..400).map(|i| f64::from(i % 4)).collect();
+        let b: Vec<f64> = (0..400).map(|i| f64::from((i / 4) % 4)).collect();
+        assert!(mutual_information(&a, &b, 4).abs() < 1e-9);
+
+        assert!(mutual_information(&[1.0], &[2.0], 4).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_covariance() {
+        let x = vec![1.0, 2.0, 3.0, 4.0];
+        let y = vec![8.0, 6.0, 4.0, 2.0];
+        let cov = covariance_from_deviations(&deviations(&x), &deviations(&y)).expect("cov");
+        assert!((cov + 10.0 / 3.0).abs() < 1e-12);
+        assert!(covariance_from_deviations(&[0.0], &[0.0]).is_err());
+        assert!(covariance_from_deviations(&[0.0, 1.0], &[0.0]).is_err());
+    }
+
+    #[test]
+    fn test_kendall_tau() {
+        let x = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        let y = vec![1.0, 4.0, 9.0, 16.0, 25.0];
+        let tau = kendall_tau(&x, &y).expect("calculate tau");
+        assert!((tau - 1.0).abs() < 1e-9); // Monotonic, not linear
+
+        let reversed: Vec<f64> = y.iter().rev().copied().collect();
+        let tau = kendall_tau(&x, &reversed).expect("calculate tau");
+        assert!((tau + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mean_ci_symmetric() {
+        let values: Vec<f64> = (1..=21).map(f64::from).collect();
+        let (lo, hi) = mean_ci(&values, 0.95, 1000, 42);
+
+        assert!(lo < 11.0 && hi > 11.0);
+        let center = f64::midpoint(lo, hi);
+        assert!((center - 11.0).abs() < 0.5);
+
+        // Same seed gives the same interval
+        assert_eq!(mean_ci(&values, 0.95, 1000, 42), (lo, hi));
     }
 }
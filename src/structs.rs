@@ -117,10 +117,100 @@ impl FileInfo {
 #[derive(Debug, Clone)]
 pub struct CsvData {
     pub headers: Vec<String>,
-    pub rows: Vec<Vec<String>>,
+    /// Read through `rows()`; never changed after construction, so `numeric_cache`
+    /// can't go stale
+    rows: Vec<Vec<String>>,
+    pub delimiter: u8,
+    /// Rows whose field count differed from the header count
+    pub ragged_rows: usize,
+    /// Reading stopped at a row cap before the end of the file
+    pub truncated: bool,
+    /// Metadata and comment lines skipped before the header
+    pub skipped_lines: usize,
+    /// Records dropped because they couldn't be parsed (`--continue-on-parse-error`)
+    pub skipped_records: usize,
+    /// `line N: error` for the first `MAX_LOGGED_PARSE_ERRORS` skipped records
+    pub parse_errors: Vec<String>,
+    /// `line N: reason` for the first `MAX_LOGGED_PARSE_ERRORS` records kept despite a
+    /// quoted field spanning lines shaped like rows (`--continue-on-parse-error`)
+    pub quote_warnings: Vec<String>,
+    /// Cells parsed as numbers, filled on the first `numeric_columns` call
+    numeric_cache: std::sync::OnceLock<Vec<Vec<Option<f64>>>>,
+}
+
+/// Lines before a CSV header to skip, such as an export's title or `# generated ...` lines
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsvPreamble {
+    /// Lines dropped unconditionally before anything else
+    pub skip_lines: usize,
+    /// Lines starting with this byte are skipped: before the header, and as
+    /// records anywhere after it
+    pub comment: Option<u8>,
+}
+
+/// Value type inferred for a CSV column from its non-empty cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InferredType {
+    /// Every cell is `true`/`false`/`0`/`1` (case-insensitive)
+    Boolean,
+    Integer,
+    Float,
+    /// Every cell is a date or timestamp in a recognized format
+    Date,
+    Text,
+    /// No non-empty cells
+    Empty,
+}
+
+impl InferredType {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::Date => "date",
+            Self::Text => "text",
+            Self::Empty => "empty",
+        }
+    }
 }
 
 impl CsvData {
+    /// A table read without any ragged, skipped or truncated rows; `from_file_with_options`
+    /// fills those in
+    #[must_use]
+    pub const fn new(headers: Vec<String>, rows: Vec<Vec<String>>, delimiter: u8) -> Self {
+        Self {
+            headers,
+            rows,
+            delimiter,
+            ragged_rows: 0,
+            truncated: false,
+            skipped_lines: 0,
+            skipped_records: 0,
+            parse_errors: Vec::new(),
+            quote_warnings: Vec::new(),
+            numeric_cache: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// The data rows, in file order
+    #[must_use]
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    /// Every column's cells parsed as numbers (`None` where empty or non-numeric),
+    /// indexed `[column][row]`. Parsed once on first use, in parallel across columns
+    /// for large files, and shared by every later caller.
+    #[must_use]
+    pub fn numeric_columns(&self) -> &[Vec<Option<f64>>] {
+        self.numeric_cache
+            .get_or_init(|| crate::csv_reader::parse_columns(&self.rows, self.col_count()))
+    }
+
     /// Get number of rows
     #[must_use]
     pub const fn row_count(&self) -> usize {
@@ -151,24 +241,50 @@ impl CsvData {
     #[must_use]
     #[allow(clippy::cast_precision_loss)]
     pub fn numeric_column_indices(&self) -> Vec<usize> {
+        let parsed = self.numeric_columns();
         (0..self.col_count())
             .filter(|&i| {
                 self.column(i).is_some_and(|col| {
                     // Consider numeric if at least 50% of non-empty values parse as numbers
-                    let non_empty: Vec<_> = col.iter().filter(|s| !s.is_empty()).collect();
-                    if non_empty.is_empty() {
+                    let non_empty = col.iter().filter(|s| !s.is_empty()).count();
+                    if non_empty == 0 {
                         return false;
                     }
-                    let numeric_count = non_empty
-                        .iter()
-                        .filter(|s| s.parse::<f64>().is_ok())
-                        .count();
-                    numeric_count as f64 / non_empty.len() as f64 >= 0.5
+                    let numeric_count = parsed[i].iter().filter(|v| v.is_some()).count();
+                    numeric_count as f64 / non_empty as f64 >= 0.5
                 })
             })
             .collect()
     }
 
+    /// Short dataset overview: shape, numeric columns, and a few preview rows
+    #[must_use]
+    pub fn summary(&self) -> String {
+        use std::fmt::Write as _;
+
+        let numeric: Vec<&str> = self
+            .numeric_column_indices()
+            .into_iter()
+            .filter_map(|i| self.headers.get(i).map(String::as_str))
+            .collect();
+
+        let mut summary = format!("{} rows x {} columns\n", self.row_count(), self.col_count());
+        let _ = writeln!(
+            summary,
+            "Numeric columns: {}",
+            if numeric.is_empty() {
+                "none".to_string()
+            } else {
+                numeric.join(", ")
+            }
+        );
+        let _ = writeln!(summary, "Preview:");
+        let _ = writeln!(summary, "{}", self.headers.join(","));
+        for row in self.rows.iter().take(3) {
+            let _ = writeln!(summary, "{}", row.join(","));
+        }
+        summary
+    }
 }
 
 // ============================================================================
@@ -210,9 +326,15 @@ pub struct NormalizedFeatures {
     pub names: Vec<String>,
     pub data: Vec<Vec<f64>>,
     pub row_indices: Vec<usize>,
-    #[allow(dead_code)]
     pub mins: Vec<f64>,
-    #[allow(dead_code)]
+    pub maxs: Vec<f64>,
+}
+
+/// Persisted min-max scaling parameters, reusable across datasets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormParams {
+    pub names: Vec<String>,
+    pub mins: Vec<f64>,
     pub maxs: Vec<f64>,
 }
 
@@ -238,7 +360,7 @@ impl NormalizedFeatures {
 }
 
 /// Descriptive statistics for a numeric column
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ColumnStats {
     pub name: String,
     pub count: usize,
@@ -250,6 +372,8 @@ pub struct ColumnStats {
     pub median: f64,
     pub q3: f64,
     pub iqr: f64,
+    /// Bootstrap 95% confidence interval for the mean (only computed with `--bootstrap`)
+    pub mean_ci: Option<(f64, f64)>,
 }
 
 impl ColumnStats {
@@ -270,22 +394,119 @@ impl ColumnStats {
     /// Format as a summary string
     #[must_use]
     pub fn summary(&self) -> String {
+        self.summary_with_precision(2)
+    }
+
+    /// Format as a summary string with `p` decimal places
+    #[must_use]
+    pub fn summary_with_precision(&self, p: usize) -> String {
+        let ci = self
+            .mean_ci
+            .map(|(lo, hi)| format!(", mean 95% CI=[{lo:.p$}, {hi:.p$}]"))
+            .unwrap_or_default();
         format!(
-            "{}: n={}, mean={:.2}, std={:.2}, min={:.2}, Q1={:.2}, median={:.2}, Q3={:.2}, max={:.2}, IQR={:.2}",
+            "{}: n={}, mean={:.p$}, std={:.p$}, min={:.p$}, Q1={:.p$}, median={:.p$}, Q3={:.p$}, max={:.p$}, IQR={:.p$}{ci}",
             self.name, self.count, self.mean, self.std_dev, self.min, self.q1, self.median, self.q3, self.max, self.iqr
         )
     }
 }
 
-/// Result of K-means clustering
-#[derive(Debug, Clone)]
+/// Result of the primary clustering (`--method`)
+#[derive(Debug, Clone, Serialize)]
 pub struct ClusterResult {
+    /// Algorithm that produced the labels
+    pub method: ClusterMethod,
     /// Cluster assignment for each sample
     pub labels: Vec<usize>,
-    /// Number of clusters
+    /// Number of clusters, not counting the noise group
     pub k: usize,
-    /// Cluster sizes
+    /// Group sizes, indexed by label (the noise group included)
     pub sizes: Vec<usize>,
+    /// Label of the trailing group holding DBSCAN noise points, if there are any
+    pub noise_cluster: Option<usize>,
+}
+
+impl ClusterResult {
+    /// Whether `label` is a real cluster rather than the noise group
+    #[must_use]
+    pub fn is_cluster(&self, label: usize) -> bool {
+        self.noise_cluster != Some(label)
+    }
+}
+
+/// How far a cluster's centroid sits from the overall mean on one feature
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureDeviation {
+    pub name: String,
+    /// Centroid minus overall mean in normalized [0, 1] space (positive = high)
+    pub deviation: f64,
+}
+
+/// Plain-language description of a K-means cluster by its most distinctive features
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterDescriptor {
+    pub cluster: usize,
+    pub size: usize,
+    /// Features deviating most from the overall mean, largest first
+    pub top_features: Vec<FeatureDeviation>,
+}
+
+impl ClusterDescriptor {
+    /// One-line description, e.g. `Cluster 2 (14 rows): high price (+0.31), low rating (-0.22)`
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let traits = if self.top_features.is_empty() {
+            "close to the overall mean on every feature".to_string()
+        } else {
+            self.top_features
+                .iter()
+                .map(|f| {
+                    let level = if f.deviation > 0.0 { "high" } else { "low" };
+                    format!("{level} {} ({:+.2})", f.name, f.deviation)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!("Cluster {} ({} rows): {traits}", self.cluster, self.size)
+    }
+}
+
+/// Two rows within the near-duplicate distance of each other (`row_a < row_b`)
+#[derive(Debug, Clone, Serialize)]
+pub struct NearDuplicatePair {
+    pub row_a: usize,
+    pub row_b: usize,
+    /// Euclidean distance in normalized feature space
+    pub distance: f64,
+}
+
+/// Near-duplicate rows found with `--near-dupe-threshold`
+#[derive(Debug, Clone, Serialize)]
+pub struct NearDuplicates {
+    pub threshold: f64,
+    pub pairs: Vec<NearDuplicatePair>,
+    /// Rows dropped before analysis as near-duplicates of a kept row
+    pub collapsed_rows: usize,
+}
+
+/// Multiplier applied to one normalized feature with `--feature-weights`
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureWeight {
+    pub name: String,
+    pub weight: f64,
+}
+
+/// Primary clustering compared against a known label column (`--truth-column`)
+#[derive(Debug, Clone, Serialize)]
+pub struct TruthComparison {
+    pub column: String,
+    pub adjusted_rand_index: f64,
+    /// Mutual information over the arithmetic mean of both labelings' entropies
+    pub normalized_mutual_info: f64,
+    /// Distinct values of the truth column, sorted
+    pub classes: Vec<String>,
+    /// Row counts indexed `[cluster label][class]`
+    pub contingency: Vec<Vec<usize>>,
 }
 
 /// Represents an anomaly detected in the data
@@ -294,52 +515,440 @@ pub struct ClusterResult {
 pub struct Anomaly {
     pub row_id: usize,
     pub anomaly_type: String,
+    /// Detection method that flagged the row (`iqr`, `dbscan`)
+    pub detector: &'static str,
     pub score: f64,
     pub details: String,
+    /// Feature whose value was flagged; `None` for whole-row detectors (DBSCAN)
+    pub column: Option<String>,
+    /// The flagged value
+    pub value: Option<f64>,
+    /// Standard deviations between `value` and the mean it was compared against
+    pub z_score: Option<f64>,
+    /// Primary cluster of the row; `None` if it isn't among the clustered samples
+    /// or is DBSCAN noise
+    pub cluster: Option<usize>,
+}
+
+/// A row flagged by one or more anomaly detectors
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsensusAnomaly {
+    pub row_id: usize,
+    /// Detectors that flagged the row, sorted by name
+    pub detectors: Vec<&'static str>,
+    /// Mean over those detectors of each one's highest score for the row
+    pub combined_score: f64,
+}
+
+/// Correlation coefficient used for the correlation matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CorrelationMethod {
+    /// Pearson product-moment (linear)
+    #[default]
+    Pearson,
+    /// Kendall's tau-b (rank-based, tie-corrected)
+    Kendall,
+}
+
+/// Algorithm used for the primary clustering written to `clusters.csv`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterMethod {
+    /// K-means with k from `-k` (or suggested)
+    #[default]
+    Kmeans,
+    /// DBSCAN with `--dbscan-eps`/`--dbscan-min-points`; noise points form their own group
+    Dbscan,
+    /// Gaussian mixture model with k components, each row assigned its most likely one
+    Gmm,
+}
+
+impl ClusterMethod {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Kmeans => "kmeans",
+            Self::Dbscan => "dbscan",
+            Self::Gmm => "gmm",
+        }
+    }
+}
+
+/// Space in which `clusters.csv` measures distance to centroid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceUnits {
+    /// Min-max normalized [0, 1] feature space
+    #[default]
+    Normalized,
+    /// Original feature units, de-normalized with the stored mins/maxs
+    Original,
 }
 
 /// Correlation matrix between numeric features
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CorrelationMatrix {
     pub names: Vec<String>,
     pub matrix: Vec<Vec<f64>>,
 }
 
+/// Sample covariance matrix between numeric features (n - 1 denominator)
+#[derive(Debug, Clone, Serialize)]
+pub struct CovarianceMatrix {
+    pub names: Vec<String>,
+    pub matrix: Vec<Vec<f64>>,
+}
+
+/// Pairwise mutual information (nats) between numeric features
+#[derive(Debug, Clone, Serialize)]
+pub struct MutualInfoMatrix {
+    pub names: Vec<String>,
+    /// Equal-width bins per variable used for the histograms
+    pub bins: usize,
+    pub matrix: Vec<Vec<f64>>,
+}
+
+/// DBSCAN role of a single point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbscanPointType {
+    /// Has at least `min_points` points (itself included) within epsilon
+    Core,
+    /// In a cluster but not dense enough to be core
+    Border,
+    /// Not assigned to any cluster
+    Noise,
+}
+
+impl DbscanPointType {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Core => "core",
+            Self::Border => "border",
+            Self::Noise => "noise",
+        }
+    }
+}
+
 /// Result of DBSCAN clustering
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DbscanResult {
     pub labels: Vec<Option<usize>>,
+    pub point_types: Vec<DbscanPointType>,
     pub n_clusters: usize,
     pub n_noise: usize,
     pub sizes: Vec<usize>,
     pub epsilon: f64,
+    /// Whether `epsilon` was estimated by the k-distance knee heuristic
+    pub epsilon_estimated: bool,
     pub min_points: usize,
 }
 
 /// Result of PCA dimensionality reduction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PcaResult {
     pub n_components: usize,
     pub explained_variance_ratio: Vec<f64>,
     pub cumulative_variance: Vec<f64>,
     pub feature_importance: Vec<(String, f64)>,
+    /// Coordinates of each sample on the principal components (aligned with `row_indices`)
+    pub projection: Vec<Vec<f64>>,
 }
 
-/// Combined result of the full analysis pipeline
-#[derive(Debug, Clone)]
+/// Combined result of the full analysis pipeline. Serializes as-is for
+/// `--dump-result`; any non-finite float becomes `null`. A constant column isn't one:
+/// its correlations are computed as 0.
+#[derive(Debug, Clone, Serialize)]
 pub struct AnalysisResult {
     pub column_stats: Vec<ColumnStats>,
     pub cluster_result: ClusterResult,
+    /// Mean pairwise Adjusted Rand Index across repeated K-means runs
+    pub cluster_stability: Option<f64>,
+    pub cluster_descriptors: Option<Vec<ClusterDescriptor>>,
     pub dbscan_result: Option<DbscanResult>,
     pub anomalies: Vec<Anomaly>,
+    /// Rows ranked by how many detectors flagged them, then by combined score
+    pub consensus: Vec<ConsensusAnomaly>,
+    /// Anomalies dropped by the type filters
+    pub suppressed_anomalies: usize,
     pub correlation: Option<CorrelationMatrix>,
+    pub covariance: Option<CovarianceMatrix>,
+    pub mutual_info: Option<MutualInfoMatrix>,
     pub pca: Option<PcaResult>,
+    /// Filled in by the caller, which detects near-duplicates before the pipeline runs
+    pub near_duplicates: Option<NearDuplicates>,
+    /// Weights applied to the normalized features, also filled in by the caller
+    pub feature_weights: Vec<FeatureWeight>,
+    /// Features coded from boolean columns by `--encode-booleans`, filled in by the caller
+    pub boolean_features: Vec<String>,
+    /// Agreement with `--truth-column`, also filled in by the caller
+    pub truth: Option<TruthComparison>,
+    /// Id column inferred to label `pca_projection.csv` when `--label-column` isn't
+    /// given, also filled in by the caller
+    pub id_column: Option<String>,
+    /// Numeric columns with cells that aren't numbers, also filled in by the caller
+    pub coercions: Vec<ColumnCoercion>,
+    /// Latitude and longitude columns mapped in `clusters.geojson` (`--geojson`),
+    /// also filled in by the caller
+    pub lat_lon_columns: Option<(String, String)>,
+}
+
+/// Non-empty cells of a numeric-classified column that don't parse as numbers.
+/// Rows holding one are left out of the feature matrix.
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnCoercion {
+    pub column: String,
+    pub count: usize,
+    /// A few distinct offending values, in order of appearance
+    pub samples: Vec<String>,
+    /// Source rows of the first few offending values
+    pub first_rows: Vec<usize>,
+}
+
+/// Change in one column's statistics between two analysis runs
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnDrift {
+    pub name: String,
+    pub mean_delta: f64,
+    pub std_dev_delta: f64,
+    pub q1_delta: f64,
+    pub median_delta: f64,
+    pub q3_delta: f64,
+    /// Mean shift in units of the old standard deviation (`None` if the old std was 0)
+    pub mean_shift_sd: Option<f64>,
+    /// Whether the mean shift exceeds the threshold
+    pub drifted: bool,
+}
+
+/// Differences between two `stats.json` files
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsDiff {
+    /// Mean shift (in old standard deviations) above which a column is flagged
+    pub threshold_sd: f64,
+    pub old_rows: usize,
+    pub new_rows: usize,
+    pub columns: Vec<ColumnDrift>,
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+    pub old_k: usize,
+    pub new_k: usize,
+    pub old_dbscan_clusters: Option<usize>,
+    pub new_dbscan_clusters: Option<usize>,
+    pub old_anomalies: usize,
+    pub new_anomalies: usize,
+}
+
+impl StatsDiff {
+    /// Human-readable drift report
+    #[must_use]
+    pub fn summary(&self) -> String {
+        use std::fmt::Write as _;
+
+        let drifted = self.columns.iter().filter(|c| c.drifted).count();
+        let mut summary = format!(
+            "Rows: {} -> {}\nColumns drifted (mean shift > {} std): {drifted} of {}\n",
+            self.old_rows,
+            self.new_rows,
+            self.threshold_sd,
+            self.columns.len()
+        );
+
+        for c in &self.columns {
+            let shift = c
+                .mean_shift_sd
+                .map_or_else(|| "n/a".to_string(), |s| format!("{s:+.2} std"));
+            let _ = writeln!(
+                summary,
+                "{} {}: mean {:+.4} ({shift}), std {:+.4}, Q1 {:+.4}, median {:+.4}, Q3 {:+.4}",
+                if c.drifted { "!" } else { "-" },
+                c.name,
+                c.mean_delta,
+                c.std_dev_delta,
+                c.q1_delta,
+                c.median_delta,
+                c.q3_delta
+            );
+        }
+        if !self.added_columns.is_empty() {
+            let _ = writeln!(summary, "Added columns: {}", self.added_columns.join(", "));
+        }
+        if !self.removed_columns.is_empty() {
+            let _ = writeln!(summary, "Removed columns: {}", self.removed_columns.join(", "));
+        }
+
+        let _ = writeln!(summary, "K-means clusters: {} -> {}", self.old_k, self.new_k);
+        let fmt_opt = |v: Option<usize>| v.map_or_else(|| "n/a".to_string(), |n| n.to_string());
+        let _ = writeln!(
+            summary,
+            "DBSCAN clusters: {} -> {}",
+            fmt_opt(self.old_dbscan_clusters),
+            fmt_opt(self.new_dbscan_clusters)
+        );
+        let _ = writeln!(
+            summary,
+            "Anomalies: {} -> {}",
+            self.old_anomalies, self.new_anomalies
+        );
+        summary
+    }
+}
+
+/// A column whose value made a row an outlier, from `anomalies_structured.csv`
+#[derive(Debug, Clone)]
+pub struct OutlierColumn {
+    pub column: String,
+    pub value: f64,
+    /// Standard deviations from the mean it was compared against
+    pub z_score: f64,
+    /// Compared against the row's cluster rather than the whole column
+    pub within_cluster: bool,
+    /// Column mean and standard deviation from `stats.json`, if described there
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+}
+
+/// One record of `anomalies.csv`
+#[derive(Debug, Clone)]
+pub struct FlaggedAnomaly {
+    pub anomaly_type: String,
+    pub score: f64,
+    pub details: String,
+}
+
+/// Everything an analyze output directory says about one row (`z explain-anomaly`)
+#[derive(Debug, Clone)]
+pub struct AnomalyExplanation {
+    pub row_id: usize,
+    pub row_count: usize,
+    /// Whether the row is in `clusters.csv` (rows with missing features are not)
+    pub clustered: bool,
+    /// `None` for an unclustered row or DBSCAN noise
+    pub cluster: Option<usize>,
+    pub distance_to_centroid: Option<f64>,
+    pub outlier_columns: Vec<OutlierColumn>,
+    pub anomalies: Vec<FlaggedAnomaly>,
+}
+
+impl AnomalyExplanation {
+    /// Human-readable per-row report
+    #[must_use]
+    pub fn summary(&self) -> String {
+        use std::fmt::Write as _;
+
+        let mut summary = format!("Row {} of {}\n", self.row_id, self.row_count);
+        match (self.clustered, self.cluster, self.distance_to_centroid) {
+            (false, _, _) => summary.push_str("Cluster: none (row was not clustered)\n"),
+            (true, None, _) => summary.push_str("Cluster: none (DBSCAN noise)\n"),
+            (true, Some(cluster), Some(distance)) => {
+                let _ = writeln!(
+                    summary,
+                    "Cluster: {cluster} (distance to centroid {distance:.4})"
+                );
+            }
+            (true, Some(cluster), None) => {
+                let _ = writeln!(summary, "Cluster: {cluster}");
+            }
+        }
+
+        if !self.outlier_columns.is_empty() {
+            summary.push_str("Outlier columns:\n");
+        }
+        for c in &self.outlier_columns {
+            let reference = match (self.cluster, c.within_cluster) {
+                (Some(cluster), true) => format!("cluster {cluster} mean"),
+                (None, true) => "cluster mean".to_string(),
+                (_, false) => "mean".to_string(),
+            };
+            let _ = write!(
+                summary,
+                "- {} = {:.4}: {:+.2} std from the {reference}",
+                c.column, c.value, c.z_score
+            );
+            if let (false, Some(mean), Some(std_dev)) = (c.within_cluster, c.mean, c.std_dev) {
+                let _ = write!(summary, " (mean {mean:.4}, std {std_dev:.4})");
+            }
+            summary.push('\n');
+        }
+
+        if self.anomalies.is_empty() {
+            summary.push_str("Anomalies: none (row was not flagged)\n");
+        } else {
+            let _ = writeln!(summary, "Anomalies ({}):", self.anomalies.len());
+        }
+        for a in &self.anomalies {
+            let _ = writeln!(
+                summary,
+                "- {} (score {:.4}): {}",
+                a.anomaly_type, a.score, a.details
+            );
+        }
+        summary
+    }
 }
 
 // ============================================================================
 // XML Types
 // ============================================================================
 
+/// How empty elements are written when the XML is saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmptyElementStyle {
+    /// `<item/>`
+    SelfClosing,
+    /// `<item></item>`
+    Expanded,
+}
+
+/// Value an element condition compares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionOperand {
+    /// The element's own trimmed text
+    Text,
+    /// An attribute's value; elements without it never satisfy the condition
+    Attribute(String),
+}
+
+/// Comparison in an element condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// Predicate on one element, parsed from e.g. `text > 1000`, `@status == 'open'`.
+/// Compares as numbers when both sides parse as numbers; otherwise only `==`/`!=`
+/// apply, as exact string comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementCondition {
+    pub operand: ConditionOperand,
+    pub comparison: Comparison,
+    pub value: String,
+}
+
+/// Element to create, e.g. one record of `append_records`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    /// Text content; `None` writes a self-closing element
+    pub text: Option<String>,
+}
+
+/// Change applied to every element satisfying a condition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConditionalOperation {
+    SetAttribute { name: String, value: String },
+    UpdateText(String),
+    Delete,
+}
+
 /// Represents an element in the XML structure
 #[derive(Debug, Clone)]
 pub struct XmlElement {
@@ -347,6 +956,8 @@ pub struct XmlElement {
     pub name: String,
     pub attributes: Vec<(String, String)>,
     pub text: Option<String>,
+    /// Whether the text came from a CDATA section
+    pub cdata: bool,
     pub depth: usize,
 }
 
@@ -369,23 +980,65 @@ impl XmlElement {
             .text
             .as_ref()
             .map(|t| {
-                let preview = if t.len() > 50 {
-                    format!("{}...", &t[..50])
-                } else {
-                    t.clone()
+                // Cut at a char boundary so multi-byte text can't split mid-character
+                let preview = match t.char_indices().nth(50) {
+                    Some((end, _)) => format!("{}...", &t[..end]),
+                    None => t.clone(),
                 };
                 format!(": \"{}\"", preview.replace('\n', "\\n"))
             })
             .unwrap_or_default();
 
-        format!("{}{attrs}{text_preview}", self.path)
+        let cdata = if self.cdata { " (CDATA)" } else { "" };
+
+        format!("{}{attrs}{cdata}{text_preview}", self.path)
     }
 }
 
+/// An element together with its surrounding structure
+#[derive(Debug, Clone)]
+pub struct ElementContext {
+    pub element: XmlElement,
+    pub parent: Option<XmlElement>,
+    pub preceding_sibling: Option<XmlElement>,
+    pub following_sibling: Option<XmlElement>,
+}
+
 // ============================================================================
 // LLM Types
 // ============================================================================
 
+/// How tool calls are read from model responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolFormat {
+    /// Structured `tool_calls` field only
+    Native,
+    /// `<tool_call>{...}</tool_call>` blocks in the message content only
+    Text,
+    /// Structured field, falling back to text blocks when it is absent
+    #[default]
+    Auto,
+}
+
+/// llama-server defaults for one model, from a `--model-config` file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelProfile {
+    pub gpu_layers: Option<u32>,
+    pub context_size: Option<u32>,
+    /// Passed to llama-server after the standard arguments
+    pub extra_args: Vec<String>,
+}
+
+/// Per-model llama-server defaults keyed by model filename, e.g.
+/// `{"models": {"qwen2.5-7b.gguf": {"gpu_layers": 20, "context_size": 8192}}}`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelConfig {
+    pub models: std::collections::HashMap<String, ModelProfile>,
+}
+
 /// Message in the conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -415,6 +1068,9 @@ pub struct ToolDefinition {
     #[serde(rename = "type")]
     pub tool_type: String,
     pub function: FunctionDefinition,
+    /// Tool never mutates state, so calls can run in parallel (not sent to the LLM)
+    #[serde(skip)]
+    pub read_only: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -446,3 +1102,16 @@ pub struct ToolResult {
     pub content: String,
 }
 
+/// One XML-changing tool call recorded with `--record-script`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptStep {
+    pub tool: String,
+    pub arguments: Value,
+}
+
+/// The XML edits of a modify run, replayable without the model via `z replay`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayScript {
+    pub steps: Vec<ScriptStep>,
+}
+
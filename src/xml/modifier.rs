@@ -1,44 +1,132 @@
-use crate::structs::{Result, XmlElement, ZError};
-use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use crate::structs::{
+    Comparison, ConditionOperand, ConditionalOperation, ElementCondition, ElementContext,
+    EmptyElementStyle, NewElement, Result, XmlElement, ZError,
+};
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesCData, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::{Reader, Writer};
-use std::cell::RefCell;
 use std::fs;
 use std::io::Cursor;
 use std::path::Path;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
 
 /// Size limits for LLM tool responses
 pub const MAX_XML_ELEMENTS: usize = 10;
+pub const MAX_XML_SOURCE: usize = 2000;
+
+/// Character encoding of the loaded document, restored when it is written back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceEncoding {
+    Utf8,
+    /// ISO-8859-1: one byte per char, U+0000 to U+00FF
+    Latin1,
+}
 
-/// XML modifier that can query and modify XML files
+/// XML modifier that can query and modify XML files.
+/// Content sits behind a lock so read-only tools can query it from several threads.
 pub struct XmlModifier {
-    content: RefCell<String>,
+    content: RwLock<String>,
+    empty_style: Option<EmptyElementStyle>,
+    encoding: SourceEncoding,
 }
 
 impl XmlModifier {
     /// Load XML from a file
     ///
     /// # Errors
-    /// Returns error if file cannot be read
+    /// Returns error if file cannot be read or is not valid in its declared encoding
     pub fn from_file(path: &Path) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Load XML from a file, refusing files larger than `max_bytes` before reading them.
+    /// The whole document is held in memory and copied by every edit.
+    ///
+    /// # Errors
+    /// Returns error if the file is too large, cannot be read, or is not valid in its
+    /// declared encoding
+    pub fn from_file_with_limit(path: &Path, max_bytes: u64) -> Result<Self> {
+        let size = fs::metadata(path)?.len();
+        if size > max_bytes {
+            return Err(ZError::Config(format!(
+                "XML file {} is {size} bytes, over the limit of {max_bytes} \
+                 (--max-xml-bytes); the whole document is held in memory",
+                path.display()
+            )));
+        }
+        Self::from_file(path)
+    }
+
+    /// Load XML from raw bytes, decoding ISO-8859-1 when the XML declaration names
+    /// it and UTF-8 otherwise. The declaration itself is kept verbatim.
+    ///
+    /// # Errors
+    /// Returns error if a document not declared as ISO-8859-1 is not valid UTF-8
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let (content, encoding) = if declared_encoding(&bytes) == SourceEncoding::Latin1 {
+            (bytes.iter().map(|&b| char::from(b)).collect(), SourceEncoding::Latin1)
+        } else {
+            let content = String::from_utf8(bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            (content, SourceEncoding::Utf8)
+        };
         Ok(Self {
-            content: RefCell::new(content),
+            content: RwLock::new(content),
+            empty_style: None,
+            encoding,
         })
     }
 
     /// Load XML from a string
-    #[allow(dead_code)]
+    #[cfg(test)]
     #[must_use]
     pub const fn from_string(content: String) -> Self {
         Self {
-            content: RefCell::new(content),
+            content: RwLock::new(content),
+            empty_style: None,
+            encoding: SourceEncoding::Utf8,
         }
     }
 
+    /// Size of the current content in bytes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.read_content().len()
+    }
+
+    /// Set the style used for empty elements when rendering output.
+    /// Without a style, every empty element keeps its original form.
+    #[must_use]
+    pub const fn with_empty_element_style(mut self, style: Option<EmptyElementStyle>) -> Self {
+        self.empty_style = style;
+        self
+    }
+
+    /// Lock the content for reading
+    fn read_content(&self) -> RwLockReadGuard<'_, String> {
+        self.content.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the content
+    fn set_content(&self, new_content: String) {
+        *self.content.write().unwrap_or_else(PoisonError::into_inner) = new_content;
+    }
+
     /// Get current XML content
     #[must_use]
     pub fn get_content(&self) -> String {
-        self.content.borrow().clone()
+        self.read_content().clone()
+    }
+
+    /// Render the current XML content for saving, applying the empty element style
+    ///
+    /// # Errors
+    /// Returns error if XML parsing fails
+    pub fn render(&self) -> Result<String> {
+        self.empty_style.map_or_else(
+            || Ok(self.get_content()),
+            |style| apply_empty_element_style(&self.read_content(), style),
+        )
     }
 
     /// Get the XML structure as a hierarchy
@@ -46,7 +134,7 @@ impl XmlModifier {
     /// # Errors
     /// Returns error if XML parsing fails
     pub fn get_structure(&self) -> Result<Vec<XmlElement>> {
-        let content = self.content.borrow();
+        let content = self.get_content();
         let mut reader = Reader::from_str(&content);
         reader.trim_text(true);
 
@@ -65,7 +153,7 @@ impl XmlModifier {
                         .filter_map(std::result::Result::ok)
                         .map(|a| {
                             let key = String::from_utf8_lossy(a.key.as_ref()).to_string();
-                            let value = String::from_utf8_lossy(&a.value).to_string();
+                            let value = attr_value(&a);
                             (key, value)
                         })
                         .collect();
@@ -75,6 +163,7 @@ impl XmlModifier {
                         name,
                         attributes,
                         text: None,
+                        cdata: false,
                         depth: path_stack.len() - 1,
                     });
                 }
@@ -86,6 +175,13 @@ impl XmlModifier {
                         }
                     }
                 }
+                Ok(Event::CData(e)) => {
+                    let text = String::from_utf8_lossy(&e).trim().to_string();
+                    if let Some(last) = elements.last_mut() {
+                        last.text = Some(text);
+                        last.cdata = true;
+                    }
+                }
                 Ok(Event::End(_)) => {
                     path_stack.pop();
                 }
@@ -99,7 +195,7 @@ impl XmlModifier {
                         .filter_map(std::result::Result::ok)
                         .map(|a| {
                             let key = String::from_utf8_lossy(a.key.as_ref()).to_string();
-                            let value = String::from_utf8_lossy(&a.value).to_string();
+                            let value = attr_value(&a);
                             (key, value)
                         })
                         .collect();
@@ -109,6 +205,7 @@ impl XmlModifier {
                         name,
                         attributes,
                         text: None,
+                        cdata: false,
                         depth: path_stack.len() - 1,
                     });
 
@@ -126,33 +223,90 @@ impl XmlModifier {
     /// Query elements matching a simplified path pattern
     /// Supports: `parent/child`, `element[@attr='value']`
     ///
+    /// Returns up to `limit` matches starting at match `offset`, together with
+    /// the total number of matches.
+    ///
     /// # Errors
     /// Returns error if XML parsing fails
-    pub fn query(&self, pattern: &str) -> Result<Vec<XmlElement>> {
+    pub fn query(
+        &self,
+        pattern: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<(Vec<XmlElement>, usize)> {
         let elements = self.get_structure()?;
 
         let (path_pattern, attr_filter) = parse_pattern(pattern);
 
-        let matched: Vec<XmlElement> = elements
+        let mut total = 0;
+        let mut matched = Vec::new();
+        for element in elements
             .into_iter()
-            .filter(|e| {
-                if !path_matches(&e.path, &e.name, &path_pattern) {
-                    return false;
-                }
+            .filter(|e| element_matches(e, &path_pattern, attr_filter.as_ref()))
+        {
+            if (offset..offset.saturating_add(limit)).contains(&total) {
+                matched.push(element);
+            }
+            total += 1;
+        }
 
-                // Match attribute filter if present
-                if let Some((attr_name, attr_value)) = &attr_filter {
-                    e.attributes
-                        .iter()
-                        .any(|(k, v)| k == attr_name && v == attr_value)
-                } else {
-                    true
-                }
-            })
-            .take(MAX_XML_ELEMENTS)
-            .collect();
+        Ok((matched, total))
+    }
+
+    /// All elements at exactly `depth` in document order (the root is depth 0)
+    ///
+    /// # Errors
+    /// Returns error if XML parsing fails
+    pub fn query_by_depth(&self, depth: usize) -> Result<Vec<XmlElement>> {
+        Ok(self
+            .get_structure()?
+            .into_iter()
+            .filter(|e| e.depth == depth)
+            .collect())
+    }
+
+    /// Get the first element matching a pattern with its parent and adjacent siblings
+    ///
+    /// # Errors
+    /// Returns error if XML parsing fails
+    pub fn element_context(&self, pattern: &str) -> Result<Option<ElementContext>> {
+        let elements = self.get_structure()?;
+        let (path_pattern, attr_filter) = parse_pattern(pattern);
+
+        let Some(idx) = elements
+            .iter()
+            .position(|e| element_matches(e, &path_pattern, attr_filter.as_ref()))
+        else {
+            return Ok(None);
+        };
+        let depth = elements[idx].depth;
+
+        // Walk backwards until we leave the parent: siblings share our depth,
+        // the first shallower element is the parent
+        let mut parent = None;
+        let mut preceding_sibling = None;
+        for e in elements[..idx].iter().rev() {
+            if e.depth < depth {
+                parent = Some(e.clone());
+                break;
+            }
+            if e.depth == depth && preceding_sibling.is_none() {
+                preceding_sibling = Some(e.clone());
+            }
+        }
 
-        Ok(matched)
+        let following_sibling = elements[idx + 1..]
+            .iter()
+            .take_while(|e| e.depth >= depth)
+            .find(|e| e.depth == depth)
+            .cloned();
+
+        Ok(Some(ElementContext {
+            element: elements[idx].clone(),
+            parent,
+            preceding_sibling,
+            following_sibling,
+        }))
     }
 
     /// Get a specific element by exact path
@@ -164,13 +318,113 @@ impl XmlModifier {
         Ok(elements.into_iter().find(|e| e.path == path))
     }
 
-    /// Update text content of an element matching the path
+    /// Value of `attr_name` on the first element matching the pattern, or `None`
+    /// when no element matches or the attribute is absent
+    ///
+    /// # Errors
+    /// Returns error if XML parsing fails
+    pub fn get_attribute(&self, path_pattern: &str, attr_name: &str) -> Result<Option<String>> {
+        let (mut elements, _) = self.query(path_pattern, 0, 1)?;
+        Ok(elements.pop().and_then(|e| {
+            e.attributes
+                .into_iter()
+                .find(|(name, _)| name == attr_name)
+                .map(|(_, value)| value)
+        }))
+    }
+
+    /// Verbatim source of the first element matching the pattern, from its start
+    /// tag through its end tag (children, comments and whitespace included)
+    ///
+    /// # Errors
+    /// Returns error if XML parsing fails
+    pub fn get_source(&self, path_pattern: &str) -> Result<Option<String>> {
+        let (path_pattern, attr_filter) = parse_pattern(path_pattern);
+        let content = self.read_content().clone();
+        let mut reader = Reader::from_str(&content);
+        reader.trim_text(false);
+
+        let mut path_stack: Vec<String> = Vec::new();
+        // (byte offset of the start tag, depth) of the matched element
+        let mut matched: Option<(usize, usize)> = None;
+
+        loop {
+            let event_start = reader.buffer_position();
+            match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    if matched.is_none()
+                        && path_matches(&path_stack.join("/"), &name, &path_pattern)
+                        && check_attr_filter(&e, attr_filter.as_ref())
+                    {
+                        matched = Some((event_start, path_stack.len()));
+                    }
+                }
+                Ok(Event::End(_)) => {
+                    if let Some((start, depth)) = matched {
+                        if depth == path_stack.len() {
+                            let end = reader.buffer_position();
+                            return Ok(Some(content[start..end].to_string()));
+                        }
+                    }
+                    path_stack.pop();
+                }
+                Ok(Event::Empty(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    if matched.is_none()
+                        && path_matches(&path_stack.join("/"), &name, &path_pattern)
+                        && check_attr_filter(&e, attr_filter.as_ref())
+                    {
+                        let end = reader.buffer_position();
+                        return Ok(Some(content[event_start..end].to_string()));
+                    }
+                    path_stack.pop();
+                }
+                Ok(Event::Eof) => return Ok(None),
+                Err(e) => return Err(ZError::Xml(e)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Update text content of an element matching the path.
+    /// Existing CDATA content is replaced with a new CDATA section.
     ///
     /// # Errors
     /// Returns error if XML parsing or modification fails
     pub fn update_text(&self, path_pattern: &str, new_text: &str) -> Result<bool> {
+        self.replace_text(path_pattern, new_text, false)
+    }
+
+    /// Set the content of an element matching the path as a CDATA section,
+    /// so markup characters are kept verbatim instead of escaped
+    ///
+    /// # Errors
+    /// Returns error if the text contains `]]>` or XML parsing or modification fails
+    pub fn set_cdata(&self, path_pattern: &str, new_text: &str) -> Result<bool> {
+        if new_text.contains("]]>") {
+            return Err(ZError::Config("CDATA content cannot contain ']]>'".into()));
+        }
+        self.replace_text(path_pattern, new_text, true)
+    }
+
+    /// Replace the text of the first matching element, writing CDATA when
+    /// `as_cdata` is set or the existing content is CDATA
+    fn replace_text(&self, path_pattern: &str, new_text: &str, as_cdata: bool) -> Result<bool> {
+        let new_event = |cdata: bool| {
+            if cdata {
+                Event::CData(BytesCData::new(new_text))
+            } else {
+                Event::Text(BytesText::new(new_text))
+            }
+        };
+
         let (path_pattern, attr_filter) = parse_pattern(path_pattern);
-        let content = self.content.borrow().clone();
+        let content = self.read_content().clone();
         let mut reader = Reader::from_str(&content);
         reader.trim_text(false);
 
@@ -194,16 +448,24 @@ impl XmlModifier {
                 }
                 Ok(Event::Text(e)) => {
                     if in_target && !modified {
-                        writer.write_event(Event::Text(BytesText::new(new_text)))?;
+                        writer.write_event(new_event(as_cdata))?;
                         modified = true;
                     } else {
                         writer.write_event(Event::Text(e))?;
                     }
                 }
+                Ok(Event::CData(e)) => {
+                    if in_target && !modified {
+                        writer.write_event(new_event(true))?;
+                        modified = true;
+                    } else {
+                        writer.write_event(Event::CData(e))?;
+                    }
+                }
                 Ok(Event::End(e)) => {
                     // If we were in target but never saw text, insert it
                     if in_target && !modified {
-                        writer.write_event(Event::Text(BytesText::new(new_text)))?;
+                        writer.write_event(new_event(as_cdata))?;
                         modified = true;
                     }
                     in_target = false;
@@ -218,7 +480,7 @@ impl XmlModifier {
 
         if modified {
             let new_content = finish_writer(writer)?;
-            *self.content.borrow_mut() = new_content;
+            self.set_content(new_content);
         }
 
         Ok(modified)
@@ -235,7 +497,7 @@ impl XmlModifier {
         attr_value: &str,
     ) -> Result<bool> {
         let (path_pattern, existing_filter) = parse_pattern(path_pattern);
-        let content = self.content.borrow().clone();
+        let content = self.read_content().clone();
         let mut reader = Reader::from_str(&content);
         reader.trim_text(false);
 
@@ -293,7 +555,82 @@ impl XmlModifier {
 
         if modified {
             let new_content = finish_writer(writer)?;
-            *self.content.borrow_mut() = new_content;
+            self.set_content(new_content);
+        }
+
+        Ok(modified)
+    }
+
+    /// Set several attributes on an element matching the path.
+    /// With `replace`, existing attributes are discarded and only `attributes` are written;
+    /// otherwise each attribute is merged as in `set_attribute`.
+    ///
+    /// # Errors
+    /// Returns error if XML parsing or modification fails
+    pub fn set_attributes(
+        &self,
+        path_pattern: &str,
+        attributes: &[(String, String)],
+        replace: bool,
+    ) -> Result<bool> {
+        let (path_pattern, existing_filter) = parse_pattern(path_pattern);
+        let content = self.read_content().clone();
+        let mut reader = Reader::from_str(&content);
+        reader.trim_text(false);
+
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        let mut path_stack: Vec<String> = Vec::new();
+        let mut modified = false;
+
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    let current_path = path_stack.join("/");
+                    let matches_path = path_matches(&current_path, &name, &path_pattern);
+                    let attr_matches = check_attr_filter(&e, existing_filter.as_ref());
+
+                    if matches_path && attr_matches && !modified {
+                        let new_elem = build_element_with_attrs(&e, &name, attributes, replace);
+                        writer.write_event(Event::Start(new_elem))?;
+                        modified = true;
+                    } else {
+                        writer.write_event(Event::Start(e))?;
+                    }
+                }
+                Ok(Event::End(e)) => {
+                    path_stack.pop();
+                    writer.write_event(Event::End(e))?;
+                }
+                Ok(Event::Empty(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    let current_path = path_stack.join("/");
+                    let matches_path = path_matches(&current_path, &name, &path_pattern);
+                    let attr_matches = check_attr_filter(&e, existing_filter.as_ref());
+
+                    if matches_path && attr_matches && !modified {
+                        let new_elem = build_element_with_attrs(&e, &name, attributes, replace);
+                        writer.write_event(Event::Empty(new_elem))?;
+                        modified = true;
+                    } else {
+                        writer.write_event(Event::Empty(e))?;
+                    }
+
+                    path_stack.pop();
+                }
+                Ok(Event::Eof) => break,
+                Ok(e) => writer.write_event(e)?,
+                Err(e) => return Err(ZError::Xml(e)),
+            }
+        }
+
+        if modified {
+            let new_content = finish_writer(writer)?;
+            self.set_content(new_content);
         }
 
         Ok(modified)
@@ -305,7 +642,7 @@ impl XmlModifier {
     /// Returns error if XML parsing or modification fails
     pub fn delete_element(&self, path_pattern: &str) -> Result<bool> {
         let (path_pattern, attr_filter) = parse_pattern(path_pattern);
-        let content = self.content.borrow().clone();
+        let content = self.read_content().clone();
         let mut reader = Reader::from_str(&content);
         reader.trim_text(false);
 
@@ -384,105 +721,561 @@ impl XmlModifier {
 
         if modified {
             let new_content = finish_writer(writer)?;
-            *self.content.borrow_mut() = new_content;
+            self.set_content(new_content);
         }
 
         Ok(modified)
     }
 
-    /// Insert a new element as a child of the matching parent
+    /// Apply `operation` to every element matching the path that satisfies
+    /// `condition`, returning how many were changed
     ///
     /// # Errors
-    /// Returns error if XML parsing or modification fails
-    pub fn insert_element(
+    /// Returns error if XML parsing or modification fails, or `update_text` selects an
+    /// element with child elements, whose text it can't replace without making mixed content
+    #[allow(clippy::too_many_lines)]
+    pub fn conditional_modify(
         &self,
-        parent_pattern: &str,
-        element_name: &str,
-        attributes: &[(String, String)],
-        text: Option<&str>,
-    ) -> Result<bool> {
-        let (path_pattern, attr_filter) = parse_pattern(parent_pattern);
-        let content = self.content.borrow().clone();
+        path_pattern: &str,
+        condition: &ElementCondition,
+        operation: &ConditionalOperation,
+    ) -> Result<usize> {
+        let (path_pattern, attr_filter) = parse_pattern(path_pattern);
+        let selected =
+            self.condition_matches(&path_pattern, attr_filter.as_ref(), condition, operation)?;
+        if selected.is_empty() {
+            return Ok(0);
+        }
+
+        let content = self.read_content().clone();
         let mut reader = Reader::from_str(&content);
         reader.trim_text(false);
 
         let mut writer = Writer::new(Cursor::new(Vec::new()));
         let mut path_stack: Vec<String> = Vec::new();
-        let mut modified = false;
-        let mut target_depth: Option<usize> = None;
+        let mut match_index = 0;
+        let mut modified = 0;
+        let mut skip_depth: Option<usize> = None;
+        // New text still to be written for the selected element being copied
+        let mut pending_text: Option<&str> = None;
+        let mut is_selected = |e: &BytesStart<'_>, path_stack: &[String], name: &str| {
+            let current_path = path_stack.join("/");
+            if !path_matches(&current_path, name, &path_pattern)
+                || !check_attr_filter(e, attr_filter.as_ref())
+            {
+                return false;
+            }
+            match_index += 1;
+            selected.contains(&(match_index - 1))
+        };
 
         loop {
             match reader.read_event() {
                 Ok(Event::Start(e)) => {
                     let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
                     path_stack.push(name.clone());
-
-                    let current_path = path_stack.join("/");
-                    let matches_path = path_matches(&current_path, &name, &path_pattern);
-                    let attr_matches = check_attr_filter(&e, attr_filter.as_ref());
-
-                    if matches_path && attr_matches && !modified {
-                        target_depth = Some(path_stack.len());
+                    // Count matches inside a deleted element too, to keep the numbering
+                    let hit = is_selected(&e, &path_stack, &name);
+                    if skip_depth.is_some() {
+                        continue;
                     }
-
-                    writer.write_event(Event::Start(e))?;
-                }
-                Ok(Event::End(e)) => {
-                    let depth = path_stack.len();
-
-                    // Insert before closing the target element
-                    if target_depth == Some(depth) && !modified {
-                        write_new_element(&mut writer, element_name, attributes, text)?;
-                        modified = true;
-                        target_depth = None;
+                    pending_text = match operation {
+                        ConditionalOperation::UpdateText(text) if hit => Some(text),
+                        _ => None,
+                    };
+                    if !hit {
+                        writer.write_event(Event::Start(e))?;
+                    } else if let ConditionalOperation::SetAttribute { name: attr, value } =
+                        operation
+                    {
+                        let new_elem = build_element_with_attr(&e, &name, attr, value);
+                        writer.write_event(Event::Start(new_elem))?;
+                    } else if pending_text.is_some() {
+                        writer.write_event(Event::Start(e))?;
+                    } else {
+                        skip_depth = Some(path_stack.len());
                     }
-
-                    path_stack.pop();
-                    writer.write_event(Event::End(e))?;
+                    modified += usize::from(hit);
                 }
                 Ok(Event::Empty(e)) => {
                     let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
-
-                    // For empty parent elements, expand them
-                    let current_path = format!("{}/{name}", path_stack.join("/"));
-                    let matches_path = path_matches(&current_path, &name, &path_pattern);
-                    let attr_matches = check_attr_filter(&e, attr_filter.as_ref());
-
-                    if matches_path && attr_matches && !modified {
-                        // Convert empty to start tag
-                        let start = BytesStart::new(&name);
-                        writer.write_event(Event::Start(start))?;
-
-                        // Add new element
-                        write_new_element(&mut writer, element_name, attributes, text)?;
-
-                        writer.write_event(Event::End(BytesEnd::new(&name)))?;
-                        modified = true;
+                    path_stack.push(name.clone());
+                    let hit = is_selected(&e, &path_stack, &name);
+                    path_stack.pop();
+                    if skip_depth.is_some() {
+                        continue;
+                    }
+                    if hit {
+                        write_modified_empty(&mut writer, e, &name, operation)?;
+                        modified += 1;
                     } else {
                         writer.write_event(Event::Empty(e))?;
                     }
                 }
+                Ok(event @ (Event::Text(_) | Event::CData(_))) if skip_depth.is_none() => {
+                    match (pending_text.take(), event) {
+                        (Some(text), Event::CData(_)) => {
+                            writer.write_event(Event::CData(BytesCData::new(text)))?;
+                        }
+                        (Some(text), _) => writer.write_event(Event::Text(BytesText::new(text)))?,
+                        (None, event) => writer.write_event(event)?,
+                    }
+                }
+                Ok(Event::End(e)) => {
+                    let depth = path_stack.len();
+                    path_stack.pop();
+                    if let Some(skip_at) = skip_depth {
+                        if depth == skip_at {
+                            skip_depth = None;
+                        }
+                        continue;
+                    }
+                    // A selected element that had no text gets it before its end tag
+                    if let Some(text) = pending_text.take() {
+                        writer.write_event(Event::Text(BytesText::new(text)))?;
+                    }
+                    writer.write_event(Event::End(e))?;
+                }
                 Ok(Event::Eof) => break,
-                Ok(e) => writer.write_event(e)?,
+                Ok(e) if skip_depth.is_none() => writer.write_event(e)?,
+                Ok(_) => {}
                 Err(e) => return Err(ZError::Xml(e)),
             }
         }
 
-        if modified {
+        if modified > 0 {
             let new_content = finish_writer(writer)?;
-            *self.content.borrow_mut() = new_content;
+            self.set_content(new_content);
         }
 
         Ok(modified)
     }
 
-    /// Write to a file atomically (write to .tmp, then rename)
-    ///
-    /// # Errors
-    /// Returns error if file operations fail
-    pub fn write_to_file(content: &str, path: &Path) -> Result<()> {
-        let tmp_path = path.with_extension("xml.tmp");
-        fs::write(&tmp_path, content)?;
+    /// Ordinals, in document order, of the elements matching the path that satisfy
+    /// `condition`. Under `update_text`, a selected element with child elements is an
+    /// error: its text can't be replaced without making mixed content.
+    fn condition_matches(
+        &self,
+        path_pattern: &str,
+        attr_filter: Option<&(String, String)>,
+        condition: &ElementCondition,
+        operation: &ConditionalOperation,
+    ) -> Result<std::collections::HashSet<usize>> {
+        let structure = self.get_structure()?;
+        let matching = structure
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| element_matches(e, path_pattern, attr_filter));
+        let mut selected = std::collections::HashSet::new();
+        for (ordinal, (i, element)) in matching.enumerate() {
+            if !condition.matches(element) {
+                continue;
+            }
+            // Elements are in document order, so a child directly follows its parent
+            let has_children = structure.get(i + 1).is_some_and(|n| n.depth > element.depth);
+            if has_children && matches!(operation, ConditionalOperation::UpdateText(_)) {
+                return Err(ZError::Config(format!(
+                    "Cannot update the text of {}: it has child elements",
+                    element.path
+                )));
+            }
+            selected.insert(ordinal);
+        }
+        Ok(selected)
+    }
+
+    /// Rename the first element matching the path, keeping its attributes and
+    /// everything inside it unchanged
+    ///
+    /// # Errors
+    /// Returns error if the new name is invalid or XML parsing fails
+    pub fn rename_element(&self, path_pattern: &str, new_name: &str) -> Result<bool> {
+        if !is_valid_element_name(new_name) {
+            return Err(ZError::Config(format!("Invalid element name: {new_name:?}")));
+        }
+
+        let (path_pattern, attr_filter) = parse_pattern(path_pattern);
+        let content = self.read_content().clone();
+        let mut reader = Reader::from_str(&content);
+        reader.trim_text(false);
+
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        let mut path_stack: Vec<String> = Vec::new();
+        let mut modified = false;
+        let mut rename_depth: Option<usize> = None;
+
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(mut e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    let current_path = path_stack.join("/");
+                    if !modified
+                        && path_matches(&current_path, &name, &path_pattern)
+                        && check_attr_filter(&e, attr_filter.as_ref())
+                    {
+                        e.set_name(new_name.as_bytes());
+                        rename_depth = Some(path_stack.len());
+                        modified = true;
+                    }
+                    writer.write_event(Event::Start(e))?;
+                }
+                Ok(Event::End(e)) => {
+                    // The matching end tag takes the new name too
+                    if rename_depth == Some(path_stack.len()) {
+                        rename_depth = None;
+                        writer.write_event(Event::End(BytesEnd::new(new_name)))?;
+                    } else {
+                        writer.write_event(Event::End(e))?;
+                    }
+                    path_stack.pop();
+                }
+                Ok(Event::Empty(mut e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    let current_path = path_stack.join("/");
+                    if !modified
+                        && path_matches(&current_path, &name, &path_pattern)
+                        && check_attr_filter(&e, attr_filter.as_ref())
+                    {
+                        e.set_name(new_name.as_bytes());
+                        modified = true;
+                    }
+                    writer.write_event(Event::Empty(e))?;
+                    path_stack.pop();
+                }
+                Ok(Event::Eof) => break,
+                Ok(e) => writer.write_event(e)?,
+                Err(e) => return Err(ZError::Xml(e)),
+            }
+        }
+
+        if modified {
+            let new_content = finish_writer(writer)?;
+            self.set_content(new_content);
+        }
+
+        Ok(modified)
+    }
+
+    /// Wrap the first matching element (or every match with `all`) in a new
+    /// `wrapper_name` element. With `group`, consecutive matching siblings
+    /// (separated only by whitespace) share one wrapper instead of getting one each.
+    /// Matches nested inside an element being wrapped are left alone.
+    ///
+    /// Returns the number of elements wrapped.
+    ///
+    /// # Errors
+    /// Returns error if the wrapper name is invalid, XML parsing fails, or the
+    /// result is not well-formed
+    pub fn wrap_elements(
+        &self,
+        path_pattern: &str,
+        wrapper_name: &str,
+        wrapper_attrs: &[(String, String)],
+        all: bool,
+        group: bool,
+    ) -> Result<usize> {
+        if !is_valid_element_name(wrapper_name) {
+            return Err(ZError::Config(format!("Invalid element name: {wrapper_name:?}")));
+        }
+
+        let (path_pattern, attr_filter) = parse_pattern(path_pattern);
+        let content = self.read_content().clone();
+        let mut reader = Reader::from_str(&content);
+        reader.trim_text(false);
+
+        let mut wrapper = BytesStart::new(wrapper_name);
+        for (key, val) in wrapper_attrs {
+            push_escaped_attr(&mut wrapper, key, val);
+        }
+
+        // Whether an element about to be pushed under `stack` is a wrap target
+        let is_target = |e: &BytesStart<'_>, stack: &[String]| {
+            let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+            let mut current_path = stack.join("/");
+            if !current_path.is_empty() {
+                current_path.push('/');
+            }
+            current_path.push_str(&name);
+            path_matches(&current_path, &name, &path_pattern)
+                && check_attr_filter(e, attr_filter.as_ref())
+        };
+
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        let mut path_stack: Vec<String> = Vec::new();
+        let mut wrap_count = 0;
+        // Depth of the matched element currently being copied into a wrapper
+        let mut in_match: Option<usize> = None;
+        // A group wrapper is open, waiting to see whether the next sibling also matches
+        let mut open_group = false;
+        // Whitespace after a grouped element, held until we know where the wrapper closes
+        let mut pending: Vec<Event<'static>> = Vec::new();
+
+        loop {
+            let event = reader.read_event()?;
+
+            if open_group && in_match.is_none() {
+                match &event {
+                    Event::Text(t) if t.iter().all(u8::is_ascii_whitespace) => {
+                        pending.push(event.into_owned());
+                        continue;
+                    }
+                    Event::Start(e) | Event::Empty(e) if is_target(e, &path_stack) => {}
+                    _ => {
+                        writer.write_event(Event::End(BytesEnd::new(wrapper_name)))?;
+                        open_group = false;
+                    }
+                }
+                for pending_event in std::mem::take(&mut pending) {
+                    writer.write_event(pending_event)?;
+                }
+            }
+
+            // Without `all`, only the first element - or the first group - is wrapped
+            let may_wrap = in_match.is_none() && (all || wrap_count == 0 || open_group);
+            match event {
+                Event::Start(e) => {
+                    let matched = may_wrap && is_target(&e, &path_stack);
+                    path_stack.push(String::from_utf8_lossy(e.name().as_ref()).to_string());
+                    if matched {
+                        if !open_group {
+                            writer.write_event(Event::Start(wrapper.borrow()))?;
+                            open_group = group;
+                        }
+                        in_match = Some(path_stack.len());
+                        wrap_count += 1;
+                    }
+                    writer.write_event(Event::Start(e))?;
+                }
+                Event::Empty(e) => {
+                    let matched = may_wrap && is_target(&e, &path_stack);
+                    if matched {
+                        if !open_group {
+                            writer.write_event(Event::Start(wrapper.borrow()))?;
+                            open_group = group;
+                        }
+                        wrap_count += 1;
+                    }
+                    writer.write_event(Event::Empty(e))?;
+                    if matched && !group {
+                        writer.write_event(Event::End(BytesEnd::new(wrapper_name)))?;
+                    }
+                }
+                Event::End(e) => {
+                    let depth = path_stack.len();
+                    path_stack.pop();
+                    writer.write_event(Event::End(e))?;
+
+                    if in_match == Some(depth) {
+                        in_match = None;
+                        if !group {
+                            writer.write_event(Event::End(BytesEnd::new(wrapper_name)))?;
+                        }
+                    }
+                }
+                Event::Eof => break,
+                e => writer.write_event(e)?,
+            }
+        }
+
+        if wrap_count > 0 {
+            let new_content = finish_writer(writer)?;
+
+            // Re-parse to make sure the wrappers nest correctly
+            let mut check = Reader::from_str(&new_content);
+            while !matches!(check.read_event()?, Event::Eof) {}
+
+            self.set_content(new_content);
+        }
+
+        Ok(wrap_count)
+    }
+
+    /// Insert a new element as a child of the matching parent
+    ///
+    /// # Errors
+    /// Returns error if XML parsing or modification fails
+    pub fn insert_element(
+        &self,
+        parent_pattern: &str,
+        element_name: &str,
+        attributes: &[(String, String)],
+        text: Option<&str>,
+    ) -> Result<bool> {
+        self.insert_children(parent_pattern, |writer| {
+            write_new_element(writer, element_name, attributes, text)
+        })
+    }
+
+    /// Append `records` in order as the last children of the first matching parent,
+    /// rewriting the document once for the whole batch
+    ///
+    /// # Errors
+    /// Returns error if XML parsing or modification fails
+    pub fn append_records(&self, parent_pattern: &str, records: &[NewElement]) -> Result<bool> {
+        if records.is_empty() {
+            return Ok(false);
+        }
+        self.insert_children(parent_pattern, |writer| {
+            for record in records {
+                writer.write_event(Event::Text(BytesText::new("\n    ")))?;
+                write_element(writer, &record.name, &record.attributes, record.text.as_deref())?;
+            }
+            writer.write_event(Event::Text(BytesText::new("\n  ")))?;
+            Ok(())
+        })
+    }
+
+    /// Call `write_children` just before the end of the first element matching the
+    /// parent pattern (expanding it if self-closing)
+    fn insert_children(
+        &self,
+        parent_pattern: &str,
+        write_children: impl Fn(&mut Writer<Cursor<Vec<u8>>>) -> Result<()>,
+    ) -> Result<bool> {
+        let (path_pattern, attr_filter) = parse_pattern(parent_pattern);
+        let content = self.read_content().clone();
+        let mut reader = Reader::from_str(&content);
+        reader.trim_text(false);
+
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        let mut path_stack: Vec<String> = Vec::new();
+        let mut modified = false;
+        let mut target_depth: Option<usize> = None;
+
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                    path_stack.push(name.clone());
+
+                    let current_path = path_stack.join("/");
+                    let matches_path = path_matches(&current_path, &name, &path_pattern);
+                    let attr_matches = check_attr_filter(&e, attr_filter.as_ref());
+
+                    if matches_path && attr_matches && !modified {
+                        target_depth = Some(path_stack.len());
+                    }
+
+                    writer.write_event(Event::Start(e))?;
+                }
+                Ok(Event::End(e)) => {
+                    let depth = path_stack.len();
+
+                    // Insert before closing the target element
+                    if target_depth == Some(depth) && !modified {
+                        write_children(&mut writer)?;
+                        modified = true;
+                        target_depth = None;
+                    }
+
+                    path_stack.pop();
+                    writer.write_event(Event::End(e))?;
+                }
+                Ok(Event::Empty(e)) => {
+                    let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+
+                    // For empty parent elements, expand them
+                    let current_path = format!("{}/{name}", path_stack.join("/"));
+                    let matches_path = path_matches(&current_path, &name, &path_pattern);
+                    let attr_matches = check_attr_filter(&e, attr_filter.as_ref());
+
+                    if matches_path && attr_matches && !modified {
+                        // Convert empty to start tag, keeping its attributes
+                        writer.write_event(Event::Start(e))?;
+
+                        write_children(&mut writer)?;
+
+                        writer.write_event(Event::End(BytesEnd::new(&name)))?;
+                        modified = true;
+                    } else {
+                        writer.write_event(Event::Empty(e))?;
+                    }
+                }
+                Ok(Event::Eof) => break,
+                Ok(e) => writer.write_event(e)?,
+                Err(e) => return Err(ZError::Xml(e)),
+            }
+        }
+
+        if modified {
+            let new_content = finish_writer(writer)?;
+            self.set_content(new_content);
+        }
+
+        Ok(modified)
+    }
+
+    /// Re-emit the XML with consistent indentation. `indent` is one level of
+    /// indentation and must repeat a single space or tab character.
+    /// Whitespace-only text between elements is dropped; text, comments and CDATA are kept.
+    ///
+    /// # Errors
+    /// Returns error if the indent is invalid or XML parsing fails
+    pub fn reformat(&self, indent: &str) -> Result<()> {
+        let indent_char = match indent.as_bytes().first() {
+            None => b' ',
+            Some(&c) if (c == b' ' || c == b'\t') && indent.bytes().all(|b| b == c) => c,
+            Some(_) => {
+                return Err(ZError::Config(format!(
+                    "Indent must be spaces or tabs only: {indent:?}"
+                )))
+            }
+        };
+
+        let content = self.get_content();
+        let mut reader = Reader::from_str(&content);
+        reader.trim_text(false);
+
+        let mut writer =
+            Writer::new_with_indent(Cursor::new(Vec::new()), indent_char, indent.len());
+
+        loop {
+            match reader.read_event() {
+                Ok(Event::Text(e)) if e.iter().all(u8::is_ascii_whitespace) => {}
+                Ok(Event::Eof) => break,
+                Ok(e) => writer.write_event(e)?,
+                Err(e) => return Err(ZError::Xml(e)),
+            }
+        }
+
+        let mut new_content = finish_writer(writer)?;
+        if content.ends_with('\n') {
+            new_content.push('\n');
+        }
+        self.set_content(new_content);
+        Ok(())
+    }
+
+    /// Encode rendered content in the encoding the document was loaded in. For an
+    /// ISO-8859-1 document, characters beyond U+00FF become numeric character references.
+    #[must_use]
+    pub fn encode(&self, content: &str) -> Vec<u8> {
+        match self.encoding {
+            SourceEncoding::Utf8 => content.as_bytes().to_vec(),
+            SourceEncoding::Latin1 => {
+                let mut bytes = Vec::with_capacity(content.len());
+                for c in content.chars() {
+                    match u8::try_from(u32::from(c)) {
+                        Ok(b) => bytes.push(b),
+                        Err(_) => bytes.extend(format!("&#{};", u32::from(c)).bytes()),
+                    }
+                }
+                bytes
+            }
+        }
+    }
+
+    /// Write to a file atomically (write to .tmp, then rename)
+    ///
+    /// # Errors
+    /// Returns error if file operations fail
+    pub fn write_to_file(content: &[u8], path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("xml.tmp");
+        fs::write(&tmp_path, content)?;
         fs::rename(&tmp_path, path)?;
         Ok(())
     }
@@ -497,6 +1290,26 @@ fn path_matches(current_path: &str, name: &str, pattern: &str) -> bool {
     }
 }
 
+/// Check if a parsed element matches a path pattern and optional attribute filter
+fn element_matches(
+    e: &XmlElement,
+    path_pattern: &str,
+    attr_filter: Option<&(String, String)>,
+) -> bool {
+    if !path_matches(&e.path, &e.name, path_pattern) {
+        return false;
+    }
+
+    // Match attribute filter if present
+    if let Some((attr_name, attr_value)) = attr_filter {
+        e.attributes
+            .iter()
+            .any(|(k, v)| k == attr_name && v == attr_value)
+    } else {
+        true
+    }
+}
+
 /// Parse a path pattern like `element[@attr='value']`
 fn parse_pattern(pattern: &str) -> (String, Option<(String, String)>) {
     if let Some(bracket_start) = pattern.find("[@") {
@@ -518,6 +1331,25 @@ fn parse_pattern(pattern: &str) -> (String, Option<(String, String)>) {
     (pattern.to_string(), None)
 }
 
+/// An attribute's value with entity and character references resolved
+fn attr_value(attr: &Attribute<'_>) -> String {
+    attr.unescape_value().map_or_else(
+        |_| String::from_utf8_lossy(&attr.value).to_string(),
+        std::borrow::Cow::into_owned,
+    )
+}
+
+/// Add an attribute, escaping the value so it reads back exactly: markup characters
+/// become entities and whitespace other than spaces becomes character references,
+/// which a parser would otherwise normalize to spaces
+fn push_escaped_attr(elem: &mut BytesStart<'_>, key: &str, value: &str) {
+    let escaped = quick_xml::escape::escape(value)
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
+        .replace('\t', "&#9;");
+    elem.push_attribute((key.as_bytes(), escaped.as_bytes()));
+}
+
 /// Check if element matches the attribute filter
 fn check_attr_filter(e: &BytesStart<'_>, filter: Option<&(String, String)>) -> bool {
     if let Some((filter_name, filter_value)) = filter {
@@ -525,8 +1357,7 @@ fn check_attr_filter(e: &BytesStart<'_>, filter: Option<&(String, String)>) -> b
             .filter_map(std::result::Result::ok)
             .any(|a| {
                 let key = String::from_utf8_lossy(a.key.as_ref());
-                let val = String::from_utf8_lossy(&a.value);
-                key == *filter_name && val == *filter_value
+                key == *filter_name && attr_value(&a) == *filter_value
             })
     } else {
         true
@@ -546,7 +1377,7 @@ fn build_element_with_attr<'a>(
     for attr in original.attributes().filter_map(std::result::Result::ok) {
         let key = String::from_utf8_lossy(attr.key.as_ref());
         if key == attr_name {
-            new_elem.push_attribute((attr_name, attr_value));
+            push_escaped_attr(&mut new_elem, attr_name, attr_value);
             found_attr = true;
         } else {
             new_elem.push_attribute(attr);
@@ -554,12 +1385,55 @@ fn build_element_with_attr<'a>(
     }
 
     if !found_attr {
-        new_elem.push_attribute((attr_name, attr_value));
+        push_escaped_attr(&mut new_elem, attr_name, attr_value);
+    }
+
+    new_elem
+}
+
+/// Build a new element with a set of attributes, either merged into or replacing the originals
+fn build_element_with_attrs<'a>(
+    original: &BytesStart<'_>,
+    name: &'a str,
+    attributes: &[(String, String)],
+    replace: bool,
+) -> BytesStart<'a> {
+    let mut new_elem = BytesStart::new(name);
+
+    if !replace {
+        for attr in original.attributes().filter_map(std::result::Result::ok) {
+            let key = String::from_utf8_lossy(attr.key.as_ref());
+            if let Some((_, value)) = attributes.iter().find(|(k, _)| *k == key) {
+                push_escaped_attr(&mut new_elem, &key, value);
+            } else {
+                new_elem.push_attribute(attr);
+            }
+        }
+    }
+
+    for (key, value) in attributes {
+        let already_written = !replace
+            && original
+                .attributes()
+                .filter_map(std::result::Result::ok)
+                .any(|a| a.key.as_ref() == key.as_bytes());
+        if !already_written {
+            push_escaped_attr(&mut new_elem, key, value);
+        }
     }
 
     new_elem
 }
 
+/// Whether `name` can be used as an XML element name
+fn is_valid_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))
+}
+
 /// Write a new element to the writer
 fn write_new_element<W: std::io::Write>(
     writer: &mut Writer<W>,
@@ -568,10 +1442,21 @@ fn write_new_element<W: std::io::Write>(
     text: Option<&str>,
 ) -> Result<()> {
     writer.write_event(Event::Text(BytesText::new("\n    ")))?;
+    write_element(writer, element_name, attributes, text)?;
+    writer.write_event(Event::Text(BytesText::new("\n  ")))?;
+    Ok(())
+}
 
+/// Write one element with its attributes and optional text, self-closing without text
+fn write_element<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    element_name: &str,
+    attributes: &[(String, String)],
+    text: Option<&str>,
+) -> Result<()> {
     let mut elem = BytesStart::new(element_name);
     for (key, val) in attributes {
-        elem.push_attribute((key.as_str(), val.as_str()));
+        push_escaped_attr(&mut elem, key, val);
     }
 
     if let Some(txt) = text {
@@ -581,11 +1466,172 @@ fn write_new_element<W: std::io::Write>(
     } else {
         writer.write_event(Event::Empty(elem))?;
     }
+    Ok(())
+}
 
-    writer.write_event(Event::Text(BytesText::new("\n  ")))?;
+/// Re-emit every empty element in the given style
+fn apply_empty_element_style(content: &str, style: EmptyElementStyle) -> Result<String> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(false);
+    reader.expand_empty_elements(style == EmptyElementStyle::Expanded);
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut pending: Option<BytesStart<'_>> = None;
+
+    loop {
+        let event = reader.read_event()?;
+
+        // A start tag immediately followed by its end tag collapses to `<name/>`
+        if let Some(start) = pending.take() {
+            if matches!(event, Event::End(_)) {
+                writer.write_event(Event::Empty(start))?;
+                continue;
+            }
+            writer.write_event(Event::Start(start))?;
+        }
+
+        match event {
+            Event::Start(e) if style == EmptyElementStyle::SelfClosing => pending = Some(e),
+            Event::Eof => break,
+            e => writer.write_event(e)?,
+        }
+    }
+
+    finish_writer(writer)
+}
+
+/// Encoding named by the XML declaration at the start of `bytes`
+fn declared_encoding(bytes: &[u8]) -> SourceEncoding {
+    let mut reader = Reader::from_reader(bytes);
+    let mut buf = Vec::new();
+    let Ok(Event::Decl(decl)) = reader.read_event_into(&mut buf) else {
+        return SourceEncoding::Utf8;
+    };
+    match decl.encoding() {
+        Some(Ok(name)) if LATIN1_LABELS.iter().any(|l| l.eq_ignore_ascii_case(&name)) => {
+            SourceEncoding::Latin1
+        }
+        _ => SourceEncoding::Utf8,
+    }
+}
+
+/// `encoding` values read as ISO-8859-1
+const LATIN1_LABELS: &[&[u8]] = &[b"ISO-8859-1", b"ISO_8859-1", b"ISO8859-1", b"LATIN1", b"L1"];
+
+/// Write a self-closing element selected by `conditional_modify` with `operation` applied
+fn write_modified_empty(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    e: BytesStart<'_>,
+    name: &str,
+    operation: &ConditionalOperation,
+) -> Result<()> {
+    match operation {
+        ConditionalOperation::SetAttribute { name: attr, value } => {
+            writer.write_event(Event::Empty(build_element_with_attr(&e, name, attr, value)))?;
+        }
+        ConditionalOperation::UpdateText(text) => {
+            writer.write_event(Event::Start(e))?;
+            writer.write_event(Event::Text(BytesText::new(text)))?;
+            writer.write_event(Event::End(BytesEnd::new(name)))?;
+        }
+        ConditionalOperation::Delete => {}
+    }
     Ok(())
 }
 
+impl ElementCondition {
+    /// Parse `text <op> value` or `@attr <op> value`, where `<op>` is one of
+    /// `<`, `<=`, `>`, `>=`, `==` (or `=`), `!=`; quotes around the value are dropped
+    ///
+    /// # Errors
+    /// Returns error if the operand, operator or value is missing
+    pub fn parse(condition: &str) -> Result<Self> {
+        let invalid = || {
+            ZError::Config(format!(
+                "Invalid condition {condition:?}; expected e.g. \"text > 1000\" or \
+                 \"@status == 'open'\""
+            ))
+        };
+        let condition_text = condition.trim();
+        let (operand, rest) = if let Some(rest) = condition_text.strip_prefix("text") {
+            (ConditionOperand::Text, rest)
+        } else if let Some(rest) = condition_text.strip_prefix('@') {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "<>=!".contains(c))
+                .ok_or_else(invalid)?;
+            if end == 0 {
+                return Err(invalid());
+            }
+            (ConditionOperand::Attribute(rest[..end].to_string()), &rest[end..])
+        } else {
+            return Err(invalid());
+        };
+
+        let rest = rest.trim_start();
+        let (comparison, value) = [
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+            ("=", Comparison::Eq),
+        ]
+        .iter()
+        .find_map(|(op, comparison)| rest.strip_prefix(op).map(|value| (*comparison, value)))
+        .ok_or_else(invalid)?;
+
+        let value = value.trim();
+        let value = ['\'', '"']
+            .iter()
+            .find_map(|&q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+            .unwrap_or(value);
+        if value.is_empty() && !matches!(comparison, Comparison::Eq | Comparison::Ne) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            operand,
+            comparison,
+            value: value.to_string(),
+        })
+    }
+
+    /// Whether `element` satisfies the condition
+    #[must_use]
+    pub fn matches(&self, element: &XmlElement) -> bool {
+        let actual = match &self.operand {
+            ConditionOperand::Text => element.text.as_deref().unwrap_or_default(),
+            ConditionOperand::Attribute(name) => {
+                match element.attributes.iter().find(|(k, _)| k == name) {
+                    Some((_, v)) => v.as_str(),
+                    None => return false,
+                }
+            }
+        };
+
+        let actual = actual.trim();
+        let ordering = match (actual.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => match self.comparison {
+                Comparison::Eq | Comparison::Ne => Some(actual.cmp(&self.value)),
+                _ => None,
+            },
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self.comparison {
+            Comparison::Lt => ordering.is_lt(),
+            Comparison::Le => ordering.is_le(),
+            Comparison::Gt => ordering.is_gt(),
+            Comparison::Ge => ordering.is_ge(),
+            Comparison::Eq => ordering.is_eq(),
+            Comparison::Ne => ordering.is_ne(),
+        }
+    }
+}
+
 /// Finish writing and convert to string
 fn finish_writer(writer: Writer<Cursor<Vec<u8>>>) -> Result<String> {
     let result = writer.into_inner().into_inner();
@@ -597,6 +1643,7 @@ fn finish_writer(writer: Writer<Cursor<Vec<u8>>>) -> Result<String> {
 }
 
 #[cfg(test)]
+#[allow(clippy::similar_names)]
 mod tests {
     use super::*;
 
@@ -628,14 +1675,128 @@ mod tests {
 
         let modifier = XmlModifier::from_string(xml.to_string());
 
-        let items = modifier.query("item").expect("query");
+        let (items, total) = modifier.query("item", 0, MAX_XML_ELEMENTS).expect("query");
         assert_eq!(items.len(), 2);
+        assert_eq!(total, 2);
 
-        let item1 = modifier.query("item[@id='1']").expect("query");
+        let (page, total) = modifier.query("item", 1, 5).expect("query");
+        assert_eq!(total, 2);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].text.as_deref(), Some("Second"));
+        assert!(modifier.query("item", 2, 5).expect("query").0.is_empty());
+
+        let (item1, _) = modifier.query("item[@id='1']", 0, MAX_XML_ELEMENTS).expect("query");
         assert_eq!(item1.len(), 1);
         assert_eq!(item1[0].text.as_deref(), Some("First"));
     }
 
+    #[test]
+    fn test_get_attribute() {
+        let xml = r#"<root><item id="1" status="open"/><item id="2"/></root>"#;
+        let modifier = XmlModifier::from_string(xml.to_string());
+
+        let status = |pattern: &str| modifier.get_attribute(pattern, "status").expect("query");
+        assert_eq!(status("item").as_deref(), Some("open"));
+        assert_eq!(status("item[@id='2']"), None);
+        assert_eq!(status("missing"), None);
+    }
+
+    #[test]
+    fn test_query_by_depth() {
+        let xml = r#"<root>
+  <items>
+    <item id="1"><name>A</name></item>
+    <item id="2">B</item>
+  </items>
+  <meta/>
+</root>"#;
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+
+        let roots = modifier.query_by_depth(0).expect("query");
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].name, "root");
+
+        let level1: Vec<String> = modifier
+            .query_by_depth(1)
+            .expect("query")
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(level1, vec!["items", "meta"]);
+
+        assert_eq!(modifier.query_by_depth(2).expect("query").len(), 2);
+        assert!(modifier.query_by_depth(9).expect("query").is_empty());
+    }
+
+    #[test]
+    fn test_get_source() {
+        let xml = r#"<root>
+  <item id="1">Plain</item>
+  <item id="2">
+    <!-- note -->
+    Mixed <b>bold</b> &amp; <![CDATA[<raw>]]>
+  </item>
+  <flag id="3"/>
+</root>"#;
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+
+        let source = modifier.get_source("item[@id='2']").expect("source");
+        assert_eq!(
+            source.as_deref(),
+            Some(
+                "<item id=\"2\">\n    <!-- note -->\n    Mixed <b>bold</b> &amp; \
+                 <![CDATA[<raw>]]>\n  </item>"
+            )
+        );
+        assert_eq!(
+            modifier.get_source("item").expect("source").as_deref(),
+            Some("<item id=\"1\">Plain</item>")
+        );
+        assert_eq!(
+            modifier.get_source("root/flag").expect("source").as_deref(),
+            Some("<flag id=\"3\"/>")
+        );
+        assert!(modifier.get_source("missing").expect("source").is_none());
+    }
+
+    #[test]
+    fn test_element_context() {
+        let xml = r#"<root>
+  <items>
+    <item id="1"><name>A</name></item>
+    <item id="2">B</item>
+    <item id="3">C</item>
+  </items>
+</root>"#;
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+        let ctx = modifier
+            .element_context("item[@id='2']")
+            .expect("context")
+            .expect("element found");
+
+        assert_eq!(ctx.parent.map(|p| p.path), Some("root/items".to_string()));
+        assert_eq!(
+            ctx.preceding_sibling.and_then(|e| e.attributes.first().cloned()),
+            Some(("id".to_string(), "1".to_string()))
+        );
+        assert_eq!(
+            ctx.following_sibling.and_then(|e| e.text),
+            Some("C".to_string())
+        );
+
+        let first = modifier
+            .element_context("items")
+            .expect("context")
+            .expect("element found");
+        assert!(first.preceding_sibling.is_none());
+        assert!(first.following_sibling.is_none());
+
+        assert!(modifier.element_context("missing").expect("context").is_none());
+    }
+
     #[test]
     fn test_update_text() {
         let xml = r#"<?xml version="1.0"?>
@@ -650,6 +1811,28 @@ mod tests {
         assert!(modifier.get_content().contains("New"));
     }
 
+    #[test]
+    fn test_cdata() {
+        let xml = "<root><script><![CDATA[a < b]]></script><note>plain</note>\
+                   <other><![CDATA[x & y]]></other></root>";
+        let modifier = XmlModifier::from_string(xml.to_string());
+
+        let script = modifier.get_element("root/script").expect("query").expect("found");
+        assert!(script.cdata);
+        assert_eq!(script.text.as_deref(), Some("a < b"));
+        assert!(!modifier.get_element("root/note").expect("query").expect("found").cdata);
+
+        assert!(modifier.update_text("script", "c < d").expect("update"));
+        assert!(modifier.set_cdata("note", "<b>bold</b>").expect("set cdata"));
+        assert!(modifier.set_cdata("note", "bad ]]> end").is_err());
+
+        assert_eq!(
+            modifier.get_content(),
+            "<root><script><![CDATA[c < d]]></script><note><![CDATA[<b>bold</b>]]></note>\
+             <other><![CDATA[x & y]]></other></root>"
+        );
+    }
+
     #[test]
     fn test_set_attribute() {
         let xml = r#"<?xml version="1.0"?>
@@ -666,6 +1849,133 @@ mod tests {
         assert!(modifier.get_content().contains("status=\"active\""));
     }
 
+    #[test]
+    fn test_attribute_escaping_roundtrip() {
+        let value = "say \"hi\" & <bye>\n\tit's done";
+        let modifier = XmlModifier::from_string(r#"<root><item id="1"/></root>"#.to_string());
+
+        assert!(modifier.set_attribute("item", "note", value).expect("set attr"));
+        let attrs = vec![("label".to_string(), value.to_string())];
+        assert!(modifier.insert_element("root", "extra", &attrs, None).expect("insert"));
+
+        // Reparse from scratch so nothing depends on the modifier's own reading
+        let content = modifier.get_content();
+        let reparsed = XmlModifier::from_string(content.clone());
+        let attribute = |path, name| reparsed.get_attribute(path, name).expect("query");
+        assert_eq!(attribute("item", "note").as_deref(), Some(value));
+        assert_eq!(attribute("extra", "label").as_deref(), Some(value));
+
+        let mut reader = Reader::from_str(&content);
+        let mut values = Vec::new();
+        loop {
+            match reader.read_event().expect("well-formed XML") {
+                Event::Eof => break,
+                Event::Start(e) | Event::Empty(e) => {
+                    for attr in e.attributes() {
+                        let attr = attr.expect("valid attribute");
+                        values.push(attr.unescape_value().expect("unescape").into_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(values.iter().filter(|v| *v == value).count(), 2);
+
+        // Filters match on the unescaped value
+        let filter = "item[@note='say \"hi\" & <bye>\n\tit's done']";
+        assert!(modifier.set_attribute(filter, "seen", "1").expect("set attr"));
+    }
+
+    #[test]
+    fn test_set_attributes_merge_and_replace() {
+        let xml = r#"<?xml version="1.0"?>
+<root>
+  <item id="1" status="old" color="red">Test</item>
+</root>"#;
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+        let modified = modifier
+            .set_attributes(
+                "item[@id='1']",
+                &[
+                    ("status".to_string(), "new".to_string()),
+                    ("size".to_string(), "L".to_string()),
+                ],
+                false,
+            )
+            .expect("merge attrs");
+
+        assert!(modified);
+        let content = modifier.get_content();
+        assert!(content.contains(r#"<item id="1" status="new" color="red" size="L">"#));
+
+        let modified = modifier
+            .set_attributes("item", &[("id".to_string(), "2".to_string())], true)
+            .expect("replace attrs");
+
+        assert!(modified);
+        let content = modifier.get_content();
+        assert!(content.contains(r#"<item id="2">Test</item>"#));
+    }
+
+    #[test]
+    fn test_conditional_modify() {
+        let modifier = XmlModifier::from_string(
+            concat!(
+                r#"<root><item qty="0"><name>a</name></item>"#,
+                r#"<item qty="5"/><item qty="12">c</item></root>"#
+            )
+            .to_string(),
+        );
+        let over = ElementCondition::parse("@qty > 3").expect("parse");
+        let set_flag = ConditionalOperation::SetAttribute {
+            name: "restock".to_string(),
+            value: "no".to_string(),
+        };
+        assert_eq!(modifier.conditional_modify("item", &over, &set_flag).expect("modify"), 2);
+        let content = modifier.get_content();
+        assert!(content.contains(r#"<item qty="5" restock="no"/>"#));
+        assert!(content.contains(r#"<item qty="12" restock="no">c</item>"#));
+        assert!(content.contains(r#"<item qty="0"><name>"#));
+
+        let text = ConditionalOperation::UpdateText("many".to_string());
+        let at_least = ElementCondition::parse("@qty>=5").expect("parse");
+        assert_eq!(modifier.conditional_modify("item", &at_least, &text).expect("modify"), 2);
+        assert!(modifier.get_content().contains(r#"restock="no">many</item>"#));
+        assert!(!modifier.get_content().contains(">c<"));
+
+        let empty = ElementCondition::parse("@qty == '0'").expect("parse");
+        let delete = ConditionalOperation::Delete;
+        assert_eq!(modifier.conditional_modify("item", &empty, &delete).expect("modify"), 1);
+        assert!(!modifier.get_content().contains("<name>"));
+        assert_eq!(modifier.conditional_modify("item", &empty, &delete).expect("modify"), 0);
+
+        // Replacing text next to child elements would make mixed content
+        let nested = XmlModifier::from_string(
+            "<root><item>\n  <name>b</name></item><item>x</item></root>".to_string(),
+        );
+        let any = ElementCondition::parse("text != 'y'").expect("parse");
+        assert!(nested.conditional_modify("item", &any, &text).is_err());
+        assert!(nested.get_content().contains("<item>\n  <name>b</name></item>"));
+
+        let name = ElementCondition::parse("text != 'x'").expect("parse");
+        assert_eq!(name.operand, ConditionOperand::Text);
+        assert_eq!(name.comparison, Comparison::Ne);
+        assert!(ElementCondition::parse("price > 3").is_err());
+        assert!(ElementCondition::parse("@qty ~ 3").is_err());
+        // Non-numeric values only support equality
+        let element = XmlElement {
+            path: "name".to_string(),
+            name: "name".to_string(),
+            attributes: Vec::new(),
+            text: Some("a".to_string()),
+            cdata: false,
+            depth: 0,
+        };
+        assert!(!ElementCondition::parse("text < b").expect("parse").matches(&element));
+        assert!(ElementCondition::parse("text == a").expect("parse").matches(&element));
+    }
+
     #[test]
     fn test_delete_element() {
         let xml = r#"<?xml version="1.0"?>
@@ -706,6 +2016,155 @@ mod tests {
         assert!(content.contains("<item id=\"new\">New item</item>"));
     }
 
+    #[test]
+    fn test_append_records() {
+        let modifier =
+            XmlModifier::from_string("<log>\n  <entry n=\"1\"/>\n</log>".to_string());
+        let records: Vec<NewElement> = (2..=3)
+            .map(|n| NewElement {
+                name: "entry".to_string(),
+                attributes: vec![("n".to_string(), n.to_string())],
+                text: (n == 3).then(|| "a < b".to_string()),
+            })
+            .collect();
+
+        assert!(modifier.append_records("log", &records).expect("append"));
+        assert_eq!(
+            modifier.get_content(),
+            "<log>\n  <entry n=\"1\"/>\n\n    <entry n=\"2\"/>\n    \
+             <entry n=\"3\">a &lt; b</entry>\n  </log>"
+        );
+        assert!(!modifier.append_records("missing", &records).expect("append"));
+        assert!(!modifier.append_records("log", &[]).expect("append"));
+    }
+
+    #[test]
+    fn test_wrap_elements() {
+        let xml = "<root>\n  <item id=\"1\"/>\n  <item id=\"2\">b</item>\n  <note/>\n  \
+                   <item id=\"3\"/>\n</root>";
+        let attrs = [("name".to_string(), "g".to_string())];
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+        assert_eq!(modifier.wrap_elements("item", "group", &attrs, false, false).expect("wrap"), 1);
+        assert!(modifier
+            .get_content()
+            .contains("<group name=\"g\"><item id=\"1\"/></group>\n  <item id=\"2\">"));
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+        assert_eq!(modifier.wrap_elements("item", "group", &[], true, false).expect("wrap"), 3);
+        assert_eq!(modifier.get_content().matches("<group>").count(), 3);
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+        assert_eq!(modifier.wrap_elements("item", "group", &[], true, true).expect("wrap"), 3);
+        assert_eq!(
+            modifier.get_content(),
+            "<root>\n  <group><item id=\"1\"/>\n  <item id=\"2\">b</item></group>\n  <note/>\n  \
+             <group><item id=\"3\"/></group>\n</root>"
+        );
+
+        assert_eq!(modifier.wrap_elements("missing", "group", &[], true, true).expect("wrap"), 0);
+        assert!(modifier.wrap_elements("item", "bad name", &[], false, false).is_err());
+    }
+
+    #[test]
+    fn test_wrap_elements_first_group() {
+        // Without `all`, the whole first group is wrapped and counted
+        let modifier = XmlModifier::from_string("<root><item>a</item><item>b</item></root>".into());
+        assert_eq!(modifier.wrap_elements("item", "g", &[], false, true).expect("wrap"), 2);
+        assert_eq!(modifier.get_content(), "<root><g><item>a</item><item>b</item></g></root>");
+
+        let xml = "<root>\n  <item/>\n  <item>b</item>\n  <note/>\n  <item/>\n</root>";
+        let modifier = XmlModifier::from_string(xml.to_string());
+        assert_eq!(modifier.wrap_elements("item", "g", &[], false, true).expect("wrap"), 2);
+        assert_eq!(
+            modifier.get_content(),
+            "<root>\n  <g><item/>\n  <item>b</item></g>\n  <note/>\n  <item/>\n</root>"
+        );
+    }
+
+    #[test]
+    fn test_rename_element() {
+        let xml = "<root><item id=\"1\"><item id=\"inner\">x</item></item><item id=\"2\"/></root>";
+        let modifier = XmlModifier::from_string(xml.to_string());
+
+        assert!(modifier.rename_element("item[@id='1']", "entry").expect("rename"));
+        assert!(modifier.rename_element("item[@id='2']", "entry").expect("rename"));
+        assert_eq!(
+            modifier.get_content(),
+            "<root><entry id=\"1\"><item id=\"inner\">x</item></entry><entry id=\"2\"/></root>"
+        );
+
+        assert!(!modifier.rename_element("missing", "entry").expect("rename"));
+        assert!(modifier.rename_element("entry", "bad name").is_err());
+    }
+
+    #[test]
+    fn test_untouched_empty_elements_preserved() {
+        let xml = "<root><items/><p>Old<br/>line</p></root>";
+
+        let modifier = XmlModifier::from_string(xml.to_string());
+        let modified = modifier
+            .insert_element("items", "item", &[], Some("x"))
+            .expect("insert");
+
+        assert!(modified);
+        let content = modifier.render().expect("render");
+        assert!(content.contains("<br/>"));
+        assert!(content.contains("<item>x</item>"));
+    }
+
+    #[test]
+    fn test_empty_element_styles() {
+        let xml = r#"<root><a x="1"/><b></b><c>text</c></root>"#;
+
+        let expanded = XmlModifier::from_string(xml.to_string())
+            .with_empty_element_style(Some(EmptyElementStyle::Expanded))
+            .render()
+            .expect("render");
+        assert_eq!(expanded, r#"<root><a x="1"></a><b></b><c>text</c></root>"#);
+
+        let self_closing = XmlModifier::from_string(xml.to_string())
+            .with_empty_element_style(Some(EmptyElementStyle::SelfClosing))
+            .render()
+            .expect("render");
+        assert_eq!(self_closing, r#"<root><a x="1"/><b/><c>text</c></root>"#);
+    }
+
+    #[test]
+    fn test_reformat() {
+        let xml = "<?xml version=\"1.0\"?>\n<root>\n<a>  keep  me </a>   <!-- note -->\n\
+                   <b><![CDATA[<raw> & stuff]]></b><c x=\"1\"/></root>\n";
+        let modifier = XmlModifier::from_string(xml.to_string());
+
+        modifier.reformat("  ").expect("reformat");
+        assert_eq!(
+            modifier.get_content(),
+            "<?xml version=\"1.0\"?>\n<root>\n  <a>  keep  me </a>\n  <!-- note -->\n  \
+             <b><![CDATA[<raw> & stuff]]></b>\n  <c x=\"1\"/>\n</root>\n"
+        );
+
+        modifier.reformat("\t").expect("reformat");
+        assert!(modifier.get_content().contains("\n\t<a>"));
+        assert!(modifier.reformat("-").is_err());
+    }
+
+    #[test]
+    fn test_text_preview_multibyte() {
+        // 48 ASCII bytes then 4-byte emoji: byte 50 falls inside the first emoji
+        let emoji = format!("{}{}", "a".repeat(48), "😀".repeat(5));
+        let accented = format!("{}é{}", "b".repeat(49), "è".repeat(5));
+        let xml = format!("<root><x>{emoji}</x><y>{accented}</y></root>");
+        let modifier = XmlModifier::from_string(xml);
+
+        let (x, _) = modifier.query("x", 0, 1).expect("query");
+        let expected = format!("{}😀😀...", "a".repeat(48));
+        assert!(x[0].display().ends_with(&format!(": \"{expected}\"")));
+
+        let (y, _) = modifier.query("y", 0, 1).expect("query");
+        let expected = format!("{}é...", "b".repeat(49));
+        assert!(y[0].display().ends_with(&format!(": \"{expected}\"")));
+    }
+
     #[test]
     fn test_parse_pattern() {
         let (path, filter) = parse_pattern("item[@id='123']");
@@ -716,4 +2175,39 @@ mod tests {
         assert_eq!(path, "root/items/item");
         assert!(filter.is_none());
     }
+
+    #[test]
+    fn test_latin1_declaration_roundtrip() {
+        let decl = br#"<?xml version="1.0" encoding="ISO-8859-1" standalone="yes"?>"#;
+        let mut original = decl.to_vec();
+        original.extend(b"\n<root>\n  <item name=\"caf\xe9\">cr\xe8me</item>\n</root>\n");
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let path = dir.path().join("latin1.xml");
+        fs::write(&path, &original).expect("write");
+
+        let modifier = XmlModifier::from_file(&path).expect("load");
+        assert_eq!(modifier.get_attribute("item", "name").expect("get"), Some("café".into()));
+        assert!(modifier.set_attribute("item", "note", "naïve €").expect("set"));
+
+        XmlModifier::write_to_file(&modifier.encode(&modifier.render().expect("render")), &path)
+            .expect("write");
+        let written = fs::read(&path).expect("read");
+        assert!(written.starts_with(decl));
+        let expected = b"<item name=\"caf\xe9\" note=\"na\xefve &#8364;\">cr\xe8me</item>";
+        assert!(written.windows(expected.len()).any(|w| w == expected));
+
+        // Without a Latin-1 declaration the input must be UTF-8
+        assert!(XmlModifier::from_bytes(b"<root>\xe9</root>".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_from_file_with_limit() {
+        let dir = tempfile::TempDir::new().expect("create temp dir");
+        let path = dir.path().join("doc.xml");
+        fs::write(&path, "<root><a/></root>").expect("write");
+
+        let modifier = XmlModifier::from_file_with_limit(&path, 17).expect("at the limit");
+        assert_eq!(modifier.len(), 17);
+        assert!(XmlModifier::from_file_with_limit(&path, 16).is_err());
+    }
 }